@@ -1,15 +1,28 @@
+use std::io::{BufRead, BufReader, Read};
 use std::process::{Command, Stdio};
 use std::time::Instant;
 
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
+use codex_driver_util::{ensure_executable, resolve_rule};
+use globset::Glob;
 use tracing::warn;
+use walkdir::WalkDir;
 use which::which;
 
+/// Env var naming the ast-grep binary to use instead of the one on `PATH`.
+pub const BINARY_ENV: &str = "AST_GREP_BIN";
+
+/// Matches kept in [`AstRunSummary::stdout`]; beyond this only
+/// [`AstRunSummary::total_matches`] keeps counting, so a sweeping rule
+/// cannot exhaust memory.
+pub const MAX_RETAINED_MATCHES: usize = 5_000;
+
 #[derive(Debug, Clone)]
 pub struct AstGrepDriver {
     binary: Utf8PathBuf,
     rules_dir: Utf8PathBuf,
+    ignore_globs: Vec<String>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -21,40 +34,252 @@ pub enum AstMode {
 #[derive(Debug, Clone)]
 pub struct AstRunSummary {
     pub mode: AstMode,
+    /// JSON array of at most [`MAX_RETAINED_MATCHES`] matches.
     pub stdout: String,
     pub stderr: String,
     pub duration_ms: u128,
+    /// Every match ast-grep reported, including those not retained.
+    pub total_matches: u64,
+}
+
+/// A single match parsed from ast-grep's `--json` output. Lines are 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AstMatch {
+    pub file: String,
+    pub start_line: u64,
+    pub end_line: u64,
+    pub rule_id: Option<String>,
+    pub message: Option<String>,
+}
+
+impl AstRunSummary {
+    /// Wraps a stored `--json` array such as a cached dry run.
+    pub fn from_json(mode: AstMode, stdout: String) -> Self {
+        let total_matches = serde_json::from_str::<Vec<serde_json::Value>>(&stdout)
+            .map_or(0, |values| values.len() as u64);
+        Self {
+            mode,
+            stdout,
+            stderr: String::new(),
+            duration_ms: 0,
+            total_matches,
+        }
+    }
+
+    /// Whether matches beyond [`MAX_RETAINED_MATCHES`] were dropped.
+    pub fn is_truncated(&self) -> bool {
+        self.total_matches > MAX_RETAINED_MATCHES as u64
+    }
+
+    /// Matches parsed from ast-grep's `--json` output; entries that do not
+    /// look like matches are ignored.
+    pub fn matches(&self) -> Vec<AstMatch> {
+        let Ok(values) = serde_json::from_str::<Vec<serde_json::Value>>(&self.stdout) else {
+            return Vec::new();
+        };
+        values.iter().filter_map(parse_match).collect()
+    }
+
+    /// Number of matches ast-grep reported, retained or not.
+    pub fn match_count(&self) -> u64 {
+        self.total_matches
+    }
+}
+
+/// Reads ast-grep's `--json=stream` NDJSON one line at a time, keeping the
+/// first `cap` matches and counting the rest. Returns the retained matches
+/// and the total.
+fn collect_stream(reader: impl BufRead, cap: usize) -> Result<(Vec<serde_json::Value>, u64)> {
+    let mut retained = Vec::new();
+    let mut total = 0;
+    for line in reader.lines() {
+        let line = line.context("reading ast-grep output")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<serde_json::Value>(&line) {
+            Ok(value) => {
+                total += 1;
+                if retained.len() < cap {
+                    retained.push(value);
+                }
+            }
+            Err(err) => warn!("skipping unparsable ast-grep output line: {err}"),
+        }
+    }
+    Ok((retained, total))
+}
+
+fn parse_match(value: &serde_json::Value) -> Option<AstMatch> {
+    let file = value.get("file")?.as_str()?.to_string();
+    let range = value.get("range")?;
+    let line = |key: &str| range.get(key)?.get("line")?.as_u64();
+    let text = |key: &str| value.get(key)?.as_str().map(str::to_string);
+    Some(AstMatch {
+        file,
+        start_line: line("start")? + 1,
+        end_line: line("end")? + 1,
+        rule_id: text("ruleId"),
+        message: text("message"),
+    })
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '[', '{'])
 }
 
 #[derive(Debug, Clone)]
 pub enum AstRunOutcome {
     Applied(AstRunSummary),
-    Skipped { reason: String },
+    /// ast-grep ran fine but reported no matches.
+    NoMatches {
+        duration_ms: u128,
+    },
+    /// ast-grep could not run (missing config or target, nonzero exit).
+    Skipped {
+        reason: String,
+    },
 }
 
 impl AstGrepDriver {
     pub fn detect(config_dir: &Utf8Path) -> Result<Option<Self>> {
+        Self::detect_with(config_dir, None)
+    }
+
+    /// Like [`detect`](Self::detect), but prefers `$AST_GREP_BIN` and then
+    /// `configured` over `PATH`. An explicit binary that is missing or not
+    /// executable is an error rather than a silent fallback.
+    pub fn detect_with(
+        config_dir: &Utf8Path,
+        configured: Option<&Utf8Path>,
+    ) -> Result<Option<Self>> {
         if !config_dir.exists() {
             return Ok(None);
         }
-        match which("ast-grep") {
-            Ok(path) => {
-                let binary = Utf8PathBuf::from_path_buf(path)
-                    .unwrap_or_else(|p| Utf8PathBuf::from(p.to_string_lossy().to_string()));
-                Ok(Some(Self {
-                    binary,
-                    rules_dir: config_dir.to_path_buf(),
-                }))
+        let explicit = match std::env::var(BINARY_ENV) {
+            Ok(path) if !path.is_empty() => Some((Utf8PathBuf::from(path), BINARY_ENV)),
+            _ => configured.map(|path| (path.to_path_buf(), "config")),
+        };
+        let binary = match explicit {
+            Some((path, source)) => {
+                ensure_executable(&path)
+                    .with_context(|| format!("ast-grep binary from {source}"))?;
+                path
             }
-            Err(_) => Ok(None),
-        }
+            None => match which("ast-grep") {
+                Ok(path) => Utf8PathBuf::from_path_buf(path)
+                    .unwrap_or_else(|p| Utf8PathBuf::from(p.to_string_lossy().to_string())),
+                Err(_) => return Ok(None),
+            },
+        };
+        Ok(Some(Self {
+            binary,
+            rules_dir: config_dir.to_path_buf(),
+            ignore_globs: Vec::new(),
+        }))
     }
 
     pub fn with_binary(binary: impl Into<Utf8PathBuf>, rules_dir: impl Into<Utf8PathBuf>) -> Self {
         Self {
             binary: binary.into(),
             rules_dir: rules_dir.into(),
+            ignore_globs: Vec::new(),
+        }
+    }
+
+    /// Excludes paths matching `globs` from every run via `--globs !<glob>`.
+    pub fn with_ignore_globs(mut self, globs: Vec<String>) -> Self {
+        self.ignore_globs = globs;
+        self
+    }
+
+    /// Rule configs for a registry entry. Glob patterns (`net/*.yml`) are
+    /// matched against paths relative to the rules dir and returned sorted;
+    /// anything else is a single path joined onto the rules dir. Absolute
+    /// paths and globs (`/shared/rules/*.yml`) are used as given.
+    pub fn expand_rules(&self, pattern: &str) -> Result<Vec<Utf8PathBuf>> {
+        if !is_glob(pattern) {
+            return Ok(vec![resolve_rule(&self.rules_dir, pattern)]);
         }
+        // An absolute glob is walked from its longest literal prefix.
+        let (base, pattern) = if Utf8Path::new(pattern).is_absolute() {
+            let path = Utf8Path::new(pattern);
+            let literal = path
+                .components()
+                .take_while(|c| !is_glob(c.as_str()))
+                .count();
+            let base: Utf8PathBuf = path.components().take(literal).collect();
+            let rest: Utf8PathBuf = path.components().skip(literal).collect();
+            (base, rest.into_string())
+        } else {
+            (self.rules_dir.clone(), pattern.to_string())
+        };
+        let matcher = Glob::new(&pattern)
+            .with_context(|| format!("invalid rule glob {pattern}"))?
+            .compile_matcher();
+        let mut rules: Vec<Utf8PathBuf> = WalkDir::new(&base)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter_map(|e| Utf8PathBuf::from_path_buf(e.into_path()).ok())
+            .filter(|path| {
+                path.strip_prefix(&base)
+                    .is_ok_and(|rel| matcher.is_match(rel.as_std_path()))
+            })
+            .collect();
+        rules.sort();
+        Ok(rules)
+    }
+
+    /// Runs each config in turn and merges their `--json` output into one
+    /// array. Stops at the first skipped config; an empty list is skipped
+    /// with "glob matched no rules".
+    pub fn run_many(
+        &self,
+        config_paths: &[Utf8PathBuf],
+        target: &Utf8Path,
+        mode: AstMode,
+    ) -> Result<AstRunOutcome> {
+        if let [single] = config_paths {
+            return self.run_with_config(single, target, mode);
+        }
+        if config_paths.is_empty() {
+            return Ok(AstRunOutcome::Skipped {
+                reason: "glob matched no rules".into(),
+            });
+        }
+        let mut matches = Vec::new();
+        let mut total_matches = 0;
+        let mut stderr = String::new();
+        let mut duration_ms = 0;
+        for config_path in config_paths {
+            match self.run_with_config(config_path, target, mode)? {
+                AstRunOutcome::Applied(summary) => {
+                    match serde_json::from_str::<Vec<serde_json::Value>>(&summary.stdout) {
+                        Ok(values) => {
+                            let room = MAX_RETAINED_MATCHES.saturating_sub(matches.len());
+                            matches.extend(values.into_iter().take(room));
+                        }
+                        Err(_) => warn!("ast-grep output for {config_path} is not a JSON array"),
+                    }
+                    total_matches += summary.total_matches;
+                    stderr.push_str(&summary.stderr);
+                    duration_ms += summary.duration_ms;
+                }
+                AstRunOutcome::NoMatches { duration_ms: ms } => duration_ms += ms,
+                skipped => return Ok(skipped),
+            }
+        }
+        if total_matches == 0 {
+            return Ok(AstRunOutcome::NoMatches { duration_ms });
+        }
+        Ok(AstRunOutcome::Applied(AstRunSummary {
+            mode,
+            stdout: serde_json::to_string(&matches)?,
+            stderr,
+            duration_ms,
+            total_matches,
+        }))
     }
 
     pub fn run(&self, target: &Utf8Path, mode: AstMode) -> Result<AstRunOutcome> {
@@ -82,8 +307,11 @@ impl AstGrepDriver {
         cmd.arg("run")
             .arg("--config")
             .arg(config_path)
-            .arg("--json")
-            .arg(target)
+            .arg("--json=stream");
+        for glob in &self.ignore_globs {
+            cmd.arg("--globs").arg(format!("!{glob}"));
+        }
+        cmd.arg(target)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped());
 
@@ -95,27 +323,76 @@ impl AstGrepDriver {
         }
 
         let start = Instant::now();
-        let output = cmd
-            .output()
+        let mut child = cmd
+            .spawn()
             .with_context(|| format!("running ast-grep via {}", self.binary))?;
+        // Drain stderr on its own thread so a chatty ast-grep cannot block
+        // on a full pipe while stdout is being streamed.
+        let mut stderr_pipe = child.stderr.take().context("ast-grep stderr not piped")?;
+        let stderr_reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stderr_pipe.read_to_end(&mut buf);
+            String::from_utf8_lossy(&buf).into_owned()
+        });
+        let stdout = child.stdout.take().context("ast-grep stdout not piped")?;
+        let collected = collect_stream(BufReader::new(stdout), MAX_RETAINED_MATCHES);
+        let status = child.wait().context("waiting for ast-grep")?;
+        let stderr = stderr_reader.join().unwrap_or_default();
         let duration_ms = start.elapsed().as_millis();
+        let (retained, total_matches) = collected?;
 
-        if !output.status.success() {
-            warn!(
-                "ast-grep exited with {}; stderr: {}",
-                output.status,
-                String::from_utf8_lossy(&output.stderr)
-            );
+        if !status.success() {
+            warn!("ast-grep exited with {status}; stderr: {stderr}");
             return Ok(AstRunOutcome::Skipped {
-                reason: format!("ast-grep exit {}", output.status),
+                reason: format!("ast-grep exit {status}"),
             });
         }
-
+        if total_matches == 0 {
+            return Ok(AstRunOutcome::NoMatches { duration_ms });
+        }
+        if total_matches > retained.len() as u64 {
+            warn!(
+                "ast-grep reported {total_matches} matches for {config_path}; keeping the first {}",
+                retained.len()
+            );
+        }
         Ok(AstRunOutcome::Applied(AstRunSummary {
             mode,
-            stdout: String::from_utf8_lossy(&output.stdout).into(),
-            stderr: String::from_utf8_lossy(&output.stderr).into(),
+            stdout: serde_json::to_string(&retained)?,
+            stderr,
             duration_ms,
+            total_matches,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn absolute_rules_ignore_the_rules_dir() {
+        let shared = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let driver = AstGrepDriver::with_binary("ast-grep", "/elsewhere/rules");
+        let cargo = shared.join("Cargo.toml");
+        assert_eq!(
+            driver.expand_rules(cargo.as_str()).unwrap(),
+            std::slice::from_ref(&cargo)
+        );
+        assert_eq!(
+            driver.expand_rules("net/a.yml").unwrap(),
+            [Utf8PathBuf::from("/elsewhere/rules/net/a.yml")]
+        );
+        let glob = format!("{shared}/*.toml");
+        assert_eq!(driver.expand_rules(&glob).unwrap(), [cargo]);
+    }
+
+    #[test]
+    fn stream_keeps_the_first_matches_and_counts_the_rest() {
+        let ndjson = "{\"file\":\"a.rs\"}\n\n{\"file\":\"b.rs\"}\nnot json\n{\"file\":\"c.rs\"}\n";
+        let (retained, total) = collect_stream(ndjson.as_bytes(), 2).unwrap();
+        assert_eq!(total, 3);
+        assert_eq!(retained.len(), 2);
+        assert_eq!(retained[1]["file"], "b.rs");
+    }
+}
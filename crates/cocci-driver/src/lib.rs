@@ -3,22 +3,59 @@ use std::process::Command;
 
 use anyhow::{Context, Result};
 use camino::{FromPathBufError, Utf8Path, Utf8PathBuf};
+use codex_driver_util::ensure_executable;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use tracing::warn;
+use walkdir::WalkDir;
 use which::which;
 
+/// Env var naming the coccinelle-for-rust binary to use instead of the one
+/// on `PATH`.
+pub const BINARY_ENV: &str = "COCCINELLE_BIN";
+
+/// Bytes of target paths passed per invocation; ignore globs turn the target
+/// into one argument per file, and this keeps argv well below the 128 KiB
+/// floor Linux guarantees for arguments plus environment.
+const MAX_TARGET_ARG_BYTES: usize = 96 * 1024;
+
 #[derive(Debug, Clone)]
 pub struct CocciDriver {
     binary: Utf8PathBuf,
     rules_dir: Utf8PathBuf,
+    ignore: Option<GlobSet>,
+    iso_file: Option<Utf8PathBuf>,
+    macro_file: Option<Utf8PathBuf>,
+}
+
+/// Outcome of one rule, from coccinelle-for-rust's exit code: 0 applied a
+/// change, 1 matched nothing, anything else (or no code) is an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CocciStatus {
+    Applied,
+    NoChange,
+    Error,
+}
+
+impl CocciStatus {
+    pub fn from_exit_code(code: Option<i32>) -> Self {
+        match code {
+            Some(0) => Self::Applied,
+            Some(1) => Self::NoChange,
+            _ => Self::Error,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CocciRuleReport {
     pub rule: Utf8PathBuf,
+    /// Arguments passed before the target files, e.g. `--patch <rule>
+    /// --iso-file <path>`.
+    pub args: Vec<String>,
     pub exit_code: Option<i32>,
     pub stdout: String,
     pub stderr: String,
-    pub success: bool,
+    pub status: CocciStatus,
 }
 
 #[derive(Debug, Clone)]
@@ -33,26 +70,119 @@ impl CocciDriver {
     }
 
     pub fn detect(rules_dir: &Utf8Path) -> Result<Option<Self>> {
+        Self::detect_with(rules_dir, None)
+    }
+
+    /// Like [`detect`](Self::detect), but prefers `$COCCINELLE_BIN` and then
+    /// `configured` over `PATH`. An explicit binary that is missing or not
+    /// executable is an error rather than a silent fallback.
+    pub fn detect_with(
+        rules_dir: &Utf8Path,
+        configured: Option<&Utf8Path>,
+    ) -> Result<Option<Self>> {
         if !rules_dir.exists() {
             return Ok(None);
         }
-        match which("coccinelle-for-rust") {
-            Ok(path) => {
-                let binary = Utf8PathBuf::try_from(path).unwrap_or_else(Self::fallback_utf8_path);
-                Ok(Some(Self {
-                    binary,
-                    rules_dir: rules_dir.to_path_buf(),
-                }))
+        let explicit = match std::env::var(BINARY_ENV) {
+            Ok(path) if !path.is_empty() => Some((Utf8PathBuf::from(path), BINARY_ENV)),
+            _ => configured.map(|path| (path.to_path_buf(), "config")),
+        };
+        let binary = match explicit {
+            Some((path, source)) => {
+                ensure_executable(&path)
+                    .with_context(|| format!("coccinelle-for-rust binary from {source}"))?;
+                path
             }
-            Err(_) => Ok(None),
-        }
+            None => match which("coccinelle-for-rust") {
+                Ok(path) => Utf8PathBuf::try_from(path).unwrap_or_else(Self::fallback_utf8_path),
+                Err(_) => return Ok(None),
+            },
+        };
+        Ok(Some(Self {
+            binary,
+            rules_dir: rules_dir.to_path_buf(),
+            ignore: None,
+            iso_file: None,
+            macro_file: None,
+        }))
     }
 
     pub fn with_binary(binary: impl Into<Utf8PathBuf>, rules_dir: impl Into<Utf8PathBuf>) -> Self {
         Self {
             binary: binary.into(),
             rules_dir: rules_dir.into(),
+            ignore: None,
+            iso_file: None,
+            macro_file: None,
+        }
+    }
+
+    /// Shared isomorphisms passed to every rule as `--iso-file`.
+    pub fn with_iso_file(mut self, path: Option<Utf8PathBuf>) -> Self {
+        self.iso_file = path;
+        self
+    }
+
+    /// Shared macro definitions passed to every rule as `--macro-file`.
+    pub fn with_macro_file(mut self, path: Option<Utf8PathBuf>) -> Self {
+        self.macro_file = path;
+        self
+    }
+
+    /// Configured include files that do not exist; they are left out of
+    /// the invocation so callers can warn instead of failing every rule.
+    pub fn missing_includes(&self) -> Vec<&Utf8Path> {
+        [&self.iso_file, &self.macro_file]
+            .into_iter()
+            .flatten()
+            .filter(|path| !path.exists())
+            .map(Utf8PathBuf::as_path)
+            .collect()
+    }
+
+    fn rule_args(&self, rule: &Utf8Path) -> Vec<String> {
+        let mut args = vec!["--patch".to_string(), rule.to_string()];
+        for (flag, path) in [
+            ("--iso-file", &self.iso_file),
+            ("--macro-file", &self.macro_file),
+        ] {
+            if let Some(path) = path.as_ref().filter(|path| path.exists()) {
+                args.push(flag.to_string());
+                args.push(path.to_string());
+            }
         }
+        args
+    }
+
+    /// Skips files under the target whose relative path matches one of
+    /// `globs`; the remaining `.rs` files are passed explicitly.
+    pub fn with_ignore_globs(mut self, globs: &[String]) -> Result<Self> {
+        if globs.is_empty() {
+            self.ignore = None;
+            return Ok(self);
+        }
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            builder.add(Glob::new(glob).with_context(|| format!("invalid ignore glob {glob}"))?);
+        }
+        self.ignore = Some(builder.build()?);
+        Ok(self)
+    }
+
+    /// The target itself, or its non-ignored `.rs` files when ignore globs
+    /// are set, split into batches of at most [`MAX_TARGET_ARG_BYTES`].
+    fn targets(&self, target: &Utf8Path) -> Vec<Vec<Utf8PathBuf>> {
+        let Some(ignore) = &self.ignore else {
+            return vec![vec![target.to_path_buf()]];
+        };
+        let files = WalkDir::new(target)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter_map(|entry| Utf8PathBuf::try_from(entry.into_path()).ok())
+            .filter(|path| path.extension() == Some("rs"))
+            .filter(|path| !ignore.is_match(path.strip_prefix(target).unwrap_or(path)));
+        batch_by_bytes(files, MAX_TARGET_ARG_BYTES)
     }
 
     pub fn run(&self, target: &Utf8Path) -> Result<CocciSummary> {
@@ -68,36 +198,123 @@ impl CocciDriver {
             if path.extension() != Some("cocci") {
                 continue;
             }
-            let output = Command::new(&self.binary)
-                .arg("--patch")
-                .arg(&path)
-                .arg(target)
-                .output();
-            match output {
-                Ok(out) => {
-                    reports.push(CocciRuleReport {
-                        rule: path.clone(),
-                        exit_code: out.status.code(),
-                        stdout: String::from_utf8_lossy(&out.stdout).into(),
-                        stderr: String::from_utf8_lossy(&out.stderr).into(),
-                        success: out.status.success(),
-                    });
-                    if !out.status.success() {
-                        warn!("coccinelle rule {} failed: {}", path, out.status);
+            reports.push(self.run_rule(&path, target)?);
+        }
+        Ok(CocciSummary { reports })
+    }
+
+    /// Runs `rule` over `target`, one invocation per target batch. The
+    /// report merges the batches: any error wins, then any applied change.
+    pub fn run_rule(&self, rule: &Utf8Path, target: &Utf8Path) -> Result<CocciRuleReport> {
+        let args = self.rule_args(rule);
+        let mut report = CocciRuleReport {
+            rule: rule.to_path_buf(),
+            args: args.clone(),
+            exit_code: Some(1),
+            stdout: String::new(),
+            stderr: String::new(),
+            status: CocciStatus::NoChange,
+        };
+        for batch in self.targets(target) {
+            let (exit_code, status) =
+                match Command::new(&self.binary).args(&args).args(&batch).output() {
+                    Ok(out) => {
+                        let status = CocciStatus::from_exit_code(out.status.code());
+                        if status == CocciStatus::Error {
+                            warn!("coccinelle rule {} failed: {}", rule, out.status);
+                        }
+                        report
+                            .stdout
+                            .push_str(&String::from_utf8_lossy(&out.stdout));
+                        report
+                            .stderr
+                            .push_str(&String::from_utf8_lossy(&out.stderr));
+                        (out.status.code(), status)
+                    }
+                    Err(err) => {
+                        warn!("failed to run coccinelle on {}: {err}", rule);
+                        report.stderr.push_str(&err.to_string());
+                        (None, CocciStatus::Error)
                     }
-                }
-                Err(err) => {
-                    reports.push(CocciRuleReport {
-                        rule: path.clone(),
-                        exit_code: None,
-                        stdout: String::new(),
-                        stderr: err.to_string(),
-                        success: false,
-                    });
-                    warn!("failed to run coccinelle on {}: {err}", path);
-                }
+                };
+            if report.status != CocciStatus::Error
+                && (status == CocciStatus::Error || status == CocciStatus::Applied)
+            {
+                report.status = status;
+                report.exit_code = exit_code;
+            }
+            if status == CocciStatus::Error && exit_code.is_none() {
+                // The binary could not be spawned; later batches would fail too.
+                break;
             }
         }
-        Ok(CocciSummary { reports })
+        Ok(report)
+    }
+}
+
+/// Splits `paths` into consecutive batches whose lengths sum to at most
+/// `max_bytes`; a single longer path still gets a batch of its own.
+fn batch_by_bytes(
+    paths: impl IntoIterator<Item = Utf8PathBuf>,
+    max_bytes: usize,
+) -> Vec<Vec<Utf8PathBuf>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut bytes = 0;
+    for path in paths {
+        let len = path.as_str().len() + 1;
+        if !batch.is_empty() && bytes + len > max_bytes {
+            batches.push(std::mem::take(&mut batch));
+            bytes = 0;
+        }
+        bytes += len;
+        batch.push(path);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_include_files_are_left_out_of_the_args() {
+        let dir = camino::Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let present = dir.join("Cargo.toml");
+        let driver = CocciDriver::with_binary("coccinelle-for-rust", "rules")
+            .with_iso_file(Some(present.clone()))
+            .with_macro_file(Some(dir.join("missing.h")));
+        assert_eq!(driver.missing_includes(), [dir.join("missing.h")]);
+        assert_eq!(
+            driver.rule_args(Utf8Path::new("r.cocci")),
+            ["--patch", "r.cocci", "--iso-file", present.as_str()]
+        );
+    }
+
+    #[test]
+    fn targets_are_batched_by_argument_bytes() {
+        let paths = ["a.rs", "bb.rs", "c.rs", "a-much-longer-name.rs"].map(Utf8PathBuf::from);
+        let batches = batch_by_bytes(paths, 11);
+        assert_eq!(
+            batches,
+            [
+                vec![Utf8PathBuf::from("a.rs"), Utf8PathBuf::from("bb.rs")],
+                vec![Utf8PathBuf::from("c.rs")],
+                vec![Utf8PathBuf::from("a-much-longer-name.rs")],
+            ]
+        );
+        assert!(batch_by_bytes(Vec::new(), 11).is_empty());
+    }
+
+    #[test]
+    fn exit_codes_map_to_statuses() {
+        assert_eq!(CocciStatus::from_exit_code(Some(0)), CocciStatus::Applied);
+        assert_eq!(CocciStatus::from_exit_code(Some(1)), CocciStatus::NoChange);
+        assert_eq!(CocciStatus::from_exit_code(Some(2)), CocciStatus::Error);
+        // Killed by a signal.
+        assert_eq!(CocciStatus::from_exit_code(None), CocciStatus::Error);
     }
 }
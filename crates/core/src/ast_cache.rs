@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// ast-grep dry-run output cached per vendor revision, keyed by the registry
+/// rule (a path or glob). An entry is only reused while the hash over the
+/// rule files it expanded to is unchanged.
+#[derive(Debug, Default)]
+pub(crate) struct AstCache {
+    path: Utf8PathBuf,
+    entries: BTreeMap<String, CachedDryRun>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedDryRun {
+    rule_hash: String,
+    stdout: String,
+}
+
+impl AstCache {
+    /// Loads `<workspace>/.forksmith-cache/<vendor_rev>/ast.json`. A missing
+    /// or unreadable cache is treated as empty.
+    pub(crate) fn load(workspace: &Utf8Path, vendor_rev: &str) -> Self {
+        let path = workspace
+            .join(".forksmith-cache")
+            .join(vendor_rev)
+            .join("ast.json");
+        let entries = fs::read(&path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        Self { path, entries }
+    }
+
+    /// Cached dry-run stdout for `rule`, if the hash of `files` still matches.
+    pub(crate) fn get(&self, rule: &str, files: &[Utf8PathBuf]) -> Option<String> {
+        let entry = self.entries.get(rule)?;
+        (hash_files(files)? == entry.rule_hash).then(|| entry.stdout.clone())
+    }
+
+    pub(crate) fn insert(&mut self, rule: &str, files: &[Utf8PathBuf], stdout: &str) {
+        if let Some(rule_hash) = hash_files(files) {
+            self.entries.insert(
+                rule.to_string(),
+                CachedDryRun {
+                    rule_hash,
+                    stdout: stdout.to_string(),
+                },
+            );
+        }
+    }
+
+    pub(crate) fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_vec(&self.entries)?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("writing ast-grep cache {}", self.path))?;
+        Ok(())
+    }
+}
+
+/// Hash over each file's path and contents; `None` if any is unreadable or
+/// the list is empty.
+fn hash_files(files: &[Utf8PathBuf]) -> Option<String> {
+    if files.is_empty() {
+        return None;
+    }
+    let mut hasher = Sha256::new();
+    for file in files {
+        hasher.update(file.as_str().as_bytes());
+        hasher.update([0]);
+        hasher.update(fs::read(file).ok()?);
+    }
+    Some(format!("{:x}", hasher.finalize()))
+}
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::process::Command;
+
+/// At most this many compiler errors are kept in the summary.
+pub const MAX_CARGO_ERRORS: usize = 20;
+
+/// One compiler error from `cargo check --message-format=json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CargoDiagnostic {
+    /// Primary span file, relative to the checked workspace.
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for CargoDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{file}:{line}: {}", self.message),
+            (Some(file), None) => write!(f, "{file}: {}", self.message),
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+pub(crate) struct CargoCheckOutcome {
+    pub passed: bool,
+    pub errors: Vec<CargoDiagnostic>,
+    /// Last stderr line, for failures that produce no compiler message
+    /// (e.g. a broken manifest).
+    pub stderr_tail: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CargoMessage {
+    reason: String,
+    message: Option<CompilerMessage>,
+}
+
+#[derive(Deserialize)]
+struct CompilerMessage {
+    level: String,
+    message: String,
+    #[serde(default)]
+    spans: Vec<CompilerSpan>,
+}
+
+#[derive(Deserialize)]
+struct CompilerSpan {
+    file_name: String,
+    line_start: usize,
+    is_primary: bool,
+}
+
+/// Runs `cargo check --message-format=json` with `extra_args` and collects
+/// up to [`MAX_CARGO_ERRORS`] compiler errors. Only a failure to spawn cargo
+/// is an `Err`; a failing check is reported in the outcome.
+pub(crate) fn run_cargo_check(
+    workdir: &Utf8Path,
+    extra_args: &[String],
+) -> Result<CargoCheckOutcome> {
+    let output = Command::new("cargo")
+        .args(["check", "--message-format=json"])
+        .args(extra_args)
+        .current_dir(workdir)
+        .output()
+        .with_context(|| format!("running cargo check in {workdir}"))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(CargoCheckOutcome {
+        passed: output.status.success(),
+        errors: parse_errors(&String::from_utf8_lossy(&output.stdout), MAX_CARGO_ERRORS),
+        stderr_tail: stderr
+            .lines()
+            .rev()
+            .find(|line| !line.trim().is_empty())
+            .map(|line| line.trim().to_string()),
+    })
+}
+
+/// The first `limit` error-level `compiler-message` entries in cargo's JSON
+/// stream; other lines and warnings are ignored.
+fn parse_errors(stdout: &str, limit: usize) -> Vec<CargoDiagnostic> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<CargoMessage>(line).ok())
+        .filter(|msg| msg.reason == "compiler-message")
+        .filter_map(|msg| msg.message)
+        .filter(|msg| msg.level == "error")
+        .map(|msg| {
+            let span = msg.spans.iter().find(|span| span.is_primary);
+            CargoDiagnostic {
+                file: span.map(|span| span.file_name.clone()),
+                line: span.map(|span| span.line_start),
+                message: msg.message,
+            }
+        })
+        .take(limit)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_errors_with_their_primary_span() {
+        let stdout = r#"{"reason":"compiler-artifact","package_id":"x"}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable","spans":[]}}
+{"reason":"compiler-message","message":{"level":"error","message":"cannot find value `y`","spans":[{"file_name":"src/a.rs","line_start":9,"is_primary":false},{"file_name":"src/lib.rs","line_start":3,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","spans":[]}}
+{"reason":"build-finished","success":false}"#;
+        let errors = parse_errors(stdout, 10);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "src/lib.rs:3: cannot find value `y`");
+        assert_eq!(errors[1].file, None);
+        assert_eq!(parse_errors(stdout, 1).len(), 1);
+    }
+}
@@ -0,0 +1,10 @@
+/// Why ahead/behind counts between two refs could not be produced.
+#[derive(Debug, thiserror::Error)]
+pub enum DivergenceError {
+    #[error("{0} does not resolve to a commit")]
+    MissingRef(String),
+    #[error("{base} and {other} share no history (unrelated roots); counts would be meaningless")]
+    UnrelatedHistories { base: String, other: String },
+    #[error(transparent)]
+    Git(#[from] anyhow::Error),
+}
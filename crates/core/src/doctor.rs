@@ -0,0 +1,72 @@
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use codex_registry::Registry;
+use fs_err as fs;
+
+use crate::fs_config::ForksmithConfig;
+use crate::run_cmd;
+
+/// Safe, additive repairs for a half set-up workspace: an empty registry,
+/// the `vendor/` parent dir and the upstream remote when `[repo]
+/// upstream_url` is configured. Never overwrites files or touches history.
+/// Returns one line per repair made.
+pub fn fix_workspace(workspace: &Utf8Path) -> Result<Vec<String>> {
+    let mut fixes = Vec::new();
+    let registry = workspace.join("patch-registry/registry.json");
+    if !registry.exists() {
+        Registry::default().save(&registry)?;
+        fixes.push(format!("created empty registry {registry}"));
+    }
+    let vendor_parent = workspace.join("vendor");
+    if !vendor_parent.exists() {
+        fs::create_dir_all(&vendor_parent)?;
+        fixes.push(format!("created {vendor_parent}"));
+    }
+
+    let config_path = workspace.join("codex-forksmith.toml");
+    if !config_path.exists() {
+        return Ok(fixes);
+    }
+    let config = ForksmithConfig::load_from_path(&config_path)?;
+    let Some(url) = &config.upstream_url else {
+        return Ok(fixes);
+    };
+    let vendor = Utf8PathBuf::from_path_buf(config.repo_path)
+        .map_err(|path| anyhow::anyhow!("repo path {} is not UTF-8", path.display()))?;
+    let remote = config.upstream_remote.as_str();
+    if vendor.join(".git").exists()
+        && run_cmd("git", &["remote", "get-url", remote], &vendor).is_err()
+    {
+        run_cmd("git", &["remote", "add", remote, url], &vendor)?;
+        fixes.push(format!("added remote {remote} -> {url} in {vendor}"));
+    }
+    Ok(fixes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_workspace_loads_and_gets_its_upstream_remote() {
+        let tmp = tempfile::tempdir().unwrap();
+        let workspace = Utf8Path::from_path(tmp.path()).unwrap();
+        let vendor = workspace.join("vendor/codex");
+        fs::create_dir_all(&vendor).unwrap();
+        run_cmd("git", &["init", "--quiet"], &vendor).unwrap();
+        fs::write(
+            workspace.join("codex-forksmith.toml"),
+            "[repo]\nupstream_url = \"https://example.com/codex.git\"\n",
+        )
+        .unwrap();
+
+        let fixes = fix_workspace(workspace).unwrap();
+        assert_eq!(fixes.len(), 2);
+        let registry = Registry::load(&workspace.join("patch-registry/registry.json")).unwrap();
+        assert!(registry.patch_sets.is_empty());
+        let url = run_cmd("git", &["remote", "get-url", "upstream"], &vendor).unwrap();
+        assert_eq!(url.trim(), "https://example.com/codex.git");
+
+        assert!(fix_workspace(workspace).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use codex_registry::Registry;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{run_cmd, UpdateOptions};
+
+/// Fingerprint of the last passing run, kept in
+/// `<workspace>/.forksmith-cache/rules-hash.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct StoredFingerprint {
+    /// Hash over the upstream rev, rule files, enabled sets and run settings.
+    pub(crate) inputs: String,
+    /// Hash of the vendor HEAD and working tree the run left behind, so a
+    /// reset or edited vendor is never mistaken for an up-to-date one.
+    pub(crate) vendor_state: String,
+}
+
+impl StoredFingerprint {
+    fn path(workspace: &Utf8Path) -> Utf8PathBuf {
+        workspace.join(".forksmith-cache").join("rules-hash.json")
+    }
+
+    /// A missing or unreadable file counts as no previous run.
+    pub(crate) fn load(workspace: &Utf8Path) -> Option<Self> {
+        let bytes = fs::read(Self::path(workspace)).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub(crate) fn save(&self, workspace: &Utf8Path) -> Result<()> {
+        let path = Self::path(workspace);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("writing rules hash {path}"))?;
+        Ok(())
+    }
+}
+
+/// Hash of everything that decides what [`crate::run_update`] produces for
+/// `upstream_rev`.
+pub(crate) fn run_fingerprint(
+    opts: &UpdateOptions,
+    registry: &Registry,
+    upstream_rev: &str,
+) -> String {
+    fingerprint(
+        upstream_rev,
+        registry,
+        &settings(opts),
+        &rule_files(opts, registry),
+    )
+}
+
+/// Every option that changes what a run applies or whether it passes,
+/// including the tool binaries and the env vars that override them.
+fn settings(opts: &UpdateOptions) -> String {
+    let env = |var: &str| std::env::var(var).ok().filter(|v| !v.is_empty());
+    format!(
+        "{:?}",
+        (
+            (opts.ast_enabled, opts.cocci_enabled),
+            (&opts.ast_grep_bin, env(codex_ast_driver::BINARY_ENV)),
+            (&opts.coccinelle_bin, env(codex_cocci_driver::BINARY_ENV)),
+            (
+                opts.cargo_check,
+                &opts.cargo_check_dir,
+                &opts.cargo_check_args
+            ),
+            (&opts.only_ids, &opts.ignore_globs),
+            (&opts.assert_nonzero_tags, opts.max_matches_per_set),
+            opts.keep_going,
+        )
+    )
+}
+
+/// Sorted files under the enabled rules dirs, the coccinelle include files
+/// and any absolute rule path outside those dirs.
+fn rule_files(opts: &UpdateOptions, registry: &Registry) -> Vec<Utf8PathBuf> {
+    let dirs = [
+        opts.ast_rules_dir.as_ref().filter(|_| opts.ast_enabled),
+        opts.coccinelle_rules_dir
+            .as_ref()
+            .filter(|_| opts.cocci_enabled),
+        opts.grit_rules_dir.as_ref(),
+    ];
+    let mut files: Vec<Utf8PathBuf> = dirs
+        .into_iter()
+        .flatten()
+        .flat_map(|dir| WalkDir::new(dir).into_iter().filter_map(|entry| entry.ok()))
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| Utf8PathBuf::from_path_buf(entry.into_path()).ok())
+        .collect();
+    files.extend(
+        [&opts.coccinelle_iso_file, &opts.coccinelle_macro_file]
+            .into_iter()
+            .flatten()
+            .cloned(),
+    );
+    files.extend(
+        registry
+            .patch_sets
+            .iter()
+            .filter(|set| set.enabled)
+            .flat_map(|set| &set.rules)
+            .map(Utf8PathBuf::from)
+            .filter(|rule| rule.is_absolute() && rule.is_file()),
+    );
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn fingerprint(
+    upstream_rev: &str,
+    registry: &Registry,
+    settings: &str,
+    files: &[Utf8PathBuf],
+) -> String {
+    let mut hasher = Sha256::new();
+    let mut field = |bytes: &[u8]| {
+        hasher.update(bytes);
+        hasher.update([0]);
+    };
+    field(upstream_rev.as_bytes());
+    field(settings.as_bytes());
+    let mut sets: Vec<_> = registry.patch_sets.iter().filter(|s| s.enabled).collect();
+    sets.sort_by(|a, b| a.id.cmp(&b.id));
+    for set in sets {
+        field(set.id.as_bytes());
+        field(format!("{:?}", set.max_matches).as_bytes());
+        for rule in &set.rules {
+            field(rule.as_bytes());
+        }
+    }
+    for file in files {
+        field(file.as_str().as_bytes());
+        // An unreadable file hashes like an empty one; it fails the run anyway.
+        field(&fs::read(file).unwrap_or_default());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Hash of the vendor HEAD, status (untracked files included) and diff.
+pub(crate) fn vendor_state(vendor: &Utf8Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    for args in [
+        &["rev-parse", "HEAD"][..],
+        &["status", "--porcelain=v2", "--untracked-files=all"][..],
+        &["diff", "HEAD", "--binary"][..],
+    ] {
+        hasher.update(run_cmd("git", args, vendor)?);
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_tracks_rule_contents_and_enabled_sets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let rule = Utf8Path::from_path(tmp.path()).unwrap().join("rule.yml");
+        fs::write(&rule, "id: a").unwrap();
+        let mut registry: Registry = serde_json::from_str(
+            r#"{"patch_sets": [
+                {"id": "b", "description": "", "rules": ["rule.yml"]},
+                {"id": "a", "description": "", "enabled": false}
+            ]}"#,
+        )
+        .unwrap();
+        let files = [rule.clone()];
+        let base = fingerprint("abc", &registry, "", &files);
+        assert_eq!(base, fingerprint("abc", &registry, "", &files));
+        assert_ne!(base, fingerprint("abd", &registry, "", &files));
+
+        fs::write(&rule, "id: b").unwrap();
+        let edited = fingerprint("abc", &registry, "", &files);
+        assert_ne!(base, edited);
+
+        registry.patch_sets[1].enabled = true;
+        assert_ne!(edited, fingerprint("abc", &registry, "", &files));
+
+        let base = settings(&UpdateOptions::default());
+        let changed: [fn(&mut UpdateOptions); 5] = [
+            |o| o.max_matches_per_set = Some(10),
+            |o| o.assert_nonzero_tags = vec!["core".into()],
+            |o| o.keep_going = true,
+            |o| o.ast_grep_bin = Some("/opt/ast-grep".into()),
+            |o| o.coccinelle_bin = Some("/opt/cocci".into()),
+        ];
+        for change in changed {
+            let mut opts = UpdateOptions::default();
+            change(&mut opts);
+            assert_ne!(base, settings(&opts));
+        }
+    }
+}
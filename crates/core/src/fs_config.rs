@@ -0,0 +1,358 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct WorkspaceSection {
+    root: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct RepoSection {
+    path: Option<String>,
+    local_remote: Option<String>,
+    local_branch: Option<String>,
+    upstream_remote: Option<String>,
+    upstream_branch: Option<String>,
+    upstream_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct BuildSection {
+    profile: Option<String>,
+    workspace: Option<String>,
+    binary_relpath: Option<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    no_default_features: bool,
+    target: Option<String>,
+    #[serde(default)]
+    extra_args: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SyncSection {
+    rebase: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawConfig {
+    workspace: Option<WorkspaceSection>,
+    repo: Option<RepoSection>,
+    build: Option<BuildSection>,
+    sync: Option<SyncSection>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForksmithConfig {
+    pub workspace_root: PathBuf,
+    pub repo_path: PathBuf,
+    pub local_remote: String,
+    pub local_branch: String,
+    pub upstream_remote: String,
+    pub upstream_branch: String,
+    /// `[repo] upstream_url`; only used by `doctor --fix` to restore a
+    /// missing upstream remote.
+    pub upstream_url: Option<String>,
+    pub build_profile: String,
+    pub build_workspace: PathBuf,
+    /// Explicit `[build] binary_relpath`; when unset the path is derived from
+    /// the profile and target, see [`ForksmithConfig::repo_binary_path`].
+    pub binary_relpath: Option<PathBuf>,
+    pub build_features: Vec<String>,
+    pub build_no_default_features: bool,
+    pub build_target: Option<String>,
+    pub build_extra_args: Vec<String>,
+    pub sync_rebase: bool,
+}
+
+/// Per-invocation replacements for loaded config values (`--repo`,
+/// `--local-branch`, `--upstream-branch`).
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    /// Resolved against the cwd, not the workspace.
+    pub repo: Option<PathBuf>,
+    pub local_branch: Option<String>,
+    pub upstream_branch: Option<String>,
+}
+
+/// Config file name searched for when `--forksmith-config` is not given.
+pub const DEFAULT_CONFIG_PATH: &str = "codex-forksmith.toml";
+/// Env var consulted for the config path when `--forksmith-config` is absent.
+pub const CONFIG_ENV: &str = "CODEX_FORKSMITH_CONFIG";
+
+/// Walks from `start` up to the filesystem root and returns the first
+/// `codex-forksmith.toml`, the way cargo finds `Cargo.toml`.
+pub fn discover_config(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(DEFAULT_CONFIG_PATH))
+        .find(|path| path.is_file())
+}
+
+impl ForksmithConfig {
+    /// `$CODEX_FORKSMITH_CONFIG`, or the nearest `codex-forksmith.toml` in
+    /// the cwd or one of its parents.
+    pub fn load_default() -> Result<Self> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV).filter(|p| !p.is_empty()) {
+            return Self::load_from_path(PathBuf::from(path));
+        }
+        let cwd = std::env::current_dir().context("reading current directory")?;
+        match discover_config(&cwd) {
+            Some(path) => Self::load_from_path(path),
+            None => anyhow::bail!(
+                "no {DEFAULT_CONFIG_PATH} found in {} or any parent directory",
+                cwd.display()
+            ),
+        }
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            anyhow::bail!(
+                "config file {} does not exist (pass --forksmith-config <path> or set {CONFIG_ENV})",
+                path.display()
+            );
+        }
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("reading config {}", path.display()))?;
+        let raw: RawConfig =
+            toml::from_str(&data).with_context(|| format!("parsing {}", path.display()))?;
+        let config_dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| PathBuf::from("."));
+
+        let workspace_root = resolve_path(
+            &config_dir,
+            raw.workspace
+                .as_ref()
+                .and_then(|w| w.root.clone())
+                .unwrap_or_else(|| ".".to_string()),
+        );
+
+        let repo_section = raw.repo.unwrap_or_default();
+        let repo_path = resolve_path(
+            &workspace_root,
+            repo_section
+                .path
+                .unwrap_or_else(|| "vendor/codex".to_string()),
+        );
+
+        let build_section = raw.build.unwrap_or_default();
+        let build_workspace = resolve_path(
+            &repo_path,
+            build_section
+                .workspace
+                .unwrap_or_else(|| "codex-rs".to_string()),
+        );
+        let sync_section = raw.sync.unwrap_or_default();
+        let binary_relpath = build_section.binary_relpath.map(PathBuf::from);
+
+        Ok(Self {
+            workspace_root,
+            repo_path,
+            local_remote: repo_section
+                .local_remote
+                .unwrap_or_else(|| "origin".to_string()),
+            local_branch: repo_section
+                .local_branch
+                .unwrap_or_else(|| "main".to_string()),
+            upstream_remote: repo_section
+                .upstream_remote
+                .unwrap_or_else(|| "upstream".to_string()),
+            upstream_branch: repo_section
+                .upstream_branch
+                .unwrap_or_else(|| "main".to_string()),
+            upstream_url: repo_section.upstream_url,
+            build_profile: build_section
+                .profile
+                .unwrap_or_else(|| "release".to_string()),
+            build_workspace,
+            binary_relpath,
+            build_features: build_section.features,
+            build_no_default_features: build_section.no_default_features,
+            build_target: build_section.target,
+            build_extra_args: build_section.extra_args,
+            sync_rebase: sync_section.rebase.unwrap_or(false),
+        })
+    }
+
+    /// Applies `overrides`. A new repo keeps the build workspace at the same
+    /// path relative to it; branch names get the checks git applies.
+    pub fn with_overrides(mut self, overrides: &ConfigOverrides) -> Result<Self> {
+        if let Some(repo) = &overrides.repo {
+            let cwd = std::env::current_dir().context("reading current directory")?;
+            let repo = resolve_path(&cwd, repo);
+            if !repo.is_dir() {
+                anyhow::bail!("--repo {} is not a directory", repo.display());
+            }
+            if let Ok(rel) = self.build_workspace.strip_prefix(&self.repo_path) {
+                self.build_workspace = repo.join(rel);
+            }
+            self.repo_path = repo;
+        }
+        if let Some(branch) = &overrides.local_branch {
+            validate_branch("--local-branch", branch)?;
+            self.local_branch = branch.clone();
+        }
+        if let Some(branch) = &overrides.upstream_branch {
+            validate_branch("--upstream-branch", branch)?;
+            self.upstream_branch = branch.clone();
+        }
+        Ok(self)
+    }
+
+    /// Replace `build_workspace` with `relpath` (resolved against the repo),
+    /// e.g. `--build-dir` while tracking an upstream that moved its crates.
+    pub fn with_build_dir(mut self, relpath: &Path) -> Result<Self> {
+        let workspace = resolve_path(&self.repo_path, relpath);
+        if !workspace.join("Cargo.toml").is_file() {
+            anyhow::bail!("--build-dir {} has no Cargo.toml", workspace.display());
+        }
+        self.build_workspace = workspace;
+        Ok(self)
+    }
+
+    /// Arguments after `cargo` for `codex build`, including the `[build]`
+    /// features/target/extra args.
+    pub fn cargo_build_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "build".to_string(),
+            "--profile".to_string(),
+            self.build_profile.clone(),
+        ];
+        if !self.build_features.is_empty() {
+            args.push("--features".to_string());
+            args.push(self.build_features.join(","));
+        }
+        if self.build_no_default_features {
+            args.push("--no-default-features".to_string());
+        }
+        if let Some(target) = &self.build_target {
+            args.push("--target".to_string());
+            args.push(target.clone());
+        }
+        args.extend(self.build_extra_args.iter().cloned());
+        args
+    }
+
+    /// The explicit `binary_relpath` if set, otherwise
+    /// `<build workspace>/target[/<triple>]/<profile dir>/codex`.
+    pub fn repo_binary_path(&self) -> PathBuf {
+        match &self.binary_relpath {
+            Some(relpath) => self.repo_path.join(relpath),
+            None => {
+                let mut path = self.build_workspace.join("target");
+                // With `--target`, cargo nests artifacts under `target/<triple>/`.
+                if let Some(triple) = &self.build_target {
+                    path.push(triple);
+                }
+                path.push(profile_dir(&self.build_profile));
+                path.push("codex");
+                path
+            }
+        }
+    }
+}
+
+/// Output directory cargo uses for a profile: `dev`/`test` build into
+/// `debug`, `bench` into `release`, custom profiles into their own name.
+fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        other => other,
+    }
+}
+
+/// The subset of `git check-ref-format --branch` that catches typos and
+/// option-looking values.
+fn validate_branch(flag: &str, name: &str) -> Result<()> {
+    let invalid = name.is_empty()
+        || name.starts_with('-')
+        || name.ends_with('/')
+        || name.ends_with(".lock")
+        || name.contains("..")
+        || name.contains("@{")
+        || name
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_control() || "~^:?*[\\".contains(c));
+    if invalid {
+        anyhow::bail!("{flag} {name:?} is not a valid branch name");
+    }
+    Ok(())
+}
+
+fn resolve_path(base: &Path, value: impl Into<PathBuf>) -> PathBuf {
+    let candidate = value.into();
+    if candidate.is_absolute() {
+        candidate
+    } else {
+        base.join(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discover_config, ConfigOverrides, ForksmithConfig};
+
+    fn load(toml: &str) -> ForksmithConfig {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("codex-forksmith.toml");
+        std::fs::write(&path, toml).unwrap();
+        ForksmithConfig::load_from_path(&path).unwrap()
+    }
+
+    #[test]
+    fn dev_profile_locates_debug_binary() {
+        let cfg = load("[build]\nprofile = \"dev\"\n");
+        assert!(cfg
+            .repo_binary_path()
+            .ends_with("vendor/codex/codex-rs/target/debug/codex"));
+    }
+
+    #[test]
+    fn discovery_walks_up_to_the_workspace_root() {
+        let tmp = tempfile::tempdir().unwrap();
+        let nested = tmp.path().join("vendor/codex/codex-rs");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(discover_config(&nested), None);
+        let config = tmp.path().join("codex-forksmith.toml");
+        std::fs::write(&config, "").unwrap();
+        assert_eq!(discover_config(&nested), Some(config));
+    }
+
+    #[test]
+    fn overrides_move_the_repo_and_validate_branches() {
+        let scratch = tempfile::tempdir().unwrap();
+        let cfg = load("").with_overrides(&ConfigOverrides {
+            repo: Some(scratch.path().to_path_buf()),
+            local_branch: Some("feature/x".into()),
+            upstream_branch: None,
+        });
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.repo_path, scratch.path());
+        assert_eq!(cfg.build_workspace, scratch.path().join("codex-rs"));
+        assert_eq!(cfg.local_branch, "feature/x");
+        assert_eq!(cfg.upstream_branch, "main");
+
+        for bad in ["", "-x", "a b", "a..b"] {
+            let overrides = ConfigOverrides {
+                upstream_branch: Some(bad.into()),
+                ..Default::default()
+            };
+            assert!(load("").with_overrides(&overrides).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn explicit_binary_relpath_wins() {
+        let cfg = load("[build]\nprofile = \"dev\"\nbinary_relpath = \"out/codex\"\n");
+        assert!(cfg.repo_binary_path().ends_with("vendor/codex/out/codex"));
+    }
+}
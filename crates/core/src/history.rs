@@ -0,0 +1,190 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use chrono::{DateTime, Utc};
+use codex_registry::PatchResult;
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::UpdateSummary;
+
+/// One line of the run history log, written once per `run_update`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub vendor_rev_before: Option<String>,
+    pub vendor_rev_after: Option<String>,
+    pub applied: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// `None` when the run skipped cargo check.
+    #[serde(default)]
+    pub cargo_check_passed: Option<bool>,
+    pub duration_ms: u64,
+}
+
+impl HistoryEntry {
+    /// `cargo_check` says whether the run checked at all.
+    pub fn from_summary(
+        started: DateTime<Utc>,
+        summary: &UpdateSummary,
+        cargo_check: bool,
+    ) -> Self {
+        let count = |pred: fn(&PatchResult) -> bool| {
+            summary
+                .patch_sets
+                .iter()
+                .filter(|set| set.result.as_ref().is_some_and(pred))
+                .count()
+        };
+        Self {
+            timestamp: started,
+            vendor_rev_before: summary.vendor_rev_before.clone(),
+            vendor_rev_after: summary.vendor_rev_after.clone(),
+            applied: count(|r| matches!(r, PatchResult::Applied { .. })),
+            skipped: count(|r| matches!(r, PatchResult::Skipped { .. })),
+            failed: count(|r| matches!(r, PatchResult::Failed { .. })),
+            cargo_check_passed: cargo_check.then_some(summary.cargo_check_passed),
+            duration_ms: (Utc::now() - started).num_milliseconds().max(0) as u64,
+        }
+    }
+
+    /// A patch set failed or cargo check ran and did not pass.
+    pub fn is_failure(&self) -> bool {
+        self.failed > 0 || self.cargo_check_passed == Some(false)
+    }
+}
+
+/// Appends `entry` as a single line. The line is written with one
+/// `write_all` on an append-mode handle and flushed, so a crash leaves at
+/// worst a truncated final line rather than corrupting earlier entries.
+pub fn append_history(path: &Utf8Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("appending to history log {path}"))?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Returns the last `n` non-empty lines of the history log, oldest first.
+pub fn read_history_tail(path: &Utf8Path, n: usize) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading history log {path}"))?;
+    let lines: Vec<String> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    let skip = lines.len().saturating_sub(n);
+    Ok(lines.into_iter().skip(skip).collect())
+}
+
+/// Which history entries [`read_history`] returns.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only entries newer than `now - since`.
+    pub since: Option<std::time::Duration>,
+    /// Only runs where a patch set or cargo check failed.
+    pub failures_only: bool,
+}
+
+/// Parses the history log and returns the entries matching `filter`, oldest
+/// first. Lines that do not parse (e.g. a truncated final line) are skipped.
+pub fn read_history(path: &Utf8Path, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+    let cutoff = match filter.since {
+        Some(since) => Some(
+            Utc::now()
+                - chrono::Duration::from_std(since)
+                    .with_context(|| format!("--since {since:?} is out of range"))?,
+        ),
+        None => None,
+    };
+    Ok(read_history_tail(path, usize::MAX)?
+        .iter()
+        .filter_map(|line| match serde_json::from_str::<HistoryEntry>(line) {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                tracing::warn!("skipping unreadable history line in {path}: {err}");
+                None
+            }
+        })
+        .filter(|entry| cutoff.is_none_or(|cutoff| entry.timestamp >= cutoff))
+        .filter(|entry| !filter.failures_only || entry.is_failure())
+        .collect())
+}
+
+/// Rewrites the history log keeping only the newest `keep` entries (whole
+/// runs, not per-set results) and returns how many were dropped. The new file is renamed into place.
+pub fn prune_history(path: &Utf8Path, keep: usize) -> Result<usize> {
+    if !path.exists() {
+        return Ok(0);
+    }
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading history log {path}"))?;
+    let lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    let dropped = lines.len().saturating_sub(keep);
+    if dropped == 0 {
+        return Ok(0);
+    }
+    let mut kept = lines[dropped..].join("\n");
+    if !kept.is_empty() {
+        kept.push('\n');
+    }
+    let tmp = path.with_extension(format!("jsonl.tmp.{}", std::process::id()));
+    fs::write(&tmp, kept).with_context(|| format!("writing history log {tmp}"))?;
+    fs::rename(&tmp, path).with_context(|| format!("renaming history log into {path}"))?;
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skipped_cargo_check_is_not_a_failure() {
+        let line = |check: &str| {
+            format!(
+                r#"{{"timestamp":"2026-01-01T00:00:00Z","vendor_rev_before":null,"vendor_rev_after":null,"applied":1,"skipped":0,"failed":0,{check}"duration_ms":5}}"#
+            )
+        };
+        let parse = |check: &str| serde_json::from_str::<HistoryEntry>(&line(check)).unwrap();
+        assert!(!parse("").is_failure());
+        assert!(!parse(r#""cargo_check_passed":null,"#).is_failure());
+        assert!(!parse(r#""cargo_check_passed":true,"#).is_failure());
+        assert!(parse(r#""cargo_check_passed":false,"#).is_failure());
+    }
+
+    #[test]
+    fn prune_keeps_the_newest_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = Utf8Path::from_path(tmp.path())
+            .unwrap()
+            .join("history.jsonl");
+        assert_eq!(prune_history(&path, 2).unwrap(), 0);
+
+        fs::write(&path, "{\"run\":1}\n{\"run\":2}\n\n{\"run\":3}\n").unwrap();
+        assert_eq!(prune_history(&path, 5).unwrap(), 0);
+        assert_eq!(prune_history(&path, 2).unwrap(), 1);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "{\"run\":2}\n{\"run\":3}\n"
+        );
+        assert_eq!(prune_history(&path, 0).unwrap(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use codex_registry::Registry;
+use fs_err as fs;
+
+use crate::run_cmd;
+
+/// Written as `codex-forksmith.toml`; mirrors the defaults both binaries
+/// assume when a key is missing.
+pub const DEFAULT_CONFIG: &str = r#"version = 1
+
+[workspace]
+root = "."
+
+[repo]
+path = "vendor/codex"
+local_remote = "origin"
+local_branch = "main"
+upstream_remote = "upstream"
+upstream_branch = "main"
+
+[build]
+profile = "release"
+workspace = "codex-rs"
+# binary_relpath defaults to codex-rs/target/<profile dir>/codex
+"#;
+
+#[derive(Debug, Clone)]
+pub struct InitOptions {
+    pub workspace: Utf8PathBuf,
+    /// Clone the vendor from here; without it the vendor is `git init`ed.
+    pub vendor_url: Option<String>,
+    /// Overwrite an existing config and registry. An existing vendor repo is
+    /// never touched.
+    pub force: bool,
+}
+
+/// What [`init_workspace`] created, as workspace-relative paths.
+#[derive(Debug, Default)]
+pub struct InitReport {
+    pub written: Vec<Utf8PathBuf>,
+    /// Set when `vendor/codex` already existed and was left alone.
+    pub kept_vendor: bool,
+}
+
+/// Lays out a fresh workspace: `codex-forksmith.toml`, an empty
+/// `patch-registry/registry.json` and `vendor/codex`. Every conflict is
+/// checked before anything is written.
+pub fn init_workspace(opts: &InitOptions) -> Result<InitReport> {
+    let root = &opts.workspace;
+    let config = Utf8Path::new("codex-forksmith.toml");
+    let registry = Utf8Path::new("patch-registry/registry.json");
+    let vendor = root.join("vendor/codex");
+
+    if !opts.force {
+        for file in [config, registry] {
+            if root.join(file).exists() {
+                anyhow::bail!(
+                    "{} already exists (pass --force to overwrite)",
+                    root.join(file)
+                );
+            }
+        }
+    }
+    let kept_vendor = vendor.exists() && !is_empty_dir(&vendor)?;
+    if kept_vendor && opts.vendor_url.is_some() {
+        anyhow::bail!("{vendor} already exists; refusing to clone over it");
+    }
+
+    fs::create_dir_all(root)?;
+    fs::write(root.join(config), DEFAULT_CONFIG)?;
+    Registry::default().save(&root.join(registry))?;
+    let mut report = InitReport {
+        written: vec![config.to_path_buf(), registry.to_path_buf()],
+        kept_vendor,
+    };
+    if !kept_vendor {
+        fs::create_dir_all(&vendor)?;
+        match &opts.vendor_url {
+            Some(url) => {
+                run_cmd("git", &["clone", "--quiet", url, "vendor/codex"], root)
+                    .with_context(|| format!("cloning {url}"))?;
+                // The config fetches upstream from `upstream`; `origin` is
+                // meant to be repointed at the user's fork.
+                run_cmd("git", &["remote", "add", "upstream", url], &vendor)?;
+            }
+            None => {
+                run_cmd(
+                    "git",
+                    &["init", "--quiet", "--initial-branch=main"],
+                    &vendor,
+                )?;
+            }
+        }
+        report.written.push("vendor/codex".into());
+    }
+    Ok(report)
+}
+
+fn is_empty_dir(path: &Utf8Path) -> Result<bool> {
+    Ok(path.is_dir() && fs::read_dir(path)?.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_to_overwrite_without_force() {
+        let tmp = tempfile::tempdir().unwrap();
+        let opts = InitOptions {
+            workspace: Utf8PathBuf::from_path_buf(tmp.path().to_path_buf()).unwrap(),
+            vendor_url: None,
+            force: false,
+        };
+        let report = init_workspace(&opts).unwrap();
+        assert_eq!(report.written.len(), 3);
+        assert!(opts.workspace.join("vendor/codex/.git").exists());
+        assert!(
+            Registry::load(&opts.workspace.join("patch-registry/registry.json"))
+                .unwrap()
+                .patch_sets
+                .is_empty()
+        );
+
+        assert!(init_workspace(&opts).is_err());
+        let report = init_workspace(&InitOptions {
+            force: true,
+            ..opts
+        })
+        .unwrap();
+        assert!(report.kept_vendor);
+        assert_eq!(report.written.len(), 2);
+    }
+}
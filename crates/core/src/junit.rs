@@ -0,0 +1,65 @@
+use codex_registry::PatchResult;
+
+use crate::UpdateSummary;
+
+impl UpdateSummary {
+    /// Renders each processed patch set as a JUnit `<testcase>`, so CI
+    /// dashboards can show which set broke after an upstream bump.
+    pub fn to_junit_xml(&self) -> String {
+        let failures = self
+            .patch_sets
+            .iter()
+            .filter(|set| matches!(set.result, Some(PatchResult::Failed { .. })))
+            .count();
+        let skipped = self
+            .patch_sets
+            .iter()
+            .filter(|set| matches!(set.result, Some(PatchResult::Skipped { .. })))
+            .count();
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"codex-forksmith\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\">\n",
+            self.patch_sets.len()
+        ));
+        for set in &self.patch_sets {
+            let name = escape(&set.id);
+            match &set.result {
+                Some(PatchResult::Failed { error }) => {
+                    xml.push_str(&format!(
+                        "  <testcase classname=\"patch_sets\" name=\"{name}\">\n    <failure message=\"{}\"/>\n  </testcase>\n",
+                        escape(error)
+                    ));
+                }
+                Some(PatchResult::Skipped { reason }) => {
+                    xml.push_str(&format!(
+                        "  <testcase classname=\"patch_sets\" name=\"{name}\">\n    <skipped message=\"{}\"/>\n  </testcase>\n",
+                        escape(reason.as_deref().unwrap_or("skipped"))
+                    ));
+                }
+                Some(PatchResult::Applied { .. }) | None => {
+                    xml.push_str(&format!(
+                        "  <testcase classname=\"patch_sets\" name=\"{name}\"/>\n"
+                    ));
+                }
+            }
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn escape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for ch in raw.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
@@ -1,60 +1,417 @@
-use std::process::Command;
+mod ast_cache;
+mod cargo_check;
+mod divergence;
+mod doctor;
+mod fingerprint;
+pub mod fs_config;
+pub mod git_auth;
+mod history;
+mod init;
+mod junit;
+mod markdown;
+mod metrics;
+mod preview;
+mod profile;
+mod sarif;
+pub mod table;
+mod webhook;
+mod worktree;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::Instant;
 
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
-use codex_ast_driver::{AstGrepDriver, AstMode, AstRunOutcome};
-use codex_cocci_driver::CocciDriver;
-use codex_pkg::build_zip;
-use codex_registry::{PatchResult, RegistryStore};
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+pub use cargo_check::{CargoDiagnostic, MAX_CARGO_ERRORS};
+use chrono::Utc;
+pub use codex_pkg::Compression;
+pub use divergence::DivergenceError;
+pub use doctor::fix_workspace;
+use fs_err as fs;
+pub use history::{
+    append_history, prune_history, read_history, read_history_tail, HistoryEntry, HistoryFilter,
+};
+pub use init::{init_workspace, InitOptions, InitReport, DEFAULT_CONFIG};
+pub use preview::{run_preview, PreviewOptions, PreviewSummary, RulePreview};
+pub use profile::{load_profiles, Profile};
+
+use ast_cache::AstCache;
+use cargo_check::run_cargo_check;
+use codex_ast_driver::{
+    AstGrepDriver, AstMode, AstRunOutcome, AstRunSummary, MAX_RETAINED_MATCHES,
+};
+use codex_cocci_driver::{CocciDriver, CocciStatus};
+use codex_driver_util::resolve_rule;
+use codex_grit_driver::{GritDriver, GritMode, GritRunOutcome};
+use codex_pkg::{build_zip, source_size, ZipOptions};
+use codex_registry::{PatchResult, Registry, RegistryStore};
+use fingerprint::{run_fingerprint, vendor_state, StoredFingerprint};
+use git_auth::{redact, remote_git_command};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use sarif::SarifFinding;
 use serde::Serialize;
-use tracing::warn;
+use tracing::span::EnteredSpan;
+use tracing::{debug, debug_span, info_span, warn, Level, Span};
+use worktree::Worktree;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UpdateOptions {
     pub workspace_root: Utf8PathBuf,
     pub vendor_dir: Utf8PathBuf,
     pub registry_path: Utf8PathBuf,
     pub ast_rules_dir: Option<Utf8PathBuf>,
     pub coccinelle_rules_dir: Option<Utf8PathBuf>,
+    pub grit_rules_dir: Option<Utf8PathBuf>,
+    /// When false the ast-grep phase is skipped without detecting the
+    /// binary or warning about it.
+    pub ast_enabled: bool,
+    /// Same as `ast_enabled`, for the coccinelle phase.
+    pub cocci_enabled: bool,
+    /// Explicit tool binaries; `AST_GREP_BIN` / `COCCINELLE_BIN` still win.
+    pub ast_grep_bin: Option<Utf8PathBuf>,
+    pub coccinelle_bin: Option<Utf8PathBuf>,
+    /// Passed to every coccinelle rule as `--iso-file` / `--macro-file`;
+    /// a missing file is left out with a warning.
+    pub coccinelle_iso_file: Option<Utf8PathBuf>,
+    pub coccinelle_macro_file: Option<Utf8PathBuf>,
+    /// Remote fetched and reset onto, usually `origin`.
+    pub upstream_remote: String,
     pub upstream_branch: String,
+    /// Exact commit or tag to pin the vendor to instead of the branch tip.
+    pub upstream_rev: Option<String>,
     pub cargo_check: bool,
+    /// Run cargo check here (relative to the vendor root) instead of the
+    /// vendor root itself, e.g. `codex-rs`.
+    pub cargo_check_dir: Option<Utf8PathBuf>,
+    /// Extra arguments after `cargo check`, e.g. `--workspace --all-features`.
+    pub cargo_check_args: Vec<String>,
     pub output_zip: Option<Utf8PathBuf>,
+    /// Write the ast-grep dry-run matches as a SARIF 2.1.0 report.
+    pub sarif_out: Option<Utf8PathBuf>,
+    /// POST the summary here after the run (Slack blocks for hooks.slack.com).
+    pub webhook_url: Option<String>,
+    /// Append one JSON line per run to this audit log.
+    pub history_log: Option<Utf8PathBuf>,
+    /// Always re-run ast-grep dry runs instead of reusing cached output.
+    pub no_cache: bool,
+    pub zip_compression: Compression,
+    /// Write Prometheus textfile metrics here after the run.
+    pub metrics_out: Option<Utf8PathBuf>,
+    /// Fail before applying anything if an enabled set carrying one of these
+    /// tags matched nothing in its ast-grep dry run.
+    pub assert_nonzero_tags: Vec<String>,
+    /// Skip applying any set whose ast-grep dry run matched more than this
+    /// many places in total. A set's registry `max_matches` overrides it.
+    pub max_matches_per_set: Option<u64>,
+    /// Record a patch set's error as `Failed` and carry on with the next set
+    /// instead of aborting the run.
+    pub keep_going: bool,
+    /// Always write the pretty JSON summary here, whatever stdout shows.
+    pub summary_out: Option<Utf8PathBuf>,
+    /// Vendor paths (globs relative to the vendor root) that ast-grep and
+    /// coccinelle must not touch, usually from `.forksmithignore`.
+    pub ignore_globs: Vec<String>,
+    /// Apply and check in a throwaway `git worktree` at the target rev and
+    /// only bring the result into `vendor_dir` if every set and cargo check
+    /// passed.
+    pub use_worktree: bool,
+    /// Apply only these patch sets; the rest are recorded as skipped.
+    /// Empty means every enabled set.
+    pub only_ids: Vec<String>,
+    /// Return an error once the run (and `summary_out`) is done if any
+    /// warning was raised.
+    pub fail_on_warnings: bool,
+    /// Commit the vendor changes once the run passes.
+    pub commit: Option<CommitOptions>,
+    /// Return early with `up_to_date` when the upstream rev, rule files and
+    /// enabled sets match the last passing run; record them when it passes.
+    /// The zip, metrics and history are still written; SARIF disables the
+    /// skip since it needs fresh dry runs.
+    pub rules_hash: bool,
+    /// With `rules_hash`, run the full pipeline even when nothing changed.
+    pub force: bool,
+}
+
+/// How [`run_update`] commits the applied patches in the vendor repo.
+#[derive(Debug, Clone, Default)]
+pub struct CommitOptions {
+    /// Commit message; `{count}` and `{upstream}` are replaced with the number
+    /// of applied patch sets and the short upstream sha. Defaults to
+    /// [`DEFAULT_COMMIT_MESSAGE`].
+    pub message: Option<String>,
+    /// `git commit --author`, e.g. `Forksmith <bot@example.com>`.
+    pub author: Option<String>,
+}
+
+pub const DEFAULT_COMMIT_MESSAGE: &str = "forksmith: apply {count} patch sets at {upstream}";
+
+impl CommitOptions {
+    fn render_message(&self, count: usize, upstream: &str) -> String {
+        self.message
+            .as_deref()
+            .unwrap_or(DEFAULT_COMMIT_MESSAGE)
+            .replace("{count}", &count.to_string())
+            .replace("{upstream}", upstream)
+    }
 }
 
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct UpdateSummary {
+    /// The run was skipped because nothing changed since the last passing
+    /// run (`UpdateOptions.rules_hash`).
+    pub up_to_date: bool,
     pub vendor_rev_before: Option<String>,
     pub vendor_rev_after: Option<String>,
     pub ast_notes: Vec<String>,
     pub cocci_notes: Vec<String>,
+    pub grit_notes: Vec<String>,
     pub cargo_check_passed: bool,
+    /// The first compiler errors when cargo check failed.
+    pub cargo_errors: Vec<CargoDiagnostic>,
     pub output_zip: Option<String>,
-    pub warnings: Vec<String>,
+    /// Size of the combined vendor diff against upstream after applying.
+    pub diff_stat: Option<DiffStat>,
+    /// Vendor commit made for this run's changes, with `UpdateOptions.commit`.
+    pub commit_sha: Option<String>,
+    pub warnings: Vec<Warning>,
+    /// Registry outcome of every patch set processed during this run.
+    pub patch_sets: Vec<PatchSetReport>,
+}
+
+/// `git diff --shortstat` counts for tracked files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DiffStat {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStat {
+    /// Parses `3 files changed, 10 insertions(+), 2 deletions(-)`; git omits
+    /// zero parts and prints nothing at all for an empty diff.
+    fn parse(shortstat: &str) -> Self {
+        let mut stat = Self::default();
+        for part in shortstat.split(',') {
+            let mut words = part.split_whitespace();
+            let (Some(n), Some(kind)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let Ok(n) = n.parse() else { continue };
+            if kind.starts_with("file") {
+                stat.files_changed = n;
+            } else if kind.starts_with("insertion") {
+                stat.insertions = n;
+            } else if kind.starts_with("deletion") {
+                stat.deletions = n;
+            }
+        }
+        stat
+    }
+}
+
+/// How much a warning matters, least severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningLevel {
+    /// Setup gaps such as a tool that is not installed.
+    Config,
+    /// Something skipped or degraded during the run.
+    Runtime,
+    /// A patch set or delivery failed; worth paging on.
+    Critical,
+}
+
+/// A summary warning; serializes as `{"severity", "source", "message"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Warning {
+    pub severity: WarningLevel,
+    /// Phase or component that raised it, e.g. `ast-grep` or `webhook`.
+    pub source: String,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(severity: WarningLevel, source: &str, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            source: source.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+impl UpdateSummary {
+    /// Writes the pretty JSON summary via a sibling temp file and a rename, so
+    /// readers never see a partial file.
+    pub fn write_json(&self, path: &Utf8Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("creating {parent}"))?;
+        }
+        let tmp = path.with_extension(format!("json.tmp.{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing summary {tmp}"))?;
+        fs::rename(&tmp, path).with_context(|| format!("renaming summary into {path}"))?;
+        Ok(())
+    }
+
+    /// Whether any processed patch set ended up `Failed`.
+    pub fn has_failures(&self) -> bool {
+        self.patch_sets
+            .iter()
+            .any(|set| matches!(set.result, Some(PatchResult::Failed { .. })))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PatchSetReport {
+    pub id: String,
+    pub match_count: Option<u64>,
+    pub result: Option<PatchResult>,
 }
 
 pub fn run_update(opts: UpdateOptions) -> Result<UpdateSummary> {
+    if (opts.ast_rules_dir.is_none() || !opts.ast_enabled) && !opts.assert_nonzero_tags.is_empty() {
+        anyhow::bail!(
+            "asserting matches for tagged sets requires an ast-grep rules dir (and no --no-ast)"
+        );
+    }
+    let started = Utc::now();
     let mut summary = UpdateSummary {
         output_zip: opts.output_zip.as_ref().map(|p| p.to_string()),
         ..Default::default()
     };
-    let vendor = opts.vendor_dir;
+    let main_vendor = opts.vendor_dir.clone();
     let registry_store = RegistryStore::new(opts.registry_path.clone());
     let mut registry = registry_store.load()?;
+    let unknown: Vec<&str> = opts
+        .only_ids
+        .iter()
+        .filter(|id| !registry.patch_sets.iter().any(|set| set.id == **id))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        anyhow::bail!("--only names unknown patch sets: {}", unknown.join(", "));
+    }
 
-    summary.vendor_rev_before = read_git_rev(&vendor).ok();
-    sync_upstream(&vendor, &opts.upstream_branch)?;
+    summary.vendor_rev_before = read_git_rev(&main_vendor).ok();
+    let mut inputs_hash = None;
+    let worktree = {
+        let _phase = Phase::enter(
+            info_span!(
+                "sync",
+                remote = %opts.upstream_remote,
+                branch = %opts.upstream_branch,
+                rev = ?opts.upstream_rev
+            ),
+            "sync",
+        );
+        let target = fetch_target(
+            &main_vendor,
+            &opts.upstream_remote,
+            &opts.upstream_branch,
+            opts.upstream_rev.as_deref(),
+        )?;
+        if opts.rules_hash {
+            let spec = format!("{target}^{{commit}}");
+            let upstream = run_cmd("git", &["rev-parse", "--verify", &spec], &main_vendor)?;
+            let inputs = run_fingerprint(&opts, &registry, upstream.trim());
+            let unchanged = StoredFingerprint::load(&opts.workspace_root).is_some_and(|stored| {
+                stored.inputs == inputs
+                    && vendor_state(&main_vendor).is_ok_and(|state| stored.vendor_state == state)
+            });
+            // SARIF needs this run's dry-run matches, so it never skips.
+            if unchanged && !opts.force && opts.sarif_out.is_none() {
+                debug!("upstream, rules and patch sets unchanged since the last passing run");
+                summary.up_to_date = true;
+                summary.vendor_rev_after = summary.vendor_rev_before.clone();
+                // The stored run passed, including its cargo check.
+                summary.cargo_check_passed = opts.cargo_check;
+                if let Some(zip_path) = &opts.output_zip {
+                    let zip_opts = ZipOptions {
+                        compression: opts.zip_compression,
+                        ..Default::default()
+                    };
+                    build_zip(&main_vendor, zip_path, &zip_opts, None)?;
+                }
+                if let Some(path) = &opts.metrics_out {
+                    metrics::write_metrics(path, &summary, opts.cargo_check)?;
+                }
+                if let Some(path) = &opts.history_log {
+                    record_history(path, started, &mut summary, opts.cargo_check);
+                }
+                if let Some(path) = &opts.summary_out {
+                    summary.write_json(path)?;
+                }
+                return Ok(summary);
+            }
+            inputs_hash = Some(inputs);
+        }
+        if opts.use_worktree {
+            Some(Worktree::add(&main_vendor, &target)?)
+        } else {
+            run_cmd("git", &["reset", "--hard", &target], &main_vendor)?;
+            None
+        }
+    };
+    // Leftover untracked files survive the reset; only what the run changes
+    // on top of them gets committed.
+    let commit_baseline = match opts.commit {
+        Some(_) => Some(dirty_files(&main_vendor)?),
+        None => None,
+    };
+    let vendor = worktree
+        .as_ref()
+        .map_or_else(|| main_vendor.clone(), |w| w.path.clone());
     summary.vendor_rev_after = read_git_rev(&vendor).ok();
 
     let m = MultiProgress::new();
+    // Spinners redraw over log lines; with debug tracing on, the span
+    // timeline is the progress display.
+    if tracing::enabled!(Level::DEBUG) {
+        m.set_draw_target(ProgressDrawTarget::hidden());
+    }
     let ast_pb = m.add(progress_spinner("ast-grep"));
     let cocci_pb = m.add(progress_spinner("coccinelle"));
+    let grit_pb = m.add(progress_spinner("grit"));
     let cargo_pb = m.add(progress_spinner("cargo"));
+    let mut findings = Vec::new();
+    let mut sarif_notices = Vec::new();
+    for set in registry.patch_sets.clone() {
+        if !selected(&opts.only_ids, &set.id) {
+            registry.record_run(
+                &set.id,
+                None,
+                PatchResult::Skipped {
+                    reason: Some("not selected".into()),
+                },
+            )?;
+        }
+    }
 
-    if let Some(ast_dir) = &opts.ast_rules_dir {
-        if let Some(driver) = AstGrepDriver::detect(ast_dir)? {
+    if let Some(ast_dir) = opts.ast_rules_dir.as_ref().filter(|_| opts.ast_enabled) {
+        let _phase = Phase::enter(info_span!("ast", rules_dir = %ast_dir), "ast-grep");
+        if let Some(driver) = AstGrepDriver::detect_with(ast_dir, opts.ast_grep_bin.as_deref())? {
+            let driver = driver.with_ignore_globs(opts.ignore_globs.clone());
             ast_pb.set_message("ast-grep dry-run");
+            let mut cache = match (&summary.vendor_rev_after, opts.no_cache) {
+                (Some(rev), false) => Some(AstCache::load(&opts.workspace_root, rev)),
+                _ => None,
+            };
+            let mut dry_runs: Vec<(String, String, Vec<Utf8PathBuf>, AstRunOutcome)> = Vec::new();
+            let mut claims: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+            let mut dry_run_failed = BTreeSet::new();
             for set in registry.patch_sets.clone() {
+                if !selected(&opts.only_ids, &set.id) {
+                    continue;
+                }
                 if !set.enabled {
                     registry.record_run(
                         &set.id,
@@ -65,19 +422,331 @@ pub fn run_update(opts: UpdateOptions) -> Result<UpdateSummary> {
                     )?;
                     continue;
                 }
-                for rule in &set.rules {
-                    let config_path = ast_dir.join(rule);
-                    match driver.run_with_config(&config_path, &vendor, AstMode::DryRun)? {
-                        AstRunOutcome::Applied(summary_run) => {
+                for rule in set.rules.iter().filter(|r| is_ast_rule(r)) {
+                    let _rule = Phase::enter(
+                        debug_span!("ast_dry_run", set = %set.id, rule = %rule),
+                        "ast-grep dry run",
+                    );
+                    let (config_paths, outcome) = match ast_dry_run(
+                        &driver,
+                        cache.as_mut(),
+                        rule,
+                        &opts.ignore_globs,
+                        &vendor,
+                    ) {
+                        Ok(result) => result,
+                        Err(err) => {
+                            keep_going_or_fail(
+                                &mut registry,
+                                &mut summary,
+                                opts.keep_going,
+                                &set.id,
+                                err,
+                            )?;
+                            dry_runs.retain(|(id, ..)| *id != set.id);
+                            dry_run_failed.insert(set.id.clone());
+                            break;
+                        }
+                    };
+                    if let AstRunOutcome::Applied(summary_run) = &outcome {
+                        if summary_run.is_truncated() {
+                            let notice = truncation_notice(&set.id, rule, summary_run);
+                            warn!("{notice}");
+                            summary.warnings.push(Warning::new(
+                                WarningLevel::Runtime,
+                                "ast-grep",
+                                notice.clone(),
+                            ));
+                            if opts.sarif_out.is_some() {
+                                sarif_notices.push(notice);
+                            }
+                        }
+                        debug!(
+                            matches = summary_run.match_count(),
+                            cached = summary_run.duration_ms == 0,
+                            "dry run matched"
+                        );
+                        for m in summary_run.matches() {
+                            claims
+                                .entry(relative_to(&vendor, m.file))
+                                .or_default()
+                                .insert(set.id.clone());
+                        }
+                    }
+                    dry_runs.push((set.id.clone(), rule.clone(), config_paths, outcome));
+                }
+            }
+            if let Some(cache) = &cache {
+                if let Err(err) = cache.save() {
+                    warn!("ast-grep cache not saved: {err:#}");
+                }
+            }
+            summary.warnings.extend(
+                overlap_warnings(&claims)
+                    .into_iter()
+                    .map(|msg| Warning::new(WarningLevel::Runtime, "ast-grep", msg)),
+            );
+            assert_nonzero_matches(
+                &registry,
+                &dry_runs,
+                &dry_run_failed,
+                &opts.only_ids,
+                &opts.assert_nonzero_tags,
+            )?;
+            let over = over_match_limit(&registry, &dry_runs, opts.max_matches_per_set);
+            for (set_id, (total, limit)) in &over {
+                let reason = format!("exceeded max matches ({total} > {limit})");
+                summary.warnings.push(Warning::new(
+                    WarningLevel::Runtime,
+                    "ast-grep",
+                    format!("{set_id} not applied: {reason}"),
+                ));
+                registry.record_run(
+                    set_id,
+                    Some(*total),
+                    PatchResult::Skipped {
+                        reason: Some(reason),
+                    },
+                )?;
+            }
+            dry_runs.retain(|(id, ..)| !over.contains_key(id));
+
+            let mut failed_sets = BTreeSet::new();
+            let mut applied_sets = BTreeSet::new();
+            for (set_id, rule, config_paths, outcome) in dry_runs {
+                if failed_sets.contains(&set_id) {
+                    continue;
+                }
+                let _rule = Phase::enter(
+                    debug_span!("ast_apply", set = %set_id, rule = %rule),
+                    "ast-grep apply",
+                );
+                match outcome {
+                    AstRunOutcome::Applied(summary_run) => {
+                        if opts.sarif_out.is_some() {
+                            findings.extend(sarif_findings(&set_id, &rule, &vendor, &summary_run));
+                        }
+                        let matches = summary_run.match_count();
+                        debug!(matches, "applying");
+                        ast_pb.set_message(format!("{set_id} → {matches} matches"));
+                        let before = snapshot_matched(&vendor, &summary_run);
+                        let applied = match driver.run_many(&config_paths, &vendor, AstMode::Apply)
+                        {
+                            Ok(applied) => applied,
+                            Err(err) => {
+                                keep_going_or_fail(
+                                    &mut registry,
+                                    &mut summary,
+                                    opts.keep_going,
+                                    &set_id,
+                                    err,
+                                )?;
+                                failed_sets.insert(set_id);
+                                continue;
+                            }
+                        };
+                        match applied {
+                            // The dry run matched, so an empty apply report
+                            // still means the rewrite ran.
+                            AstRunOutcome::Applied(_) | AstRunOutcome::NoMatches { .. } => {
+                                let (bytes, files) = changed_source_bytes(&before);
+                                summary.ast_notes.push(format!(
+                                    "rule {rule} changed {bytes} source bytes across {files} files"
+                                ));
+                                registry.record_run(
+                                    &set_id,
+                                    Some(matches),
+                                    PatchResult::Applied {
+                                        changed_files: files,
+                                    },
+                                )?;
+                                applied_sets.insert(set_id.clone());
+                            }
+                            AstRunOutcome::Skipped { reason } => {
+                                warn!("ast rule {} skipped: {}", rule, reason);
+                                summary.warnings.push(Warning::new(
+                                    WarningLevel::Runtime,
+                                    "ast-grep",
+                                    reason.clone(),
+                                ));
+                                registry.record_run(
+                                    &set_id,
+                                    Some(matches),
+                                    PatchResult::Skipped {
+                                        reason: Some(reason),
+                                    },
+                                )?;
+                            }
+                        }
+                    }
+                    AstRunOutcome::NoMatches { .. } => {
+                        debug!("no matches");
+                        record_no_matches(&mut registry, &applied_sets, &set_id)?;
+                    }
+                    AstRunOutcome::Skipped { reason } => {
+                        warn!("ast dry run {} skipped: {}", rule, reason);
+                        registry.record_run(
+                            &set_id,
+                            None,
+                            PatchResult::Skipped {
+                                reason: Some(reason),
+                            },
+                        )?;
+                    }
+                }
+            }
+        } else if !opts.assert_nonzero_tags.is_empty() {
+            anyhow::bail!("ast-grep binary not found; cannot assert matches for tagged sets");
+        } else {
+            summary.warnings.push(Warning::new(
+                WarningLevel::Config,
+                "ast-grep",
+                "ast-grep binary not found; skipping",
+            ));
+        }
+    }
+    ast_pb.finish_with_message("ast-grep complete");
+    if let Some(sarif_path) = &opts.sarif_out {
+        sarif::write_sarif(sarif_path, &findings, &sarif_notices)?;
+    }
+
+    if let Some(cocci_dir) = opts
+        .coccinelle_rules_dir
+        .as_ref()
+        .filter(|_| opts.cocci_enabled)
+    {
+        let _phase = Phase::enter(info_span!("cocci", rules_dir = %cocci_dir), "coccinelle");
+        if let Some(driver) = CocciDriver::detect_with(cocci_dir, opts.coccinelle_bin.as_deref())? {
+            let driver = driver
+                .with_ignore_globs(&opts.ignore_globs)?
+                .with_iso_file(opts.coccinelle_iso_file.clone())
+                .with_macro_file(opts.coccinelle_macro_file.clone());
+            for missing in driver.missing_includes() {
+                warn!("coccinelle include {missing} missing; running rules without it");
+                summary.warnings.push(Warning::new(
+                    WarningLevel::Config,
+                    "coccinelle",
+                    format!("include file {missing} missing; rules ran without it"),
+                ));
+            }
+            cocci_pb.set_message("coccinelle pass");
+            // Rules come from the registry so nested and absolute paths run
+            // exactly as listed; a rule shared by several sets runs once.
+            let mut rules = Vec::new();
+            for set in &registry.patch_sets {
+                if !set.enabled || !selected(&opts.only_ids, &set.id) {
+                    continue;
+                }
+                for rule in set.rules.iter().filter(|r| r.ends_with(".cocci")) {
+                    let path = resolve_rule(cocci_dir, rule);
+                    if !rules.contains(&path) {
+                        rules.push(path);
+                    }
+                }
+            }
+            let mut reports = Vec::new();
+            for rule in &rules {
+                let before = dirty_files(&vendor)?;
+                let report = driver.run_rule(rule, &vendor)?;
+                let changed = changed_paths(&before, &dirty_files(&vendor)?);
+                reports.push((report, changed));
+            }
+            for (item, changed) in &reports {
+                let mut note = format!(
+                    "{} -> {:?} exit={:?}",
+                    item.rule, item.status, item.exit_code
+                );
+                if item.args.len() > 2 {
+                    note.push_str(&format!(" args={}", item.args[2..].join(" ")));
+                }
+                summary.cocci_notes.push(note);
+                let result = match item.status {
+                    CocciStatus::Applied => PatchResult::Applied {
+                        changed_files: changed.len() as u64,
+                    },
+                    CocciStatus::NoChange => PatchResult::Skipped {
+                        reason: Some("no change".into()),
+                    },
+                    CocciStatus::Error => PatchResult::Failed {
+                        error: format!("exit {:?}: {}", item.exit_code, item.stderr.trim()),
+                    },
+                };
+                for set in registry.patch_sets.clone() {
+                    if set.enabled
+                        && selected(&opts.only_ids, &set.id)
+                        && set
+                            .rules
+                            .iter()
+                            .any(|r| resolve_rule(cocci_dir, r) == item.rule)
+                    {
+                        registry.record_run(&set.id, None, result.clone())?;
+                    }
+                }
+            }
+        } else {
+            summary.warnings.push(Warning::new(
+                WarningLevel::Config,
+                "coccinelle",
+                "coccinelle-for-rust missing; skipped",
+            ));
+        }
+    }
+    cocci_pb.finish_with_message("coccinelle complete");
+
+    if let Some(grit_dir) = &opts.grit_rules_dir {
+        let _phase = Phase::enter(info_span!("grit", rules_dir = %grit_dir), "grit");
+        if let Some(driver) = GritDriver::detect(grit_dir)? {
+            grit_pb.set_message("grit dry-run");
+            for set in registry.patch_sets.clone() {
+                if !set.enabled || !selected(&opts.only_ids, &set.id) {
+                    continue;
+                }
+                for rule in set.rules.iter().filter(|r| r.ends_with(".grit")) {
+                    let _rule = Phase::enter(
+                        debug_span!("grit_pattern", set = %set.id, rule = %rule),
+                        "grit pattern",
+                    );
+                    let pattern_path = resolve_rule(grit_dir, rule);
+                    let dry_run =
+                        match driver.run_with_config(&pattern_path, &vendor, GritMode::DryRun) {
+                            Ok(outcome) => outcome,
+                            Err(err) => {
+                                keep_going_or_fail(
+                                    &mut registry,
+                                    &mut summary,
+                                    opts.keep_going,
+                                    &set.id,
+                                    err,
+                                )?;
+                                break;
+                            }
+                        };
+                    match dry_run {
+                        GritRunOutcome::Applied(summary_run) => {
                             let estimated = summary_run.stdout.lines().count() as u64;
-                            ast_pb.set_message(format!("{} → {} matches", set.id, estimated));
-                            match driver.run_with_config(&config_path, &vendor, AstMode::Apply)? {
-                                AstRunOutcome::Applied(apply_summary) => {
-                                    summary.ast_notes.push(format!(
-                                        "rule {} changed {} bytes",
-                                        rule,
-                                        apply_summary.stdout.len()
-                                    ));
+                            grit_pb.set_message(format!("{} → {} matches", set.id, estimated));
+                            let applied = match driver.run_with_config(
+                                &pattern_path,
+                                &vendor,
+                                GritMode::Apply,
+                            ) {
+                                Ok(outcome) => outcome,
+                                Err(err) => {
+                                    keep_going_or_fail(
+                                        &mut registry,
+                                        &mut summary,
+                                        opts.keep_going,
+                                        &set.id,
+                                        err,
+                                    )?;
+                                    break;
+                                }
+                            };
+                            match applied {
+                                GritRunOutcome::Applied(_) => {
+                                    summary
+                                        .grit_notes
+                                        .push(format!("pattern {rule} matched {estimated} lines"));
                                     registry.record_run(
                                         &set.id,
                                         Some(estimated),
@@ -86,9 +755,13 @@ pub fn run_update(opts: UpdateOptions) -> Result<UpdateSummary> {
                                         },
                                     )?;
                                 }
-                                AstRunOutcome::Skipped { reason } => {
-                                    warn!("ast rule {} skipped: {}", rule, reason);
-                                    summary.warnings.push(reason.clone());
+                                GritRunOutcome::Skipped { reason } => {
+                                    warn!("grit pattern {} skipped: {}", rule, reason);
+                                    summary.warnings.push(Warning::new(
+                                        WarningLevel::Runtime,
+                                        "grit",
+                                        reason.clone(),
+                                    ));
                                     registry.record_run(
                                         &set.id,
                                         Some(estimated),
@@ -99,8 +772,8 @@ pub fn run_update(opts: UpdateOptions) -> Result<UpdateSummary> {
                                 }
                             }
                         }
-                        AstRunOutcome::Skipped { reason } => {
-                            warn!("ast dry run {} skipped: {}", rule, reason);
+                        GritRunOutcome::Skipped { reason } => {
+                            warn!("grit dry run {} skipped: {}", rule, reason);
                             registry.record_run(
                                 &set.id,
                                 None,
@@ -113,47 +786,511 @@ pub fn run_update(opts: UpdateOptions) -> Result<UpdateSummary> {
                 }
             }
         } else {
-            summary
-                .warnings
-                .push("ast-grep binary not found; skipping".into());
+            summary.warnings.push(Warning::new(
+                WarningLevel::Config,
+                "grit",
+                "grit binary not found; skipping",
+            ));
         }
     }
-    ast_pb.finish_with_message("ast-grep complete");
+    grit_pb.finish_with_message("grit complete");
 
-    if let Some(cocci_dir) = &opts.coccinelle_rules_dir {
-        if let Some(driver) = CocciDriver::detect(cocci_dir)? {
-            cocci_pb.set_message("coccinelle pass");
-            let report = driver.run(&vendor)?;
-            for item in &report.reports {
-                let note = format!(
-                    "{} -> success={} exit={:?}",
-                    item.rule, item.success, item.exit_code
-                );
-                summary.cocci_notes.push(note);
-            }
-        } else {
-            summary
-                .warnings
-                .push("coccinelle-for-rust missing; skipped".into());
-        }
+    match run_cmd("git", &["diff", "--shortstat", "HEAD"], &vendor) {
+        Ok(output) => summary.diff_stat = Some(DiffStat::parse(&output)),
+        Err(err) => warn!("vendor diff stat unavailable: {err:#}"),
     }
-    cocci_pb.finish_with_message("coccinelle complete");
 
     if opts.cargo_check {
+        let check_dir = match &opts.cargo_check_dir {
+            Some(dir) => vendor.join(dir),
+            None => vendor.clone(),
+        };
+        if !check_dir.is_dir() {
+            anyhow::bail!("cargo check dir {check_dir} does not exist");
+        }
+        let _phase = Phase::enter(
+            info_span!("cargo_check", workdir = %check_dir),
+            "cargo check",
+        );
         cargo_pb.set_message("cargo check");
-        summary.cargo_check_passed = run_cargo_check(&vendor)?;
+        let outcome = run_cargo_check(&check_dir, &opts.cargo_check_args)?;
+        summary.cargo_check_passed = outcome.passed;
+        if !outcome.passed {
+            let detail = match (outcome.errors.len(), outcome.stderr_tail) {
+                (0, Some(tail)) => tail,
+                (0, None) => "no compiler errors reported".to_string(),
+                (n, _) => format!("{n} compiler error(s)"),
+            };
+            summary.warnings.push(Warning::new(
+                WarningLevel::Critical,
+                "cargo check",
+                format!("failed in {check_dir}: {detail}"),
+            ));
+        }
+        summary.cargo_errors = outcome.errors;
         cargo_pb.finish_with_message("cargo check complete");
     }
 
     if let Some(zip_path) = opts.output_zip.as_ref() {
-        build_zip(&vendor, zip_path)?;
+        let _phase = Phase::enter(info_span!("zip", output = %zip_path), "zip");
+        let zip_pb = m.add(ProgressBar::new(source_size(&vendor)));
+        zip_pb.set_style(
+            ProgressStyle::with_template(
+                "{bar:30} {percent:>3}% {bytes}/{total_bytes} zip ({msg}, {eta})",
+            )
+            .unwrap(),
+        );
+        let zip_opts = ZipOptions {
+            compression: opts.zip_compression,
+            ..Default::default()
+        };
+        build_zip(&vendor, zip_path, &zip_opts, Some(&zip_pb))?;
+        zip_pb.finish_with_message("zip complete");
     }
     let _ = m.clear();
 
+    summary.patch_sets = registry
+        .patch_sets
+        .iter()
+        .filter(|set| set.last_applied_at.is_some_and(|at| at >= started))
+        .map(|set| PatchSetReport {
+            id: set.id.clone(),
+            match_count: set.last_match_count,
+            result: set.last_result.clone(),
+        })
+        .collect();
+    if let Some(worktree) = &worktree {
+        if !run_passed(&summary, opts.cargo_check) {
+            summary.warnings.push(Warning::new(
+                WarningLevel::Critical,
+                "worktree",
+                format!("run did not pass; {main_vendor} left untouched"),
+            ));
+        } else {
+            let _phase = Phase::enter(info_span!("promote", worktree = %worktree.path), "promote");
+            worktree.promote()?;
+        }
+    }
+    if let (Some(commit), Some(baseline)) = (&opts.commit, &commit_baseline) {
+        if !run_passed(&summary, opts.cargo_check) {
+            summary.warnings.push(Warning::new(
+                WarningLevel::Runtime,
+                "commit",
+                "run did not pass; nothing committed",
+            ));
+        } else {
+            let _phase = Phase::enter(info_span!("commit", vendor = %main_vendor), "commit");
+            let applied = summary
+                .patch_sets
+                .iter()
+                .filter(|set| matches!(set.result, Some(PatchResult::Applied { .. })))
+                .count();
+            let upstream = summary.vendor_rev_after.as_deref().unwrap_or("unknown");
+            let message = commit.render_message(applied, &upstream[..upstream.len().min(10)]);
+            let changed = changed_paths(baseline, &dirty_files(&main_vendor)?);
+            summary.commit_sha =
+                commit_vendor(&main_vendor, &changed, &message, commit.author.as_deref())?;
+        }
+    }
+    if let Some(inputs) = inputs_hash {
+        if run_passed(&summary, opts.cargo_check) {
+            let stored = StoredFingerprint {
+                inputs,
+                vendor_state: vendor_state(&main_vendor)?,
+            };
+            if let Err(err) = stored.save(&opts.workspace_root) {
+                warn!("rules hash not saved: {err:#}");
+            }
+        }
+    }
     registry_store.save(&registry)?;
+    if let Some(path) = &opts.metrics_out {
+        metrics::write_metrics(path, &summary, opts.cargo_check)?;
+    }
+
+    if let Some(url) = &opts.webhook_url {
+        if let Err(err) = webhook::notify(url, &summary) {
+            warn!("webhook delivery failed: {err:#}");
+            summary.warnings.push(Warning::new(
+                WarningLevel::Critical,
+                "webhook",
+                format!("delivery failed: {err:#}"),
+            ));
+        }
+    }
+    if let Some(path) = &opts.history_log {
+        record_history(path, started, &mut summary, opts.cargo_check);
+    }
+    if let Some(path) = &opts.summary_out {
+        summary.write_json(path)?;
+    }
+    if opts.fail_on_warnings && !summary.warnings.is_empty() {
+        let list: Vec<String> = summary
+            .warnings
+            .iter()
+            .map(|w| format!("  [{:?}] {w}", w.severity))
+            .collect();
+        anyhow::bail!(
+            "{} warning(s) with --fail-on-warnings:\n{}",
+            summary.warnings.len(),
+            list.join("\n")
+        );
+    }
     Ok(summary)
 }
 
+/// Appends the run to the history log; a write failure only warns.
+fn record_history(
+    path: &Utf8Path,
+    started: chrono::DateTime<Utc>,
+    summary: &mut UpdateSummary,
+    cargo_check: bool,
+) {
+    let entry = HistoryEntry::from_summary(started, summary, cargo_check);
+    if let Err(err) = append_history(path, &entry) {
+        warn!("history log not written: {err:#}");
+        summary.warnings.push(Warning::new(
+            WarningLevel::Runtime,
+            "history",
+            format!("log not written: {err:#}"),
+        ));
+    }
+}
+
+/// Rules that are not coccinelle or grit files belong to ast-grep.
+fn is_ast_rule(rule: &str) -> bool {
+    !rule.ends_with(".cocci") && !rule.ends_with(".grit")
+}
+
+/// Whether `--only` leaves `id` in the run.
+fn selected(only_ids: &[String], id: &str) -> bool {
+    only_ids.is_empty() || only_ids.iter().any(|only| only == id)
+}
+
+/// Dry-runs one registry rule (expanded if it is a glob), reusing `cache`
+/// output when the rule files are unchanged.
+fn ast_dry_run(
+    driver: &AstGrepDriver,
+    cache: Option<&mut AstCache>,
+    rule: &str,
+    ignore_globs: &[String],
+    vendor: &Utf8Path,
+) -> Result<(Vec<Utf8PathBuf>, AstRunOutcome)> {
+    let config_paths = driver.expand_rules(rule)?;
+    // Ignore globs change what ast-grep reports, so they are part of the key.
+    let key = std::iter::once(rule.to_string())
+        .chain(ignore_globs.iter().map(|glob| format!("!{glob}")))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(stdout) = cache.as_ref().and_then(|c| c.get(&key, &config_paths)) {
+        let run = AstRunSummary::from_json(AstMode::DryRun, stdout);
+        let outcome = if run.match_count() == 0 {
+            AstRunOutcome::NoMatches { duration_ms: 0 }
+        } else {
+            AstRunOutcome::Applied(run)
+        };
+        return Ok((config_paths, outcome));
+    }
+    let outcome = driver.run_many(&config_paths, vendor, AstMode::DryRun)?;
+    match (cache, &outcome) {
+        // A truncated run's stdout no longer carries the full match count.
+        (Some(cache), AstRunOutcome::Applied(run)) if !run.is_truncated() => {
+            cache.insert(&key, &config_paths, &run.stdout)
+        }
+        (Some(cache), AstRunOutcome::NoMatches { .. }) => cache.insert(&key, &config_paths, "[]"),
+        _ => {}
+    }
+    Ok((config_paths, outcome))
+}
+
+/// With `keep_going`, records `err` as the set's `Failed` result plus a
+/// warning and returns `Ok` so the caller moves on; otherwise returns `err`.
+fn keep_going_or_fail(
+    registry: &mut Registry,
+    summary: &mut UpdateSummary,
+    keep_going: bool,
+    set_id: &str,
+    err: anyhow::Error,
+) -> Result<()> {
+    if !keep_going {
+        return Err(err);
+    }
+    let error = format!("{err:#}");
+    warn!("patch set {set_id} failed: {error}");
+    summary.warnings.push(Warning::new(
+        WarningLevel::Critical,
+        set_id,
+        format!("patch set failed: {error}"),
+    ));
+    registry.record_run(set_id, None, PatchResult::Failed { error })?;
+    Ok(())
+}
+
+/// Reads `.forksmithignore`-style globs: one per line, blank lines and `#`
+/// comments skipped. A missing file yields no globs.
+pub fn read_ignore_file(path: &Utf8Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("reading ignore file {path}"))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Sets whose dry runs matched more than their limit, with `(total, limit)`.
+/// A set's own `max_matches` overrides `default_limit`.
+fn over_match_limit(
+    registry: &Registry,
+    dry_runs: &[(String, String, Vec<Utf8PathBuf>, AstRunOutcome)],
+    default_limit: Option<u64>,
+) -> BTreeMap<String, (u64, u64)> {
+    let mut totals: BTreeMap<&str, u64> = BTreeMap::new();
+    for (set_id, _, _, outcome) in dry_runs {
+        if let AstRunOutcome::Applied(run) = outcome {
+            *totals.entry(set_id.as_str()).or_default() += run.match_count();
+        }
+    }
+    totals
+        .into_iter()
+        .filter_map(|(id, total)| {
+            let limit = registry
+                .patch_sets
+                .iter()
+                .find(|set| set.id == id)
+                .and_then(|set| set.max_matches)
+                .or(default_limit)?;
+            (total > limit).then(|| (id.to_string(), (total, limit)))
+        })
+        .collect()
+}
+
+/// Errors listing every enabled set tagged with one of `tags` whose ast-grep
+/// dry runs matched nothing (a skipped dry run counts as zero) or failed
+/// under `--keep-going`. Sets without ast-grep rules or left out by `--only`
+/// are not checked.
+fn assert_nonzero_matches(
+    registry: &Registry,
+    dry_runs: &[(String, String, Vec<Utf8PathBuf>, AstRunOutcome)],
+    failed: &BTreeSet<String>,
+    only_ids: &[String],
+    tags: &[String],
+) -> Result<()> {
+    if tags.is_empty() {
+        return Ok(());
+    }
+    let mut errored = Vec::new();
+    let mut unmatched = Vec::new();
+    for set in registry.patch_sets.iter().filter(|set| {
+        set.enabled
+            && selected(only_ids, &set.id)
+            && set.tags.iter().any(|t| tags.contains(t))
+            && set.rules.iter().any(|r| is_ast_rule(r))
+    }) {
+        if failed.contains(&set.id) {
+            errored.push(set.id.as_str());
+            continue;
+        }
+        let mut runs = dry_runs.iter().filter(|(id, ..)| *id == set.id).peekable();
+        if runs.peek().is_none() {
+            continue;
+        }
+        let matches: u64 = runs
+            .map(|(.., outcome)| match outcome {
+                AstRunOutcome::Applied(run) => run.match_count(),
+                AstRunOutcome::NoMatches { .. } | AstRunOutcome::Skipped { .. } => 0,
+            })
+            .sum();
+        if matches == 0 {
+            unmatched.push(set.id.as_str());
+        }
+    }
+    let tags = tags.join("/");
+    match (errored.is_empty(), unmatched.is_empty()) {
+        (true, true) => Ok(()),
+        (true, false) => anyhow::bail!(
+            "patch sets tagged {tags} matched nothing: {}",
+            unmatched.join(", ")
+        ),
+        (false, true) => anyhow::bail!(
+            "patch sets tagged {tags} failed their dry run: {}",
+            errored.join(", ")
+        ),
+        (false, false) => anyhow::bail!(
+            "patch sets tagged {tags} failed their dry run: {}; matched nothing: {}",
+            errored.join(", "),
+            unmatched.join(", ")
+        ),
+    }
+}
+
+/// One warning per file that more than one patch set's dry run matched.
+fn overlap_warnings(claims: &BTreeMap<String, BTreeSet<String>>) -> Vec<String> {
+    claims
+        .iter()
+        .filter(|(_, sets)| sets.len() > 1)
+        .map(|(file, sets)| {
+            let ids: Vec<&str> = sets.iter().map(String::as_str).collect();
+            format!(
+                "{file} is targeted by multiple patch sets: {}",
+                ids.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Contents of every path `git status` reports as changed or untracked;
+/// deleted files map to no contents.
+fn dirty_files(repo: &Utf8Path) -> Result<BTreeMap<Utf8PathBuf, Option<Vec<u8>>>> {
+    let status = run_cmd(
+        "git",
+        &[
+            "status",
+            "--porcelain",
+            "-z",
+            "--untracked-files=all",
+            "--no-renames",
+        ],
+        repo,
+    )?;
+    Ok(status
+        .split('\0')
+        .filter_map(|entry| entry.get(3..))
+        .filter(|path| !path.is_empty())
+        .map(|path| {
+            let path = Utf8PathBuf::from(path);
+            let contents = fs::read(repo.join(&path)).ok();
+            (path, contents)
+        })
+        .collect())
+}
+
+/// Paths whose dirty state differs between two [`dirty_files`] snapshots.
+fn changed_paths(
+    before: &BTreeMap<Utf8PathBuf, Option<Vec<u8>>>,
+    after: &BTreeMap<Utf8PathBuf, Option<Vec<u8>>>,
+) -> BTreeSet<Utf8PathBuf> {
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|path| before.get(*path) != after.get(*path))
+        .cloned()
+        .collect()
+}
+
+/// Warning for a dry run that matched more than [`MAX_RETAINED_MATCHES`]:
+/// overlap detection, the change stats and SARIF only see the retained ones.
+fn truncation_notice(set_id: &str, rule: &str, run: &AstRunSummary) -> String {
+    format!(
+        "{set_id}: rule {rule} matched {} times; only the first {MAX_RETAINED_MATCHES} \
+         were used for overlap detection, change stats and SARIF",
+        run.match_count()
+    )
+}
+
+/// Contents of the files a dry run matched, taken before the apply so the
+/// rewrite can be measured on disk.
+fn snapshot_matched(vendor: &Utf8Path, run: &AstRunSummary) -> BTreeMap<Utf8PathBuf, Vec<u8>> {
+    run.matches()
+        .into_iter()
+        .filter_map(|m| {
+            let path = vendor.join(relative_to(vendor, m.file));
+            let bytes = fs::read(&path).ok()?;
+            Some((path, bytes))
+        })
+        .collect()
+}
+
+/// Source bytes rewritten and files touched since `before` was taken.
+fn changed_source_bytes(before: &BTreeMap<Utf8PathBuf, Vec<u8>>) -> (u64, u64) {
+    let mut bytes = 0;
+    let mut files = 0;
+    for (path, old) in before {
+        let new = fs::read(path).unwrap_or_default();
+        if *old != new {
+            files += 1;
+            bytes += changed_span(old, &new) as u64;
+        }
+    }
+    (bytes, files)
+}
+
+/// Length of the region between the common prefix and suffix, measured on
+/// the longer side.
+fn changed_span(old: &[u8], new: &[u8]) -> usize {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    old.len().max(new.len()) - prefix - suffix
+}
+
+fn relative_to(vendor: &Utf8Path, file: String) -> String {
+    Utf8Path::new(&file)
+        .strip_prefix(vendor)
+        .map(|p| p.to_string())
+        .unwrap_or(file)
+}
+
+fn sarif_findings(
+    set_id: &str,
+    rule: &str,
+    vendor: &Utf8Path,
+    run: &AstRunSummary,
+) -> Vec<SarifFinding> {
+    run.matches()
+        .into_iter()
+        .map(|m| {
+            let uri = relative_to(vendor, m.file);
+            SarifFinding {
+                rule_id: m.rule_id.unwrap_or_else(|| rule.to_string()),
+                message: m
+                    .message
+                    .unwrap_or_else(|| format!("{set_id}: rule {rule} matched")),
+                uri,
+                start_line: m.start_line,
+                end_line: m.end_line,
+            }
+        })
+        .collect()
+}
+
+/// Enters `span` and logs start/finish (with elapsed time) at debug level;
+/// the span is exited when the guard drops.
+struct Phase {
+    name: &'static str,
+    start: Instant,
+    _span: EnteredSpan,
+}
+
+impl Phase {
+    fn enter(span: Span, name: &'static str) -> Self {
+        let span = span.entered();
+        debug!("{name} started");
+        Self {
+            name,
+            start: Instant::now(),
+            _span: span,
+        }
+    }
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        debug!(
+            elapsed_ms = self.start.elapsed().as_millis() as u64,
+            "{} finished", self.name
+        );
+    }
+}
+
 fn progress_spinner(label: &str) -> ProgressBar {
     let pb = ProgressBar::new_spinner();
     pb.set_style(
@@ -165,25 +1302,121 @@ fn progress_spinner(label: &str) -> ProgressBar {
     pb
 }
 
-fn sync_upstream(vendor: &Utf8Path, branch: &str) -> Result<()> {
-    run_cmd("git", &["fetch", "origin"], vendor)?;
-    run_cmd(
-        "git",
-        &["reset", "--hard", &format!("origin/{branch}")],
-        vendor,
-    )?;
+/// Fetches `remote` and resolves the commit the vendor should be synced to.
+fn fetch_target(
+    vendor: &Utf8Path,
+    remote: &str,
+    branch: &str,
+    rev: Option<&str>,
+) -> Result<String> {
+    fetch_remote(vendor, remote).with_context(|| format!("fetching remote {remote}"))?;
+    Ok(match rev {
+        Some(rev) => {
+            let spec = format!("{rev}^{{commit}}");
+            run_cmd("git", &["rev-parse", "--verify", "--quiet", &spec], vendor)
+                .with_context(|| format!("pinned revision {rev} not found after fetch"))?
+                .trim()
+                .to_string()
+        }
+        None => format!("{remote}/{branch}"),
+    })
+}
+
+/// `git fetch <remote> --tags`, authenticated per [`remote_git_command`].
+fn fetch_remote(vendor: &Utf8Path, remote: &str) -> Result<()> {
+    let (mut cmd, token) =
+        remote_git_command(vendor.as_std_path(), remote, &["fetch", remote, "--tags"]);
+    let output = cmd
+        .output()
+        .with_context(|| format!("running git fetch in {vendor}"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git fetch failed: {}", redact(&stderr, token.as_deref()));
+    }
     Ok(())
 }
 
+/// Records a rule that matched nothing as skipped, unless another rule of
+/// the same set already applied this run.
+fn record_no_matches(
+    registry: &mut Registry,
+    applied_sets: &BTreeSet<String>,
+    set_id: &str,
+) -> Result<()> {
+    if applied_sets.contains(set_id) {
+        return Ok(());
+    }
+    registry.record_run(
+        set_id,
+        Some(0),
+        PatchResult::Skipped {
+            reason: Some("no matches".into()),
+        },
+    )
+}
+
+/// Whether no patch set failed and, when it ran, cargo check passed.
+fn run_passed(summary: &UpdateSummary, cargo_check: bool) -> bool {
+    !summary.has_failures() && (!cargo_check || summary.cargo_check_passed)
+}
+
+/// Stages `paths` (additions, edits and deletions) and commits them; `None`
+/// when there was nothing to commit.
+fn commit_vendor(
+    repo: &Utf8Path,
+    paths: &BTreeSet<Utf8PathBuf>,
+    message: &str,
+    author: Option<&str>,
+) -> Result<Option<String>> {
+    if paths.is_empty() {
+        return Ok(None);
+    }
+    // Pathspecs go through stdin so a large change cannot overflow argv.
+    let mut child = Command::new("git")
+        .args(["add", "-A", "--pathspec-from-file=-", "--pathspec-file-nul"])
+        .current_dir(repo)
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running git add in {repo}"))?;
+    let mut pathspecs = Vec::new();
+    for path in paths {
+        pathspecs.extend_from_slice(format!(":(literal){path}").as_bytes());
+        pathspecs.push(0);
+    }
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(&pathspecs)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git add failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    let unchanged = Command::new("git")
+        .args(["diff", "--cached", "--quiet"])
+        .current_dir(repo)
+        .status()
+        .with_context(|| format!("running git diff in {repo}"))?
+        .success();
+    if unchanged {
+        return Ok(None);
+    }
+    let author_arg = author.map(|author| format!("--author={author}"));
+    let mut args = vec!["commit", "--quiet", "-m", message];
+    args.extend(author_arg.as_deref());
+    run_cmd("git", &args, repo)?;
+    read_git_rev(repo).map(Some)
+}
+
 fn read_git_rev(repo: &Utf8Path) -> Result<String> {
     let output = run_cmd("git", &["rev-parse", "HEAD"], repo)?;
     Ok(output.trim().to_string())
 }
 
-fn run_cargo_check(workdir: &Utf8Path) -> Result<bool> {
-    run_cmd("cargo", &["check"], workdir).map(|_| true)
-}
-
 fn run_cmd(bin: &str, args: &[&str], dir: &Utf8Path) -> Result<String> {
     let output = Command::new(bin)
         .args(args)
@@ -195,3 +1428,241 @@ fn run_cmd(bin: &str, args: &[&str], dir: &Utf8Path) -> Result<String> {
     }
     Ok(String::from_utf8_lossy(&output.stdout).into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_repo(repo: &Utf8Path) {
+        run_cmd("git", &["init", "--quiet", "--initial-branch=main"], repo).unwrap();
+        run_cmd("git", &["config", "user.name", "test"], repo).unwrap();
+        run_cmd("git", &["config", "user.email", "test@example.com"], repo).unwrap();
+    }
+
+    fn commit_all(repo: &Utf8Path, message: &str) {
+        run_cmd("git", &["add", "-A"], repo).unwrap();
+        run_cmd("git", &["commit", "--quiet", "-m", message], repo).unwrap();
+    }
+
+    fn registry(json: &str) -> Registry {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn dry_run(id: &str, matches: usize) -> (String, String, Vec<Utf8PathBuf>, AstRunOutcome) {
+        let outcome = if matches == 0 {
+            AstRunOutcome::NoMatches { duration_ms: 0 }
+        } else {
+            let stdout = serde_json::to_string(&vec![serde_json::json!({}); matches]).unwrap();
+            AstRunOutcome::Applied(AstRunSummary::from_json(AstMode::DryRun, stdout))
+        };
+        (id.to_string(), format!("{id}.yml"), Vec::new(), outcome)
+    }
+
+    #[test]
+    fn match_limit_skips_only_sets_over_their_limit() {
+        let registry = registry(
+            r#"{"patch_sets": [
+                {"id": "under", "description": ""},
+                {"id": "at", "description": ""},
+                {"id": "over", "description": ""},
+                {"id": "loose", "description": "", "max_matches": 10},
+                {"id": "strict", "description": "", "max_matches": 1}
+            ]}"#,
+        );
+        let runs = [
+            dry_run("under", 2),
+            dry_run("at", 2),
+            dry_run("at", 1),
+            dry_run("over", 4),
+            dry_run("loose", 8),
+            dry_run("strict", 2),
+        ];
+        let over = over_match_limit(&registry, &runs, Some(3));
+        assert_eq!(
+            over.into_iter().collect::<Vec<_>>(),
+            [("over".to_string(), (4, 3)), ("strict".to_string(), (2, 1))]
+        );
+
+        let over = over_match_limit(&registry, &runs, None);
+        assert_eq!(over.keys().collect::<Vec<_>>(), ["strict"]);
+    }
+
+    #[test]
+    fn nonzero_assertion_checks_only_ast_sets_with_dry_runs() {
+        let registry = registry(
+            r#"{"patch_sets": [
+                {"id": "ast", "description": "", "rules": ["ast.yml"], "tags": ["core"]},
+                {"id": "cocci", "description": "", "rules": ["fix.cocci"], "tags": ["core"]},
+                {"id": "off", "description": "", "rules": ["off.yml"], "tags": ["core"], "enabled": false},
+                {"id": "empty", "description": "", "rules": ["empty.yml"], "tags": ["other"]}
+            ]}"#,
+        );
+        let tags = ["core".to_string()];
+        let none = BTreeSet::new();
+        let runs = [dry_run("ast", 2), dry_run("empty", 0)];
+        assert_nonzero_matches(&registry, &runs, &none, &[], &tags).unwrap();
+
+        let runs = [dry_run("ast", 0)];
+        let err = assert_nonzero_matches(&registry, &runs, &none, &[], &tags).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "patch sets tagged core matched nothing: ast"
+        );
+    }
+
+    #[test]
+    fn nonzero_assertion_reports_failed_dry_runs_as_errors() {
+        let registry = registry(
+            r#"{"patch_sets": [
+                {"id": "ast", "description": "", "rules": ["ast.yml"], "tags": ["core"]}
+            ]}"#,
+        );
+        let failed = BTreeSet::from(["ast".to_string()]);
+        let err =
+            assert_nonzero_matches(&registry, &[], &failed, &[], &["core".into()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "patch sets tagged core failed their dry run: ast"
+        );
+    }
+
+    #[test]
+    fn no_matches_after_an_apply_keeps_the_applied_result() {
+        let mut registry = registry(
+            r#"{"patch_sets": [
+                {"id": "a", "description": "", "rules": ["one.yml", "two.yml"]},
+                {"id": "b", "description": "", "rules": ["three.yml"]}
+            ]}"#,
+        );
+        let applied = PatchResult::Applied { changed_files: 2 };
+        registry.record_run("a", Some(4), applied.clone()).unwrap();
+        let applied_sets = BTreeSet::from(["a".to_string()]);
+
+        record_no_matches(&mut registry, &applied_sets, "a").unwrap();
+        record_no_matches(&mut registry, &applied_sets, "b").unwrap();
+        let result = |id: &str| {
+            let set = registry.patch_sets.iter().find(|s| s.id == id).unwrap();
+            set.last_result.clone()
+        };
+        assert_eq!(result("a"), Some(applied));
+        assert_eq!(
+            result("b"),
+            Some(PatchResult::Skipped {
+                reason: Some("no matches".into())
+            })
+        );
+    }
+
+    #[test]
+    fn changed_span_measures_the_edited_region() {
+        let old = b"first\nmiddle\nlast\n";
+        assert_eq!(changed_span(old, old), 0);
+        assert_eq!(changed_span(b"", b""), 0);
+        assert_eq!(changed_span(old, b"FIRST\nmiddle\nlast\n"), 5);
+        assert_eq!(changed_span(old, b"first\nmiddle\nlast line\n"), 5);
+        // A pure insertion or deletion spans the inserted or removed bytes.
+        assert_eq!(changed_span(old, b"first\nmiddle\nmiddle\nlast\n"), 7);
+        assert_eq!(changed_span(old, b"first\nlast\n"), 7);
+        assert_eq!(changed_span(b"", b"new"), 3);
+        assert_eq!(changed_span(b"gone", b""), 4);
+    }
+
+    #[test]
+    fn diff_stat_parses_every_shortstat_shape() {
+        let stat = |files_changed, insertions, deletions| DiffStat {
+            files_changed,
+            insertions,
+            deletions,
+        };
+        for (shortstat, expected) in [
+            (
+                " 3 files changed, 10 insertions(+), 2 deletions(-)\n",
+                stat(3, 10, 2),
+            ),
+            (" 2 files changed, 5 insertions(+)\n", stat(2, 5, 0)),
+            (" 4 files changed, 7 deletions(-)\n", stat(4, 0, 7)),
+            (
+                " 1 file changed, 1 insertion(+), 1 deletion(-)\n",
+                stat(1, 1, 1),
+            ),
+            ("", stat(0, 0, 0)),
+        ] {
+            assert_eq!(DiffStat::parse(shortstat), expected, "{shortstat:?}");
+        }
+    }
+
+    #[test]
+    fn commit_vendor_stages_only_the_given_paths() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Utf8Path::from_path(tmp.path()).unwrap();
+        init_repo(repo);
+        fs::write(repo.join("lib.rs"), "fn a() {}").unwrap();
+        fs::write(repo.join("gone.rs"), "").unwrap();
+        commit_all(repo, "init");
+        fs::write(repo.join("stray.log"), "left over").unwrap();
+
+        let baseline = dirty_files(repo).unwrap();
+        fs::write(repo.join("lib.rs"), "fn b() {}").unwrap();
+        fs::remove_file(repo.join("gone.rs")).unwrap();
+        let changed = changed_paths(&baseline, &dirty_files(repo).unwrap());
+        let sha = commit_vendor(repo, &changed, "apply", None).unwrap();
+        assert!(sha.is_some());
+        let status = run_cmd("git", &["status", "--porcelain"], repo).unwrap();
+        assert_eq!(status, "?? stray.log\n");
+        assert_eq!(
+            commit_vendor(repo, &BTreeSet::new(), "apply", None).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn ignore_file_skips_blanks_and_comments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(tmp.path()).unwrap();
+        let path = dir.join(".forksmithignore");
+        assert!(read_ignore_file(&path).unwrap().is_empty());
+
+        fs::write(&path, "# generated\n\n  target/**  \n*.snap\n").unwrap();
+        assert_eq!(read_ignore_file(&path).unwrap(), ["target/**", "*.snap"]);
+
+        let err = read_ignore_file(dir).unwrap_err();
+        assert_eq!(err.to_string(), format!("reading ignore file {dir}"));
+    }
+
+    #[test]
+    fn changed_paths_counts_new_edited_and_reverted_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Utf8Path::from_path(tmp.path()).unwrap();
+        init_repo(repo);
+        for name in ["clean.rs", "dirty.rs", "reverted.rs", "stable.rs"] {
+            fs::write(repo.join(name), "fn a() {}").unwrap();
+        }
+        commit_all(repo, "init");
+        for name in ["dirty.rs", "reverted.rs", "stable.rs"] {
+            fs::write(repo.join(name), "fn b() {}").unwrap();
+        }
+
+        let before = dirty_files(repo).unwrap();
+        fs::write(repo.join("clean.rs"), "fn c() {}").unwrap();
+        fs::write(repo.join("dirty.rs"), "fn c() {}").unwrap();
+        fs::write(repo.join("reverted.rs"), "fn a() {}").unwrap();
+        fs::write(repo.join("new.rs"), "").unwrap();
+        let changed = changed_paths(&before, &dirty_files(repo).unwrap());
+        let names: Vec<&str> = changed.iter().map(|p| p.as_str()).collect();
+        assert_eq!(names, ["clean.rs", "dirty.rs", "new.rs", "reverted.rs"]);
+    }
+
+    #[test]
+    fn nonzero_assertion_ignores_sets_left_out_by_only() {
+        let registry = registry(
+            r#"{"patch_sets": [
+                {"id": "picked", "description": "", "rules": ["picked.yml"], "tags": ["core"]},
+                {"id": "other", "description": "", "rules": ["other.yml"], "tags": ["core"]}
+            ]}"#,
+        );
+        let failed = BTreeSet::from(["other".to_string()]);
+        let runs = [dry_run("picked", 1), dry_run("other", 0)];
+        let only = ["picked".to_string()];
+        assert_nonzero_matches(&registry, &runs, &failed, &only, &["core".into()]).unwrap();
+    }
+}
@@ -0,0 +1,87 @@
+use codex_registry::PatchResult;
+
+use crate::UpdateSummary;
+
+impl UpdateSummary {
+    /// Renders the summary as GitHub-flavored Markdown for PR comments.
+    pub fn to_markdown(&self) -> String {
+        let mut md = String::from("## codex-forksmith update\n\n");
+        if self.up_to_date {
+            md.push_str("Up to date: nothing changed since the last passing run.\n\n");
+        }
+        md.push_str(&format!(
+            "**Vendor:** `{}` → `{}`\n\n",
+            short_rev(self.vendor_rev_before.as_deref()),
+            short_rev(self.vendor_rev_after.as_deref())
+        ));
+        md.push_str(&format!(
+            "**cargo check:** {}\n\n",
+            if self.cargo_check_passed {
+                "✅ passed"
+            } else {
+                "❌ failed or not run"
+            }
+        ));
+        if !self.cargo_errors.is_empty() {
+            md.push_str("```\n");
+            for error in &self.cargo_errors {
+                md.push_str(&format!("{error}\n"));
+            }
+            md.push_str("```\n\n");
+        }
+
+        if !self.patch_sets.is_empty() {
+            md.push_str("| Patch set | Matches | Status |\n");
+            md.push_str("| --- | ---: | --- |\n");
+            for set in &self.patch_sets {
+                let matches = set
+                    .match_count
+                    .map(|count| count.to_string())
+                    .unwrap_or_else(|| "–".into());
+                md.push_str(&format!(
+                    "| `{}` | {} | {} |\n",
+                    escape_cell(&set.id),
+                    matches,
+                    escape_cell(&status_cell(set.result.as_ref()))
+                ));
+            }
+            md.push('\n');
+        }
+
+        if !self.warnings.is_empty() {
+            md.push_str(&format!(
+                "<details>\n<summary>⚠️ Warnings ({})</summary>\n\n",
+                self.warnings.len()
+            ));
+            for warning in &self.warnings {
+                md.push_str(&format!("- **{:?}** {warning}\n", warning.severity));
+            }
+            md.push_str("\n</details>\n");
+        }
+        md
+    }
+}
+
+fn status_cell(result: Option<&PatchResult>) -> String {
+    match result {
+        Some(PatchResult::Applied { changed_files }) => {
+            format!("✅ applied ({changed_files} changed)")
+        }
+        Some(PatchResult::Skipped { reason }) => {
+            format!("⚠️ skipped ({})", reason.as_deref().unwrap_or("no reason"))
+        }
+        Some(PatchResult::Failed { error }) => format!("❌ failed: {error}"),
+        None => "–".into(),
+    }
+}
+
+fn short_rev(rev: Option<&str>) -> &str {
+    match rev {
+        Some(rev) => &rev[..rev.len().min(7)],
+        None => "unknown",
+    }
+}
+
+fn escape_cell(raw: &str) -> String {
+    raw.replace('|', "\\|").replace('\n', " ")
+}
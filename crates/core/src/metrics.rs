@@ -0,0 +1,73 @@
+use std::fmt::Write as _;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use codex_registry::PatchResult;
+use fs_err as fs;
+
+use crate::UpdateSummary;
+
+/// Writes the summary in Prometheus text exposition format for the
+/// node_exporter textfile collector. The file is written to a sibling temp
+/// path and renamed into place so a concurrent scrape never sees half of it.
+/// `forksmith_cargo_check` is omitted when the check did not run.
+pub fn write_metrics(path: &Utf8Path, summary: &UpdateSummary, cargo_checked: bool) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("prom.tmp.{}", std::process::id()));
+    fs::write(&tmp, render(summary, cargo_checked))
+        .with_context(|| format!("writing metrics {tmp}"))?;
+    fs::rename(&tmp, path).with_context(|| format!("renaming metrics into {path}"))?;
+    Ok(())
+}
+
+fn render(summary: &UpdateSummary, cargo_checked: bool) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "# HELP forksmith_patch_matches Matches reported for a patch set in the last run.\n",
+    );
+    out.push_str("# TYPE forksmith_patch_matches gauge\n");
+    for set in &summary.patch_sets {
+        if let Some(count) = set.match_count {
+            let _ = writeln!(
+                out,
+                "forksmith_patch_matches{{set=\"{}\"}} {count}",
+                escape_label(&set.id)
+            );
+        }
+    }
+
+    let applied = summary
+        .patch_sets
+        .iter()
+        .filter(|set| matches!(set.result, Some(PatchResult::Applied { .. })))
+        .count();
+    out.push_str("# HELP forksmith_patch_applied_total Patch sets applied in the last run.\n");
+    out.push_str("# TYPE forksmith_patch_applied_total gauge\n");
+    let _ = writeln!(out, "forksmith_patch_applied_total {applied}");
+
+    if cargo_checked {
+        let result = if summary.cargo_check_passed {
+            "pass"
+        } else {
+            "fail"
+        };
+        out.push_str("# HELP forksmith_cargo_check Result of cargo check on the vendor tree.\n");
+        out.push_str("# TYPE forksmith_cargo_check gauge\n");
+        let _ = writeln!(out, "forksmith_cargo_check{{result=\"{result}\"}} 1");
+    }
+
+    let changed = summary.vendor_rev_before != summary.vendor_rev_after;
+    out.push_str("# HELP forksmith_vendor_rev_changed Whether the vendor revision moved.\n");
+    out.push_str("# TYPE forksmith_vendor_rev_changed gauge\n");
+    let _ = writeln!(out, "forksmith_vendor_rev_changed {}", u8::from(changed));
+    out
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use codex_ast_driver::{AstGrepDriver, AstMode, AstRunOutcome};
+use codex_driver_util::resolve_rule;
+use codex_grit_driver::{GritDriver, GritMode, GritRunOutcome};
+use codex_registry::Registry;
+use serde::Serialize;
+
+use crate::{is_ast_rule, read_git_rev, relative_to, run_cmd, selected, Warning, WarningLevel};
+
+/// Inputs for [`run_preview`]. Unlike `UpdateOptions` there is no sync and
+/// no apply switch: every driver runs in dry-run mode and the registry is
+/// only read.
+#[derive(Debug, Clone, Default)]
+pub struct PreviewOptions {
+    pub vendor_dir: Utf8PathBuf,
+    pub registry_path: Utf8PathBuf,
+    pub ast_rules_dir: Option<Utf8PathBuf>,
+    pub coccinelle_rules_dir: Option<Utf8PathBuf>,
+    pub grit_rules_dir: Option<Utf8PathBuf>,
+    pub ast_grep_bin: Option<Utf8PathBuf>,
+    pub ignore_globs: Vec<String>,
+    /// Preview only these patch sets; empty means every enabled set.
+    pub only_ids: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PreviewSummary {
+    pub vendor_rev: Option<String>,
+    pub rules: Vec<RulePreview>,
+    pub warnings: Vec<Warning>,
+}
+
+/// Dry-run result for one registry rule.
+#[derive(Debug, Serialize)]
+pub struct RulePreview {
+    pub set_id: String,
+    pub engine: &'static str,
+    pub rule: String,
+    /// `None` when the rule could not run; see `note`.
+    pub matches: Option<u64>,
+    /// Vendor-relative files the rule would touch.
+    pub files: Vec<String>,
+    pub note: Option<String>,
+}
+
+/// Dry-runs every enabled patch set against the vendor as it is checked out
+/// and fails if the vendor tree differs afterwards in any way.
+pub fn run_preview(opts: &PreviewOptions) -> Result<PreviewSummary> {
+    let vendor = &opts.vendor_dir;
+    if !vendor.exists() {
+        anyhow::bail!("vendor dir {vendor} missing");
+    }
+    let before = tree_state(vendor)?;
+    let registry = Registry::load(&opts.registry_path)?;
+    let sets: Vec<_> = registry
+        .patch_sets
+        .iter()
+        .filter(|set| set.enabled && selected(&opts.only_ids, &set.id))
+        .collect();
+    let mut summary = PreviewSummary {
+        vendor_rev: read_git_rev(vendor).ok(),
+        ..Default::default()
+    };
+
+    if let Some(ast_dir) = &opts.ast_rules_dir {
+        match AstGrepDriver::detect_with(ast_dir, opts.ast_grep_bin.as_deref())? {
+            Some(driver) => {
+                let driver = driver.with_ignore_globs(opts.ignore_globs.clone());
+                for set in &sets {
+                    for rule in set.rules.iter().filter(|r| is_ast_rule(r)) {
+                        let config_paths = driver.expand_rules(rule)?;
+                        let outcome = driver.run_many(&config_paths, vendor, AstMode::DryRun)?;
+                        let preview = match outcome {
+                            AstRunOutcome::Applied(run) => {
+                                let files: BTreeSet<String> = run
+                                    .matches()
+                                    .into_iter()
+                                    .map(|m| relative_to(vendor, m.file))
+                                    .collect();
+                                rule_preview(
+                                    set.id.as_str(),
+                                    "ast-grep",
+                                    rule,
+                                    Some(run.match_count()),
+                                    files,
+                                    None,
+                                )
+                            }
+                            AstRunOutcome::NoMatches { .. } => rule_preview(
+                                set.id.as_str(),
+                                "ast-grep",
+                                rule,
+                                Some(0),
+                                BTreeSet::new(),
+                                None,
+                            ),
+                            AstRunOutcome::Skipped { reason } => rule_preview(
+                                set.id.as_str(),
+                                "ast-grep",
+                                rule,
+                                None,
+                                BTreeSet::new(),
+                                Some(reason),
+                            ),
+                        };
+                        summary.rules.push(preview);
+                    }
+                }
+            }
+            None => summary.warnings.push(Warning::new(
+                WarningLevel::Config,
+                "ast-grep",
+                "ast-grep binary not found; skipping",
+            )),
+        }
+    }
+
+    if opts.coccinelle_rules_dir.is_some() {
+        summary.warnings.push(Warning::new(
+            WarningLevel::Runtime,
+            "coccinelle",
+            "coccinelle has no dry-run mode; not previewed",
+        ));
+    }
+
+    if let Some(grit_dir) = &opts.grit_rules_dir {
+        match GritDriver::detect(grit_dir)? {
+            Some(driver) => {
+                for set in &sets {
+                    for rule in set.rules.iter().filter(|r| r.ends_with(".grit")) {
+                        let outcome = driver.run_with_config(
+                            &resolve_rule(grit_dir, rule),
+                            vendor,
+                            GritMode::DryRun,
+                        )?;
+                        let preview = match outcome {
+                            GritRunOutcome::Applied(run) => {
+                                let matches = run.stdout.lines().count() as u64;
+                                rule_preview(
+                                    set.id.as_str(),
+                                    "grit",
+                                    rule,
+                                    Some(matches),
+                                    BTreeSet::new(),
+                                    None,
+                                )
+                            }
+                            GritRunOutcome::Skipped { reason } => rule_preview(
+                                set.id.as_str(),
+                                "grit",
+                                rule,
+                                None,
+                                BTreeSet::new(),
+                                Some(reason),
+                            ),
+                        };
+                        summary.rules.push(preview);
+                    }
+                }
+            }
+            None => summary.warnings.push(Warning::new(
+                WarningLevel::Config,
+                "grit",
+                "grit binary not found; skipping",
+            )),
+        }
+    }
+
+    if tree_state(vendor)? != before {
+        anyhow::bail!(
+            "vendor tree {vendor} changed during preview; inspect `git status` there (this is a bug)"
+        );
+    }
+    Ok(summary)
+}
+
+fn rule_preview(
+    set_id: &str,
+    engine: &'static str,
+    rule: &str,
+    matches: Option<u64>,
+    files: BTreeSet<String>,
+    note: Option<String>,
+) -> RulePreview {
+    RulePreview {
+        set_id: set_id.to_string(),
+        engine,
+        rule: rule.to_string(),
+        matches,
+        files: files.into_iter().collect(),
+        note,
+    }
+}
+
+/// Everything `git` can see of the working tree: status (including
+/// untracked files) plus the full diff against HEAD.
+fn tree_state(vendor: &Utf8Path) -> Result<String> {
+    let status = run_cmd(
+        "git",
+        &["status", "--porcelain", "--untracked-files=all"],
+        vendor,
+    )?;
+    let diff = run_cmd("git", &["diff", "HEAD", "--binary"], vendor)?;
+    Ok(status + &diff)
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeSet;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use codex_registry::Registry;
+use fs_err as fs;
+use serde::Deserialize;
+
+/// A named selection of patch sets from `[[profile]]` in
+/// `codex-forksmith.toml`: every set listed by id plus every set carrying
+/// one of `tags`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    #[serde(default)]
+    pub patch_sets: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ProfileFile {
+    #[serde(default)]
+    profile: Vec<Profile>,
+}
+
+impl Profile {
+    /// Ids of the registry sets this profile selects, in registry order.
+    pub fn resolve(&self, registry: &Registry) -> Vec<String> {
+        registry
+            .patch_sets
+            .iter()
+            .filter(|set| {
+                self.patch_sets.contains(&set.id) || set.tags.iter().any(|t| self.tags.contains(t))
+            })
+            .map(|set| set.id.clone())
+            .collect()
+    }
+}
+
+/// Reads the `[[profile]]` entries of `config` (none when the file is
+/// missing) and checks that ids are unique and every listed patch set
+/// exists in `registry`.
+pub fn load_profiles(config: &Utf8Path, registry: &Registry) -> Result<Vec<Profile>> {
+    if !config.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(config)?;
+    let file: ProfileFile =
+        toml::from_str(&text).with_context(|| format!("parsing [[profile]] in {config}"))?;
+    let mut seen = BTreeSet::new();
+    for profile in &file.profile {
+        if !seen.insert(profile.id.as_str()) {
+            anyhow::bail!("duplicate profile id {:?} in {config}", profile.id);
+        }
+        let unknown: Vec<&str> = profile
+            .patch_sets
+            .iter()
+            .filter(|id| !registry.patch_sets.iter().any(|set| set.id == **id))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            anyhow::bail!(
+                "profile {:?} names unknown patch sets: {}",
+                profile.id,
+                unknown.join(", ")
+            );
+        }
+    }
+    Ok(file.profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profiles_select_by_id_and_tag_and_reject_unknown_sets() {
+        let registry: Registry = serde_json::from_str(
+            r#"{"patch_sets": [
+                {"id": "a", "description": "", "tags": ["ui"]},
+                {"id": "b", "description": ""},
+                {"id": "c", "description": "", "tags": ["net"]}
+            ]}"#,
+        )
+        .unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let config = Utf8Path::from_path(tmp.path())
+            .unwrap()
+            .join("codex-forksmith.toml");
+        fs::write(
+            &config,
+            "[repo]\npath = \"vendor/codex\"\n\n[[profile]]\nid = \"full\"\npatch_sets = [\"b\"]\ntags = [\"net\"]\n",
+        )
+        .unwrap();
+        let profiles = load_profiles(&config, &registry).unwrap();
+        assert_eq!(profiles[0].resolve(&registry), ["b", "c"]);
+
+        fs::write(&config, "[[profile]]\nid = \"x\"\npatch_sets = [\"zz\"]\n").unwrap();
+        assert!(load_profiles(&config, &registry).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use fs_err as fs;
+use serde_json::{json, Value};
+
+/// One ast-grep match destined for a SARIF result.
+#[derive(Debug, Clone)]
+pub struct SarifFinding {
+    pub rule_id: String,
+    pub message: String,
+    pub uri: String,
+    pub start_line: u64,
+    pub end_line: u64,
+}
+
+/// Writes `findings` as SARIF 2.1.0; each of `notices` (e.g. a truncated
+/// match list) becomes a warning-level tool execution notification.
+pub fn write_sarif(path: &Utf8Path, findings: &[SarifFinding], notices: &[String]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_vec_pretty(&to_sarif(findings, notices))?;
+    fs::write(path, json).with_context(|| format!("writing SARIF report {path}"))?;
+    Ok(())
+}
+
+fn to_sarif(findings: &[SarifFinding], notices: &[String]) -> Value {
+    let mut rules = BTreeMap::new();
+    for finding in findings {
+        rules.entry(finding.rule_id.as_str()).or_insert_with(|| {
+            json!({
+                "id": finding.rule_id,
+                "shortDescription": { "text": finding.rule_id },
+            })
+        });
+    }
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            json!({
+                "ruleId": finding.rule_id,
+                "level": "warning",
+                "message": { "text": finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": finding.uri },
+                        "region": {
+                            "startLine": finding.start_line,
+                            "endLine": finding.end_line,
+                        },
+                    },
+                }],
+            })
+        })
+        .collect();
+    let notifications: Vec<Value> = notices
+        .iter()
+        .map(|text| json!({ "level": "warning", "message": { "text": text } }))
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "codex-forksmith",
+                    "informationUri": "https://github.com/toxicwind/codex-forksmith",
+                    "rules": rules.into_values().collect::<Vec<_>>(),
+                },
+            },
+            "invocations": [{
+                "executionSuccessful": true,
+                "toolExecutionNotifications": notifications,
+            }],
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notices_become_tool_execution_notifications() {
+        let sarif = to_sarif(&[], &["rule a.yml matched 9000 times".into()]);
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["invocations"][0]["toolExecutionNotifications"][0]["message"]["text"],
+            "rule a.yml matched 9000 times"
+        );
+        assert_eq!(run["results"], json!([]));
+    }
+}
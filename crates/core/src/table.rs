@@ -0,0 +1,101 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+/// Whole-row highlight, only emitted when color is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowStyle {
+    Plain,
+    Dim,
+    Red,
+}
+
+impl RowStyle {
+    fn ansi(self) -> Option<&'static str> {
+        match self {
+            RowStyle::Plain => None,
+            RowStyle::Dim => Some("\x1b[2m"),
+            RowStyle::Red => Some("\x1b[31m"),
+        }
+    }
+}
+
+/// Left-aligned text table sized to its widest cells.
+#[derive(Debug)]
+pub struct Table {
+    headers: Vec<&'static str>,
+    rows: Vec<(Vec<String>, RowStyle)>,
+}
+
+impl Table {
+    pub fn new(headers: Vec<&'static str>) -> Self {
+        Self {
+            headers,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>, style: RowStyle) {
+        self.rows.push((cells, style));
+    }
+
+    pub fn render(&self, color: bool) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.chars().count()).collect();
+        for (cells, _) in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(cells) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let line = |cells: &mut dyn Iterator<Item = &str>| {
+            let padded: Vec<String> = cells
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:<width$}"))
+                .collect();
+            padded.join("  ").trim_end().to_string()
+        };
+        let mut out = line(&mut self.headers.iter().copied());
+        out.push('\n');
+        for (cells, style) in &self.rows {
+            let text = line(&mut cells.iter().map(String::as_str));
+            match style.ansi().filter(|_| color) {
+                Some(code) => out.push_str(&format!("{code}{text}\x1b[0m")),
+                None => out.push_str(&text),
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Color only for a terminal stdout, and never when `NO_COLOR` is set.
+pub fn color_enabled() -> bool {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    !no_color && io::stdout().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_fit_the_widest_cell_and_the_header_leads() {
+        let mut table = Table::new(vec!["ID", "RESULT"]);
+        table.row(vec!["long-set-id".into(), "ok".into()], RowStyle::Plain);
+        table.row(vec!["a".into(), "failed".into()], RowStyle::Red);
+        assert_eq!(
+            table.render(false),
+            "ID           RESULT\nlong-set-id  ok\na            failed\n"
+        );
+        assert_eq!(
+            table.render(true).lines().nth(2),
+            Some("\x1b[31ma            failed\x1b[0m")
+        );
+    }
+
+    #[test]
+    fn empty_table_renders_only_the_header() {
+        assert_eq!(
+            Table::new(vec!["ID", "RESULT"]).render(true),
+            "ID  RESULT\n"
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+
+use crate::UpdateSummary;
+
+/// POSTs the summary to `url`: Slack blocks for `hooks.slack.com`, the raw
+/// summary JSON otherwise. Errors never include the URL, which is a secret
+/// for Slack incoming webhooks.
+#[cfg(feature = "webhook")]
+pub(crate) fn notify(url: &str, summary: &UpdateSummary) -> Result<()> {
+    let body = if is_slack(url) {
+        serde_json::to_string(&slack_payload(summary))?
+    } else {
+        serde_json::to_string(summary)?
+    };
+    match ureq::post(url)
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+    {
+        Ok(_) => Ok(()),
+        Err(ureq::Error::Status(code, _)) => anyhow::bail!("webhook returned HTTP {code}"),
+        Err(ureq::Error::Transport(transport)) => {
+            anyhow::bail!("webhook transport error ({})", transport.kind())
+        }
+    }
+}
+
+#[cfg(not(feature = "webhook"))]
+pub(crate) fn notify(_url: &str, _summary: &UpdateSummary) -> Result<()> {
+    anyhow::bail!("codex-core was built without the `webhook` feature")
+}
+
+#[cfg(feature = "webhook")]
+fn is_slack(url: &str) -> bool {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    host.eq_ignore_ascii_case("hooks.slack.com")
+}
+
+#[cfg(feature = "webhook")]
+fn slack_payload(summary: &UpdateSummary) -> serde_json::Value {
+    let short = |rev: &Option<String>| {
+        rev.as_deref()
+            .map(|r| r[..r.len().min(7)].to_string())
+            .unwrap_or_else(|| "unknown".into())
+    };
+    let headline = format!(
+        "codex-forksmith update: vendor `{}` → `{}`, cargo check {}, {} warning(s)",
+        short(&summary.vendor_rev_before),
+        short(&summary.vendor_rev_after),
+        if summary.cargo_check_passed {
+            "passed"
+        } else {
+            "failed or skipped"
+        },
+        summary.warnings.len()
+    );
+    serde_json::json!({
+        "text": headline,
+        "blocks": [{
+            "type": "section",
+            "text": { "type": "mrkdwn", "text": headline },
+        }],
+    })
+}
@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use tempfile::TempDir;
+use tracing::warn;
+
+use crate::{read_git_rev, run_cmd};
+
+/// A detached `git worktree` of the vendor used to apply and check patches
+/// without touching the main checkout. Removed again on drop, whether the
+/// run succeeded or not.
+#[derive(Debug)]
+pub(crate) struct Worktree {
+    repo: Utf8PathBuf,
+    pub(crate) path: Utf8PathBuf,
+    /// Commit the worktree was created at.
+    base: String,
+    // Declared last so the worktree is unregistered before the dir goes.
+    _dir: TempDir,
+}
+
+impl Worktree {
+    /// Adds a worktree of `repo` at `rev` in a fresh private temp dir.
+    pub(crate) fn add(repo: &Utf8Path, rev: &str) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("forksmith-worktree-")
+            .tempdir()
+            .context("creating worktree temp dir")?;
+        let path = Utf8PathBuf::from_path_buf(dir.path().join("vendor"))
+            .map_err(|p| anyhow!("temp dir {} is not UTF-8", p.display()))?;
+        run_cmd(
+            "git",
+            &["worktree", "add", "--detach", path.as_str(), rev],
+            repo,
+        )
+        .with_context(|| format!("creating worktree {path} at {rev}"))?;
+        let base = read_git_rev(&path)?;
+        Ok(Self {
+            repo: repo.to_path_buf(),
+            path,
+            base,
+            _dir: dir,
+        })
+    }
+
+    /// Commits the worktree's changes, moves the main checkout to the base
+    /// rev and fast-forwards it onto that commit, then un-commits it again
+    /// so the main checkout holds the same uncommitted state a direct run
+    /// would have produced.
+    pub(crate) fn promote(&self) -> Result<()> {
+        run_cmd("git", &["add", "--all"], &self.path)?;
+        run_cmd(
+            "git",
+            &[
+                "-c",
+                "user.name=codex-forksmith",
+                "-c",
+                "user.email=codex-forksmith@localhost",
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "--no-verify",
+                "-m",
+                "forksmith: worktree changes",
+            ],
+            &self.path,
+        )?;
+        let head = read_git_rev(&self.path)?;
+        run_cmd(
+            "git",
+            &["reset", "--quiet", "--hard", &self.base],
+            &self.repo,
+        )?;
+        run_cmd("git", &["merge", "--quiet", "--ff-only", &head], &self.repo)
+            .with_context(|| format!("fast-forwarding {} to {head}", self.repo))?;
+        run_cmd(
+            "git",
+            &["reset", "--quiet", "--mixed", &self.base],
+            &self.repo,
+        )?;
+        Ok(())
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        // `--force` because a run that did not pass leaves changes behind.
+        let path = self.path.as_str();
+        if let Err(err) = run_cmd("git", &["worktree", "remove", "--force", path], &self.repo) {
+            warn!("worktree {path} not removed: {err:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fs_err as fs;
+
+    #[test]
+    fn promote_leaves_the_worktree_changes_uncommitted_in_the_repo() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = Utf8Path::from_path(tmp.path()).unwrap();
+        run_cmd("git", &["init", "--quiet", "--initial-branch=main"], repo).unwrap();
+        run_cmd("git", &["config", "user.name", "test"], repo).unwrap();
+        run_cmd("git", &["config", "user.email", "test@example.com"], repo).unwrap();
+        fs::write(repo.join("lib.rs"), "fn a() {}\n").unwrap();
+        run_cmd("git", &["add", "-A"], repo).unwrap();
+        run_cmd("git", &["commit", "--quiet", "-m", "init"], repo).unwrap();
+        let base = read_git_rev(repo).unwrap();
+
+        let worktree = Worktree::add(repo, "HEAD").unwrap();
+        let path = worktree.path.clone();
+        fs::write(path.join("lib.rs"), "fn b() {}\n").unwrap();
+        fs::write(path.join("new.rs"), "").unwrap();
+        assert_eq!(
+            fs::read_to_string(repo.join("lib.rs")).unwrap(),
+            "fn a() {}\n"
+        );
+
+        worktree.promote().unwrap();
+        drop(worktree);
+        assert!(!path.exists());
+        assert_eq!(read_git_rev(repo).unwrap(), base);
+        assert_eq!(
+            fs::read_to_string(repo.join("lib.rs")).unwrap(),
+            "fn b() {}\n"
+        );
+        let status = run_cmd("git", &["status", "--porcelain"], repo).unwrap();
+        assert_eq!(status, " M lib.rs\n?? new.rs\n");
+    }
+}
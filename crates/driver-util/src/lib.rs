@@ -0,0 +1,43 @@
+//! Helpers shared by the ast-grep, coccinelle and grit drivers.
+
+use anyhow::{bail, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// Errors unless `path` is a regular file with an execute bit (on unix); used
+/// to vet explicitly configured tool binaries.
+pub fn ensure_executable(path: &Utf8Path) -> Result<()> {
+    let meta = std::fs::metadata(path).with_context(|| format!("{path} does not exist"))?;
+    if !meta.is_file() {
+        bail!("{path} is not a file");
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if meta.permissions().mode() & 0o111 == 0 {
+            bail!("{path} is not executable");
+        }
+    }
+    Ok(())
+}
+
+/// `rule` verbatim when absolute, otherwise joined onto `rules_dir`.
+pub fn resolve_rule(rules_dir: &Utf8Path, rule: &str) -> Utf8PathBuf {
+    let rule = Utf8Path::new(rule);
+    if rule.is_absolute() {
+        rule.to_path_buf()
+    } else {
+        rules_dir.join(rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_rules_join_the_rules_dir() {
+        let dir = Utf8Path::new("/rules");
+        assert_eq!(resolve_rule(dir, "nested/a.cocci"), "/rules/nested/a.cocci");
+        assert_eq!(resolve_rule(dir, "/abs/b.cocci"), "/abs/b.cocci");
+    }
+}
@@ -0,0 +1,119 @@
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::warn;
+use which::which;
+
+#[derive(Debug, Clone)]
+pub struct GritDriver {
+    binary: Utf8PathBuf,
+    rules_dir: Utf8PathBuf,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum GritMode {
+    DryRun,
+    Apply,
+}
+
+#[derive(Debug, Clone)]
+pub struct GritRunSummary {
+    pub mode: GritMode,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration_ms: u128,
+}
+
+#[derive(Debug, Clone)]
+pub enum GritRunOutcome {
+    Applied(GritRunSummary),
+    Skipped { reason: String },
+}
+
+impl GritDriver {
+    pub fn detect(rules_dir: &Utf8Path) -> Result<Option<Self>> {
+        if !rules_dir.exists() {
+            return Ok(None);
+        }
+        match which("grit") {
+            Ok(path) => {
+                let binary = Utf8PathBuf::from_path_buf(path)
+                    .unwrap_or_else(|p| Utf8PathBuf::from(p.to_string_lossy().to_string()));
+                Ok(Some(Self {
+                    binary,
+                    rules_dir: rules_dir.to_path_buf(),
+                }))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn with_binary(binary: impl Into<Utf8PathBuf>, rules_dir: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            binary: binary.into(),
+            rules_dir: rules_dir.into(),
+        }
+    }
+
+    pub fn run(&self, target: &Utf8Path, mode: GritMode) -> Result<GritRunOutcome> {
+        self.run_with_config(&self.rules_dir, target, mode)
+    }
+
+    pub fn run_with_config(
+        &self,
+        pattern_path: &Utf8Path,
+        target: &Utf8Path,
+        mode: GritMode,
+    ) -> Result<GritRunOutcome> {
+        if !pattern_path.exists() {
+            return Ok(GritRunOutcome::Skipped {
+                reason: format!("grit pattern {} missing", pattern_path),
+            });
+        }
+        if !target.exists() {
+            return Ok(GritRunOutcome::Skipped {
+                reason: format!("target {} missing", target),
+            });
+        }
+
+        let mut cmd = Command::new(&self.binary);
+        cmd.arg("apply")
+            .arg(pattern_path)
+            .arg(target)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        match mode {
+            GritMode::DryRun => {
+                cmd.arg("--dry-run");
+            }
+            GritMode::Apply => {}
+        }
+
+        let start = Instant::now();
+        let output = cmd
+            .output()
+            .with_context(|| format!("running grit via {}", self.binary))?;
+        let duration_ms = start.elapsed().as_millis();
+
+        if !output.status.success() {
+            warn!(
+                "grit exited with {}; stderr: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr)
+            );
+            return Ok(GritRunOutcome::Skipped {
+                reason: format!("grit exit {}", output.status),
+            });
+        }
+
+        Ok(GritRunOutcome::Applied(GritRunSummary {
+            mode,
+            stdout: String::from_utf8_lossy(&output.stdout).into(),
+            stderr: String::from_utf8_lossy(&output.stderr).into(),
+            duration_ms,
+        }))
+    }
+}
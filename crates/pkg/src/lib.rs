@@ -1,37 +1,433 @@
-use std::io;
+use std::collections::BTreeMap;
+use std::io::{self, Cursor, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Condvar, Mutex};
+use std::thread;
 
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use fs_err as fs;
+use indicatif::ProgressBar;
 use walkdir::WalkDir;
 use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive};
 
-pub fn build_zip(source: &Utf8Path, output: &Utf8Path) -> Result<()> {
+/// Knobs for [`build_zip`].
+#[derive(Debug, Clone)]
+pub struct ZipOptions {
+    /// Store every entry under this top-level directory (e.g. `codex-vendor`).
+    pub prefix: Option<String>,
+    /// Worker threads compressing file entries; defaults to the CPU count.
+    pub threads: usize,
+    pub compression: Compression,
+}
+
+/// Compression applied to file entries. `Fast`/`Default`/`Best` are deflate
+/// levels 1/6/9; `Zstd` needs the `zstd` feature.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    Store,
+    Fast,
+    #[default]
+    Default,
+    Best,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    fn file_options(self) -> FileOptions {
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        match self {
+            Self::Store => FileOptions::default().compression_method(CompressionMethod::Stored),
+            Self::Fast => deflated.compression_level(Some(1)),
+            Self::Default => deflated,
+            Self::Best => deflated.compression_level(Some(9)),
+            #[cfg(feature = "zstd")]
+            Self::Zstd => FileOptions::default().compression_method(CompressionMethod::Zstd),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "store" | "none" => Ok(Self::Store),
+            "fast" => Ok(Self::Fast),
+            "default" => Ok(Self::Default),
+            "best" => Ok(Self::Best),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(Self::Zstd),
+            other => anyhow::bail!("unknown compression {other:?} (store, fast, default, best)"),
+        }
+    }
+}
+
+impl Default for ZipOptions {
+    fn default() -> Self {
+        Self {
+            prefix: None,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            compression: Compression::Default,
+        }
+    }
+}
+
+/// Total size in bytes of the regular files under `source`, for sizing a
+/// progress bar before calling [`build_zip`].
+pub fn source_size(source: &Utf8Path) -> u64 {
+    WalkDir::new(source)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Zips `source` into `output` per `opts`. When `progress` is given it is
+/// advanced by the bytes read from each file and its message tracks the file
+/// count.
+pub fn build_zip(
+    source: &Utf8Path,
+    output: &Utf8Path,
+    opts: &ZipOptions,
+    progress: Option<&ProgressBar>,
+) -> Result<()> {
     if !source.exists() {
         anyhow::bail!("source {} missing", source);
     }
+    let prefix = opts
+        .prefix
+        .as_deref()
+        .map(normalize_prefix)
+        .transpose()?
+        .unwrap_or_default();
     if let Some(parent) = output.parent() {
         fs::create_dir_all(parent)?;
     }
     let file = fs::File::create(output).with_context(|| format!("creating {output}"))?;
+    // An output inside `source` would otherwise be walked into itself.
+    let output_rel = match (fs::canonicalize(source), fs::canonicalize(output)) {
+        (Ok(source), Ok(output)) => output.strip_prefix(&source).ok().map(Path::to_path_buf),
+        _ => None,
+    };
     let mut zip = zip::ZipWriter::new(file);
-    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let options = opts.compression.file_options();
+
+    if !prefix.is_empty() {
+        zip.add_directory(prefix.as_str(), options)?;
+    }
 
-    for entry in WalkDir::new(source).into_iter().filter_map(|e| e.ok()) {
+    let mut entries = Vec::new();
+    for entry in WalkDir::new(source)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
         let path = entry.path();
         let rel = path.strip_prefix(source).unwrap();
+        if output_rel.as_deref() == Some(rel) {
+            continue;
+        }
         let rel = Utf8PathBuf::from(rel.to_string_lossy().to_string());
+        let name = format!("{prefix}{}", rel.as_str().replace('\\', "/"));
         if entry.file_type().is_dir() {
             if !rel.as_str().is_empty() {
-                zip.add_directory(rel.as_str(), options)?;
+                entries.push(Entry::Dir(name));
             }
             continue;
         }
-        let mut f = fs::File::open(path)?;
-        zip.start_file(rel.as_str(), options)?;
-        io::copy(&mut f, &mut zip)?;
+        entries.push(Entry::File {
+            path: path.to_path_buf(),
+            name,
+        });
     }
 
+    write_entries(&mut zip, &entries, options, opts.threads.max(1), progress)?;
     zip.finish()?;
     Ok(())
 }
+
+enum Entry {
+    Dir(String),
+    File { path: PathBuf, name: String },
+}
+
+/// Compressed entries that may wait in memory for an earlier, slower one
+/// before workers stop picking up new files.
+const MAX_BUFFERED_BYTES: usize = 64 << 20;
+
+/// Compresses file entries on `threads` workers, each into its own one-entry
+/// in-memory archive, and raw-copies them into `zip` in walk order so the
+/// result does not depend on thread scheduling.
+fn write_entries<W: Write + Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    entries: &[Entry],
+    options: FileOptions,
+    threads: usize,
+    progress: Option<&ProgressBar>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let abort = AtomicBool::new(false);
+    let buffered = Mutex::new(0usize);
+    let drained = Condvar::new();
+    let (tx, rx) = mpsc::channel::<(usize, Result<Option<Vec<u8>>>)>();
+    thread::scope(|scope| {
+        for _ in 0..threads.min(entries.len()) {
+            let tx = tx.clone();
+            let (next, abort, buffered, drained) = (&next, &abort, &buffered, &drained);
+            scope.spawn(move || loop {
+                {
+                    let mut used = buffered.lock().unwrap();
+                    while *used > MAX_BUFFERED_BYTES && !abort.load(Ordering::Relaxed) {
+                        used = drained.wait(used).unwrap();
+                    }
+                }
+                if abort.load(Ordering::Relaxed) {
+                    return;
+                }
+                // Indices are claimed in order, so the entry the writer waits
+                // for is always in flight and the budget cannot deadlock.
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(entry) = entries.get(idx) else {
+                    return;
+                };
+                let result = match entry {
+                    Entry::Dir(_) => Ok(None),
+                    Entry::File { path, name } => {
+                        compress_file(path, name, options, progress).map(Some)
+                    }
+                };
+                if let Ok(Some(data)) = &result {
+                    *buffered.lock().unwrap() += data.len();
+                }
+                if tx.send((idx, result)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(tx);
+
+        let written = (|| -> Result<()> {
+            let mut pending = BTreeMap::new();
+            let mut files = 0u64;
+            for (idx, entry) in entries.iter().enumerate() {
+                let data = loop {
+                    if let Some(data) = pending.remove(&idx) {
+                        break data;
+                    }
+                    let (done, result) = rx.recv().context("zip worker exited early")?;
+                    pending.insert(done, result?);
+                };
+                match (entry, data) {
+                    (Entry::Dir(name), _) => zip.add_directory(name.as_str(), options)?,
+                    (Entry::File { name, .. }, Some(data)) => {
+                        let mut single = ZipArchive::new(Cursor::new(&data))?;
+                        zip.raw_copy_file_rename(single.by_index_raw(0)?, name.as_str())?;
+                        *buffered.lock().unwrap() -= data.len();
+                        drained.notify_all();
+                        files += 1;
+                        if let Some(pb) = progress {
+                            pb.set_message(format!("{files} files"));
+                        }
+                    }
+                    (Entry::File { .. }, None) => {
+                        unreachable!("file entries are always compressed")
+                    }
+                }
+            }
+            Ok(())
+        })();
+        if written.is_err() {
+            abort.store(true, Ordering::Relaxed);
+            drained.notify_all();
+        }
+        written
+    })
+}
+
+fn compress_file(
+    path: &Path,
+    name: &str,
+    options: FileOptions,
+    progress: Option<&ProgressBar>,
+) -> Result<Vec<u8>> {
+    let mut f = fs::File::open(path)?;
+    let mut single = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    single.start_file(name, options)?;
+    match progress {
+        Some(pb) => io::copy(&mut pb.wrap_read(f), &mut single)?,
+        None => io::copy(&mut f, &mut single)?,
+    };
+    Ok(single.finish()?.into_inner())
+}
+
+/// Forward slashes only, no leading separator, exactly one trailing one;
+/// empty when the prefix has no path components. `.`, `..` and drive
+/// components such as `C:` are rejected so entries cannot escape the
+/// extraction dir.
+fn normalize_prefix(prefix: &str) -> Result<String> {
+    let parts: Vec<&str> = prefix
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty())
+        .collect();
+    if let Some(bad) = parts
+        .iter()
+        .find(|part| matches!(**part, "." | "..") || part.contains(':'))
+    {
+        anyhow::bail!("zip prefix {prefix:?} has an invalid component {bad:?}");
+    }
+    Ok(if parts.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", parts.join("/"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_wraps_extracted_tree() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(tmp.path()).unwrap();
+        let source = root.join("src");
+        fs::create_dir_all(source.join("nested")).unwrap();
+        fs::write(source.join("nested/file.txt"), "hello").unwrap();
+        fs::write(source.join("top.txt"), "top").unwrap();
+
+        let output = root.join("out.zip");
+        let opts = ZipOptions {
+            prefix: Some("\\codex-vendor\\".into()),
+            ..Default::default()
+        };
+        build_zip(&source, &output, &opts, None).unwrap();
+
+        let extract = root.join("extract");
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        archive.extract(&extract).unwrap();
+
+        let entries: Vec<_> = fs::read_dir(&extract)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, vec!["codex-vendor"]);
+        let wrapped = extract.join("codex-vendor");
+        assert_eq!(
+            fs::read_to_string(wrapped.join("nested/file.txt")).unwrap(),
+            "hello"
+        );
+        assert_eq!(fs::read_to_string(wrapped.join("top.txt")).unwrap(), "top");
+    }
+
+    #[test]
+    fn output_inside_source_is_not_archived() {
+        let tmp = tempfile::tempdir().unwrap();
+        let source = Utf8Path::from_path(tmp.path()).unwrap();
+        fs::write(source.join("top.txt"), "top").unwrap();
+        let output = source.join("dist/out.zip");
+
+        for _ in 0..2 {
+            build_zip(source, &output, &ZipOptions::default(), None).unwrap();
+        }
+
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let names: Vec<String> = (0..archive.len())
+            .map(|i| archive.by_index(i).unwrap().name().to_string())
+            .collect();
+        assert_eq!(names, vec!["dist/", "top.txt"]);
+    }
+
+    #[test]
+    fn archive_does_not_depend_on_thread_count() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(tmp.path()).unwrap();
+        let source = root.join("src");
+        for i in 0..40 {
+            let dir = source.join(format!("dir{}", i % 5));
+            fs::create_dir_all(&dir).unwrap();
+            // Uneven sizes so workers finish out of order.
+            let body = format!("file {i}\n").repeat(1 + (i * 37) % 500);
+            fs::write(dir.join(format!("f{i}.txt")), body).unwrap();
+        }
+
+        let entries = |threads: usize| {
+            let output = root.join(format!("out{threads}.zip"));
+            let opts = ZipOptions {
+                threads,
+                ..Default::default()
+            };
+            build_zip(&source, &output, &opts, None).unwrap();
+            let mut archive = ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+            (0..archive.len())
+                .map(|i| {
+                    let mut file = archive.by_index(i).unwrap();
+                    let mut contents = Vec::new();
+                    io::copy(&mut file, &mut contents).unwrap();
+                    (file.name().to_string(), contents)
+                })
+                .collect::<Vec<_>>()
+        };
+        let single = entries(1);
+        assert_eq!(single.len(), 45);
+        assert_eq!(single, entries(8));
+    }
+
+    #[test]
+    fn normalize_prefix_uses_single_trailing_slash() {
+        assert_eq!(normalize_prefix("a\\b//").unwrap(), "a/b/");
+        assert_eq!(normalize_prefix("/").unwrap(), "");
+    }
+
+    #[test]
+    fn normalize_prefix_rejects_escaping_components() {
+        for prefix in [".", "a/./b", "..", "a/../../b", "C:\\vendor", "C:"] {
+            assert!(normalize_prefix(prefix).is_err(), "{prefix}");
+        }
+        assert_eq!(normalize_prefix("a..b/.c").unwrap(), "a..b/.c/");
+    }
+
+    #[test]
+    fn store_is_larger_than_best() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(tmp.path()).unwrap();
+        let source = root.join("src");
+        fs::create_dir_all(&source).unwrap();
+        let text = "fn main() { println!(\"hello\"); }\n".repeat(4096);
+        for i in 0..8 {
+            fs::write(source.join(format!("file{i}.rs")), &text).unwrap();
+        }
+
+        let zip_with = |compression, name: &str| {
+            let output = root.join(name);
+            let opts = ZipOptions {
+                compression,
+                ..Default::default()
+            };
+            build_zip(&source, &output, &opts, None).unwrap();
+            output
+        };
+        let stored = zip_with(Compression::Store, "store.zip");
+        let best = zip_with(Compression::Best, "best.zip");
+
+        let size = |path: &Utf8Path| fs::metadata(path).unwrap().len();
+        assert!(size(&stored) > size(&best) * 10);
+        // Stored entries skip deflate entirely, which is where the speed comes from.
+        let mut archive = ZipArchive::new(fs::File::open(&stored).unwrap()).unwrap();
+        assert_eq!(
+            archive.by_name("file0.rs").unwrap().compression(),
+            CompressionMethod::Stored
+        );
+        let mut archive = ZipArchive::new(fs::File::open(&best).unwrap()).unwrap();
+        assert_eq!(
+            archive.by_name("file0.rs").unwrap().compression(),
+            CompressionMethod::Deflated
+        );
+    }
+}
@@ -1,10 +1,15 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
 use anyhow::{Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use chrono::{DateTime, Utc};
 use fs_err as fs;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct PatchSet {
     pub id: String,
     pub description: String,
@@ -16,17 +21,51 @@ pub struct PatchSet {
     pub tags: Vec<String>,
     #[serde(default)]
     pub notes: Option<String>,
+    /// Overrides `--max-matches` for this set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_matches: Option<u64>,
     #[serde(default)]
     pub created_at: Option<DateTime<Utc>>,
+    /// When the set was last processed by a run, whatever the outcome.
     #[serde(default)]
     pub last_applied_at: Option<DateTime<Utc>>,
+    /// When the set last ended a run `Applied`.
+    #[serde(default)]
+    pub last_success_at: Option<DateTime<Utc>>,
     #[serde(default)]
     pub last_match_count: Option<u64>,
     #[serde(default)]
     pub last_result: Option<PatchResult>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+impl PatchSet {
+    /// True when the set last applied successfully more than `threshold`
+    /// ago; runs that skipped or failed it do not count. A set that never
+    /// applied is stale once it is older than `threshold`, or immediately if
+    /// it has no `created_at`.
+    pub fn is_stale(&self, threshold: Duration) -> bool {
+        let threshold = chrono::Duration::from_std(threshold).unwrap_or(chrono::Duration::MAX);
+        let Some(cutoff) = Utc::now().checked_sub_signed(threshold) else {
+            return false;
+        };
+        match (self.last_success(), self.created_at) {
+            (Some(applied), _) => applied < cutoff,
+            (None, Some(created)) => created < cutoff,
+            (None, None) => true,
+        }
+    }
+
+    /// `last_success_at`, falling back to `last_applied_at` for registries
+    /// written before it existed whose last result was `Applied`.
+    fn last_success(&self) -> Option<DateTime<Utc>> {
+        self.last_success_at.or_else(|| {
+            self.last_applied_at
+                .filter(|_| matches!(self.last_result, Some(PatchResult::Applied { .. })))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(tag = "status", rename_all = "snake_case")]
 pub enum PatchResult {
     Applied { changed_files: u64 },
@@ -34,13 +73,18 @@ pub enum PatchResult {
     Failed { error: String },
 }
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 pub struct Registry {
     #[serde(default)]
     pub patch_sets: Vec<PatchSet>,
 }
 
 impl Registry {
+    /// JSON Schema for `registry.json`, derived from the serde attributes.
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(Registry)
+    }
+
     pub fn load(path: &Utf8Path) -> Result<Self> {
         if !path.exists() {
             return Ok(Self::default());
@@ -70,6 +114,19 @@ impl Registry {
         Ok(())
     }
 
+    /// Forgets the last result and match count of `id` so the next run is
+    /// judged like a first run. The set stays enabled or disabled as it was.
+    pub fn reset_run_state(&mut self, id: &str) -> Result<()> {
+        let set = self
+            .patch_sets
+            .iter_mut()
+            .find(|p| p.id == id)
+            .with_context(|| format!("patch set {id} not found"))?;
+        set.last_match_count = None;
+        set.last_result = None;
+        Ok(())
+    }
+
     pub fn record_run(
         &mut self,
         id: &str,
@@ -82,17 +139,148 @@ impl Registry {
             .iter_mut()
             .find(|p| p.id == id)
             .with_context(|| format!("patch set {id} not found"))?;
+        set.created_at.get_or_insert(now);
         set.last_applied_at = Some(now);
+        if matches!(result, PatchResult::Applied { .. }) {
+            set.last_success_at = Some(now);
+        }
         set.last_match_count = match_count;
         set.last_result = Some(result);
         Ok(())
     }
 
+    /// Preflight check: every enabled set has rules, each rule exists under
+    /// its engine's rules dir (`.cocci` → coccinelle, `.grit` is not checked,
+    /// anything else → ast-grep), and ids are unique. Rules whose dir is
+    /// `None` and glob patterns are not checked.
+    pub fn validate(
+        &self,
+        ast_rules_dir: Option<&Utf8Path>,
+        cocci_rules_dir: Option<&Utf8Path>,
+    ) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+        let mut seen = HashSet::new();
+        for set in &self.patch_sets {
+            if !seen.insert(set.id.as_str()) {
+                issues.push(ValidationIssue::DuplicateId {
+                    set_id: set.id.clone(),
+                });
+            }
+            if !set.enabled {
+                continue;
+            }
+            if set.rules.is_empty() {
+                issues.push(ValidationIssue::EmptyRules {
+                    set_id: set.id.clone(),
+                });
+            }
+            for rule in &set.rules {
+                let dir = if rule.contains(['*', '?', '[', '{']) {
+                    None
+                } else if rule.ends_with(".cocci") {
+                    cocci_rules_dir
+                } else if rule.ends_with(".grit") {
+                    None
+                } else {
+                    ast_rules_dir
+                };
+                let Some(dir) = dir else { continue };
+                let path = dir.join(rule);
+                if !path.is_file() {
+                    issues.push(ValidationIssue::MissingRule {
+                        set_id: set.id.clone(),
+                        rule: rule.clone(),
+                        path,
+                    });
+                }
+            }
+        }
+        issues
+    }
+
+    /// One-shot health overview; a set "matched zero" when its last run
+    /// reported `last_match_count == Some(0)`.
+    pub fn stats(&self) -> RegistryStats {
+        let enabled = self.patch_sets.iter().filter(|s| s.enabled).count();
+        RegistryStats {
+            total: self.patch_sets.len(),
+            enabled,
+            disabled: self.patch_sets.len() - enabled,
+            zero_matches: self
+                .patch_sets
+                .iter()
+                .filter(|s| s.last_match_count == Some(0))
+                .count(),
+            failed: self
+                .patch_sets
+                .iter()
+                .filter(|s| matches!(s.last_result, Some(PatchResult::Failed { .. })))
+                .count(),
+            never_applied: self
+                .patch_sets
+                .iter()
+                .filter(|s| s.last_success().is_none())
+                .count(),
+            oldest_last_applied_at: self
+                .patch_sets
+                .iter()
+                .filter_map(|s| s.last_applied_at)
+                .min(),
+        }
+    }
+
+    /// Semantic changes going from `self` to `other`, keyed by set id so
+    /// reordering and serialization noise do not show up.
+    pub fn diff(&self, other: &Registry) -> RegistryDiff {
+        let before: BTreeMap<&str, &PatchSet> =
+            self.patch_sets.iter().map(|s| (s.id.as_str(), s)).collect();
+        let after: BTreeMap<&str, &PatchSet> = other
+            .patch_sets
+            .iter()
+            .map(|s| (s.id.as_str(), s))
+            .collect();
+        let mut diff = RegistryDiff::default();
+        for (id, old) in &before {
+            let Some(new) = after.get(id) else {
+                diff.removed.push(id.to_string());
+                continue;
+            };
+            let (rules_added, rules_removed) = list_changes(&old.rules, &new.rules);
+            let (tags_added, tags_removed) = list_changes(&old.tags, &new.tags);
+            let change = PatchSetChange {
+                id: id.to_string(),
+                enabled: (old.enabled != new.enabled).then_some(new.enabled),
+                rules_added,
+                rules_removed,
+                tags_added,
+                tags_removed,
+            };
+            if !change.is_empty() {
+                diff.changed.push(change);
+            }
+        }
+        diff.added = after
+            .keys()
+            .filter(|id| !before.contains_key(*id))
+            .map(|id| id.to_string())
+            .collect();
+        diff
+    }
+
+    pub fn stale_sets(&self, threshold: Duration) -> impl Iterator<Item = &PatchSet> {
+        self.patch_sets
+            .iter()
+            .filter(move |set| set.is_stale(threshold))
+    }
+
     pub fn ensure_patch_set<F>(&mut self, templ: PatchSetTemplate, build_notes: F) -> &PatchSet
     where
         F: FnOnce() -> Option<String>,
     {
         if let Some(idx) = self.patch_sets.iter().position(|p| p.id == templ.id) {
+            // Sets from older registries predate `created_at`; start the
+            // clock now so they are not immediately stale.
+            self.patch_sets[idx].created_at.get_or_insert_with(Utc::now);
             return &self.patch_sets[idx];
         }
         let mut new_set = templ.into_patch_set();
@@ -109,6 +297,138 @@ impl Registry {
     }
 }
 
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct RegistryStats {
+    pub total: usize,
+    pub enabled: usize,
+    pub disabled: usize,
+    /// Sets whose last run matched nothing (degraded against upstream).
+    pub zero_matches: usize,
+    pub failed: usize,
+    pub never_applied: usize,
+    pub oldest_last_applied_at: Option<DateTime<Utc>>,
+}
+
+impl fmt::Display for RegistryStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "patch sets   : {} ({} enabled, {} disabled)",
+            self.total, self.enabled, self.disabled
+        )?;
+        writeln!(f, "zero matches : {}", self.zero_matches)?;
+        writeln!(f, "failed       : {}", self.failed)?;
+        writeln!(f, "never applied: {}", self.never_applied)?;
+        match self.oldest_last_applied_at {
+            Some(at) => write!(f, "oldest run   : {}", at.to_rfc3339()),
+            None => write!(f, "oldest run   : -"),
+        }
+    }
+}
+
+/// Result of [`Registry::diff`]; ids are sorted.
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct RegistryDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<PatchSetChange>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq, Eq)]
+pub struct PatchSetChange {
+    pub id: String,
+    /// The new `enabled` value, when it flipped.
+    pub enabled: Option<bool>,
+    pub rules_added: Vec<String>,
+    pub rules_removed: Vec<String>,
+    pub tags_added: Vec<String>,
+    pub tags_removed: Vec<String>,
+}
+
+impl RegistryDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl PatchSetChange {
+    fn is_empty(&self) -> bool {
+        self.enabled.is_none()
+            && self.rules_added.is_empty()
+            && self.rules_removed.is_empty()
+            && self.tags_added.is_empty()
+            && self.tags_removed.is_empty()
+    }
+}
+
+impl fmt::Display for RegistryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        for id in &self.added {
+            writeln!(f, "+ {id}")?;
+        }
+        for id in &self.removed {
+            writeln!(f, "- {id}")?;
+        }
+        for change in &self.changed {
+            writeln!(f, "~ {}", change.id)?;
+            if let Some(enabled) = change.enabled {
+                let state = if enabled { "enabled" } else { "disabled" };
+                writeln!(f, "    {state}")?;
+            }
+            for rule in &change.rules_added {
+                writeln!(f, "    + rule {rule}")?;
+            }
+            for rule in &change.rules_removed {
+                writeln!(f, "    - rule {rule}")?;
+            }
+            for tag in &change.tags_added {
+                writeln!(f, "    + tag {tag}")?;
+            }
+            for tag in &change.tags_removed {
+                writeln!(f, "    - tag {tag}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entries only in `new` and entries only in `old`, in list order.
+fn list_changes(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = new.iter().filter(|x| !old.contains(x)).cloned().collect();
+    let removed = old.iter().filter(|x| !new.contains(x)).cloned().collect();
+    (added, removed)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationIssue {
+    MissingRule {
+        set_id: String,
+        rule: String,
+        path: Utf8PathBuf,
+    },
+    EmptyRules {
+        set_id: String,
+    },
+    DuplicateId {
+        set_id: String,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingRule { set_id, rule, path } => {
+                write!(f, "{set_id}: rule {rule} not found at {path}")
+            }
+            Self::EmptyRules { set_id } => write!(f, "{set_id}: enabled but has no rules"),
+            Self::DuplicateId { set_id } => write!(f, "{set_id}: duplicate patch set id"),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PatchSetTemplate {
     pub id: String,
@@ -126,8 +446,10 @@ impl PatchSetTemplate {
             enabled: true,
             tags: self.tags,
             notes: None,
+            max_matches: None,
             created_at: Some(Utc::now()),
             last_applied_at: None,
+            last_success_at: None,
             last_match_count: None,
             last_result: None,
         }
@@ -159,3 +481,92 @@ impl RegistryStore {
         &self.path
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_that_keep_failing_or_skipping_go_stale() {
+        let day = Duration::from_secs(24 * 60 * 60);
+        let long_ago = Utc::now() - chrono::Duration::days(200);
+        let mut registry = Registry {
+            patch_sets: vec![PatchSet {
+                id: "a".into(),
+                description: String::new(),
+                rules: vec!["a.yml".into()],
+                enabled: true,
+                tags: Vec::new(),
+                notes: None,
+                max_matches: None,
+                created_at: Some(long_ago),
+                last_applied_at: None,
+                last_success_at: Some(long_ago),
+                last_match_count: None,
+                last_result: None,
+            }],
+        };
+        registry
+            .record_run(
+                "a",
+                None,
+                PatchResult::Failed {
+                    error: "boom".into(),
+                },
+            )
+            .unwrap();
+        registry
+            .record_run(
+                "a",
+                Some(0),
+                PatchResult::Skipped {
+                    reason: Some("no matches".into()),
+                },
+            )
+            .unwrap();
+        assert!(registry.patch_sets[0].is_stale(90 * day));
+        assert_eq!(registry.stale_sets(90 * day).count(), 1);
+
+        registry
+            .record_run("a", Some(2), PatchResult::Applied { changed_files: 1 })
+            .unwrap();
+        assert!(!registry.patch_sets[0].is_stale(90 * day));
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_sets() {
+        let registry = |json: &str| -> Registry { serde_json::from_str(json).unwrap() };
+        let old = registry(
+            r#"{"patch_sets": [
+                {"id": "gone", "description": "", "rules": ["g.yml"]},
+                {"id": "kept", "description": "", "rules": ["a.yml", "b.yml"], "tags": ["x"]},
+                {"id": "same", "description": "old text", "rules": ["s.yml"]}
+            ]}"#,
+        );
+        let new = registry(
+            r#"{"patch_sets": [
+                {"id": "same", "description": "new text", "rules": ["s.yml"]},
+                {"id": "kept", "description": "", "rules": ["b.yml", "c.yml"], "tags": ["y"],
+                 "enabled": false},
+                {"id": "fresh", "description": "", "rules": ["f.yml"]}
+            ]}"#,
+        );
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff,
+            RegistryDiff {
+                added: vec!["fresh".into()],
+                removed: vec!["gone".into()],
+                changed: vec![PatchSetChange {
+                    id: "kept".into(),
+                    enabled: Some(false),
+                    rules_added: vec!["c.yml".into()],
+                    rules_removed: vec!["a.yml".into()],
+                    tags_added: vec!["y".into()],
+                    tags_removed: vec!["x".into()],
+                }],
+            }
+        );
+        assert!(old.diff(&old).is_empty());
+    }
+}
@@ -1,10 +1,20 @@
+use std::collections::BTreeSet;
 use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
 
-use anyhow::Result;
-use camino::Utf8PathBuf;
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use clap::{Args, Parser, Subcommand};
-use codex_core::{run_update, UpdateOptions, UpdateSummary};
-use codex_registry::RegistryStore;
+use codex_core::table::{self, RowStyle, Table};
+use codex_core::{
+    fix_workspace, init_workspace, load_profiles, prune_history, read_history, read_ignore_file,
+    run_preview, run_update, CommitOptions, Compression, HistoryFilter, InitOptions,
+    PreviewOptions, UpdateOptions, UpdateSummary, Warning, WarningLevel,
+};
+use codex_registry::{PatchResult, Registry, RegistryStore};
 use serde::Serialize;
 use tracing_subscriber::{fmt, EnvFilter};
 
@@ -12,9 +22,13 @@ fn main() -> Result<()> {
     init_tracing();
     let cli = Cli::parse();
     match cli.command {
-        Commands::Update(args) => cmd_update(args),
+        Commands::Update(args) => cmd_update(*args),
+        Commands::Preview(args) => cmd_preview(args),
         Commands::Registry(cmd) => cmd_registry(cmd),
         Commands::Doctor(args) => cmd_doctor(args),
+        Commands::Log(args) => cmd_log(args),
+        Commands::History(args) => cmd_history(args),
+        Commands::Init(args) => cmd_init(args),
     }
 }
 
@@ -37,9 +51,18 @@ struct Cli {
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    Update(UpdateArgs),
+    Update(Box<UpdateArgs>),
+    /// Dry-run every enabled patch set against the vendor as checked out;
+    /// never syncs, applies or records anything
+    Preview(PreviewArgs),
     Registry(RegistryArgs),
     Doctor(DoctorArgs),
+    /// Print recent entries from the run history log
+    Log(LogArgs),
+    /// Maintain the run history log
+    History(HistoryArgs),
+    /// Create codex-forksmith.toml, an empty registry and vendor/codex
+    Init(InitArgs),
 }
 
 #[derive(Args, Debug)]
@@ -59,15 +82,154 @@ struct UpdateArgs {
     #[arg(long)]
     cocci_rules: Option<Utf8PathBuf>,
 
+    #[arg(long)]
+    grit_rules: Option<Utf8PathBuf>,
+
+    /// Skip the ast-grep phase entirely (no detection, no warning)
+    #[arg(long)]
+    no_ast: bool,
+
+    /// Skip the coccinelle phase entirely (no detection, no warning)
+    #[arg(long)]
+    no_cocci: bool,
+
+    /// ast-grep binary to use instead of PATH (AST_GREP_BIN takes precedence)
+    #[arg(long)]
+    ast_grep_bin: Option<Utf8PathBuf>,
+
+    /// coccinelle-for-rust binary to use instead of PATH (COCCINELLE_BIN takes precedence)
+    #[arg(long)]
+    coccinelle_bin: Option<Utf8PathBuf>,
+
+    /// Isomorphism file passed to every coccinelle rule as --iso-file
+    #[arg(long, value_name = "PATH")]
+    cocci_iso_file: Option<Utf8PathBuf>,
+
+    /// Macro file passed to every coccinelle rule as --macro-file
+    #[arg(long, value_name = "PATH")]
+    cocci_macro_file: Option<Utf8PathBuf>,
+
+    /// Vendor remote to fetch and sync from
+    #[arg(long, default_value = "origin")]
+    remote: String,
+
     #[arg(long, default_value = "main")]
     branch: String,
 
+    /// Pin the vendor to this commit or tag instead of the branch tip
+    #[arg(long)]
+    rev: Option<String>,
+
     #[arg(long)]
     output_zip: Option<Utf8PathBuf>,
 
+    /// Compression for --output-zip: store, fast, default, best (or zstd with the feature)
+    #[arg(long, default_value = "default")]
+    zip_compression: Compression,
+
+    /// Write ast-grep dry-run matches as a SARIF 2.1.0 report
+    #[arg(long)]
+    sarif_out: Option<Utf8PathBuf>,
+
+    /// POST the summary to this webhook (Slack or generic JSON)
+    #[arg(long, env = "CODEX_FORKSMITH_WEBHOOK")]
+    webhook_url: Option<String>,
+
+    /// Append a JSON line per run here (default: patch-registry/history.jsonl)
+    #[arg(long)]
+    history_log: Option<Utf8PathBuf>,
+
+    /// Do not append to the run history log
+    #[arg(long)]
+    no_history: bool,
+
+    /// Write Prometheus textfile metrics (e.g. for node_exporter)
+    #[arg(long)]
+    metrics_out: Option<Utf8PathBuf>,
+
+    /// Write patch-set outcomes as JUnit XML
+    #[arg(long)]
+    junit_out: Option<Utf8PathBuf>,
+
+    /// Also write the JSON summary here, regardless of --json
+    #[arg(long)]
+    summary_out: Option<Utf8PathBuf>,
+
+    /// Write the summary as Markdown (e.g. for a PR comment)
+    #[arg(long)]
+    markdown_out: Option<Utf8PathBuf>,
+
     #[arg(long)]
     skip_cargo_check: bool,
 
+    /// Run cargo check in this dir, relative to the vendor root
+    #[arg(long, value_name = "DIR")]
+    cargo_check_dir: Option<Utf8PathBuf>,
+
+    /// Extra argument for cargo check (repeatable), e.g. `--cargo-check-arg=--all-features`
+    #[arg(
+        long = "cargo-check-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true
+    )]
+    cargo_check_args: Vec<String>,
+
+    /// Fail without applying if an enabled set with this tag matches nothing
+    #[arg(long = "assert-matches-tag", value_name = "TAG")]
+    assert_matches_tags: Vec<String>,
+
+    /// Skip (with a warning) any set whose dry run matches more than N places
+    #[arg(
+        long = "max-matches",
+        value_name = "N",
+        env = "CODEX_FORKSMITH_MAX_MATCHES"
+    )]
+    max_matches: Option<u64>,
+
+    /// Re-run ast-grep dry runs even when the vendor rev and rules are unchanged
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Record per-set errors as failed and continue; exits nonzero at the end
+    #[arg(long)]
+    keep_going: bool,
+
+    /// Apply only this patch set (repeatable); others are recorded as skipped
+    #[arg(long = "only", value_name = "ID")]
+    only_ids: Vec<String>,
+
+    /// Apply the sets of this `[[profile]]` from codex-forksmith.toml, plus any --only
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Exit with an error if the run raised any warning (after writing --summary-out)
+    #[arg(long)]
+    fail_on_warnings: bool,
+
+    /// Apply in a temporary git worktree; the vendor only changes if the run passes
+    #[arg(long)]
+    use_worktree: bool,
+
+    /// Commit the vendor changes once the run passes
+    #[arg(long)]
+    commit: bool,
+
+    /// Commit message template; `{count}` and `{upstream}` are substituted
+    #[arg(long, value_name = "TEMPLATE", requires = "commit")]
+    commit_message: Option<String>,
+
+    /// Commit author, e.g. "Forksmith <bot@example.com>"
+    #[arg(long, value_name = "AUTHOR", requires = "commit")]
+    commit_author: Option<String>,
+
+    /// Skip the run when upstream, rule files and enabled sets match the last passing run
+    #[arg(long)]
+    rules_hash: bool,
+
+    /// With --rules-hash, run everything even if nothing changed
+    #[arg(long, requires = "rules_hash")]
+    force: bool,
+
     #[arg(long)]
     json: bool,
 }
@@ -83,15 +245,204 @@ struct RegistryArgs {
 
 #[derive(Subcommand, Debug)]
 enum RegistryCommand {
-    List,
-    Enable { id: String },
-    Disable { id: String },
+    List {
+        /// Mark sets that have not applied within this many days as stale
+        #[arg(long, default_value_t = 90)]
+        stale_days: u64,
+        /// Also show descriptions, timestamps and notes
+        #[arg(long)]
+        wide: bool,
+    },
+    Enable {
+        id: String,
+    },
+    Disable {
+        id: String,
+    },
+    /// Clear a set's last result and match count so the next run starts fresh
+    Touch {
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        id: Option<String>,
+        /// Reset every patch set
+        #[arg(long)]
+        all: bool,
+    },
+    /// Summarize enabled/zero-match/failed sets
+    Stats {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check that every enabled set's rule files exist; exits 1 on issues
+    Validate {
+        #[arg(long)]
+        ast_rules: Option<Utf8PathBuf>,
+        #[arg(long)]
+        cocci_rules: Option<Utf8PathBuf>,
+    },
+    /// Show added/removed sets and enabled, rule and tag changes between two registries
+    Diff {
+        #[arg(long)]
+        from: Utf8PathBuf,
+        #[arg(long)]
+        to: Utf8PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print the JSON Schema for registry.json (point an editor's `$schema` at it)
+    Schema,
+}
+
+#[derive(Args, Debug)]
+struct InitArgs {
+    /// Directory to initialize (default: the cwd)
+    #[arg(long)]
+    workspace: Option<Utf8PathBuf>,
+
+    /// Clone the vendor repo from here instead of `git init`
+    #[arg(long, value_name = "GIT_URL")]
+    vendor_url: Option<String>,
+
+    /// Overwrite an existing config and registry
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+struct LogArgs {
+    #[arg(long)]
+    workspace: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    history_log: Option<Utf8PathBuf>,
+
+    /// Number of most recent entries to print
+    #[arg(long, default_value_t = 20)]
+    tail: usize,
+
+    /// Only entries newer than this, e.g. `7d` or `12h`
+    #[arg(long, value_parser = humantime::parse_duration)]
+    since: Option<std::time::Duration>,
+
+    /// Only runs where a patch set or cargo check failed
+    #[arg(long)]
+    failures_only: bool,
+
+    /// Print matching entries as JSON lines
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args, Debug)]
+struct HistoryArgs {
+    #[arg(long)]
+    workspace: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    history_log: Option<Utf8PathBuf>,
+
+    #[command(subcommand)]
+    command: HistoryCommand,
+}
+
+#[derive(Subcommand, Debug)]
+enum HistoryCommand {
+    /// Truncate the history log to the newest N entries; each entry is one
+    /// whole run, covering every patch set it touched
+    Prune {
+        #[arg(long)]
+        keep: usize,
+    },
+}
+
+#[derive(Args, Debug)]
+struct PreviewArgs {
+    #[arg(long)]
+    workspace: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    vendor_dir: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    registry: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    ast_rules: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    cocci_rules: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    grit_rules: Option<Utf8PathBuf>,
+
+    #[arg(long)]
+    ast_grep_bin: Option<Utf8PathBuf>,
+
+    /// Preview only this patch set (repeatable)
+    #[arg(long = "only", value_name = "ID")]
+    only_ids: Vec<String>,
+
+    #[arg(long)]
+    json: bool,
 }
 
 #[derive(Args, Debug)]
 struct DoctorArgs {
     #[arg(long)]
     workspace: Option<Utf8PathBuf>,
+
+    /// Create missing dirs, an empty registry and the configured upstream remote
+    #[arg(long)]
+    fix: bool,
+}
+
+fn cmd_preview(args: PreviewArgs) -> Result<()> {
+    let workspace = args
+        .workspace
+        .or_else(default_workspace)
+        .unwrap_or_else(|| Utf8PathBuf::from_path_buf(env::current_dir().unwrap()).unwrap());
+    let summary = run_preview(&PreviewOptions {
+        vendor_dir: args
+            .vendor_dir
+            .unwrap_or_else(|| workspace.join("vendor/codex")),
+        registry_path: args
+            .registry
+            .unwrap_or_else(|| workspace.join("patch-registry/registry.json")),
+        ast_rules_dir: args.ast_rules,
+        coccinelle_rules_dir: args.cocci_rules,
+        grit_rules_dir: args.grit_rules,
+        ast_grep_bin: args.ast_grep_bin,
+        ignore_globs: read_ignore_file(&workspace.join(".forksmithignore"))?,
+        only_ids: args.only_ids,
+    })?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+    println!("vendor: {:?}", summary.vendor_rev);
+    for rule in &summary.rules {
+        match rule.matches {
+            Some(n) => println!(
+                "{} [{}] {}: {n} matches in {} files",
+                rule.set_id,
+                rule.engine,
+                rule.rule,
+                rule.files.len()
+            ),
+            None => println!(
+                "{} [{}] {}: not run ({})",
+                rule.set_id,
+                rule.engine,
+                rule.rule,
+                rule.note.as_deref().unwrap_or("unknown")
+            ),
+        }
+        for file in &rule.files {
+            println!("    {file}");
+        }
+    }
+    print_warnings(&summary.warnings);
+    println!("vendor tree unchanged");
+    Ok(())
 }
 
 fn cmd_update(args: UpdateArgs) -> Result<()> {
@@ -105,8 +456,36 @@ fn cmd_update(args: UpdateArgs) -> Result<()> {
     let registry_path = args
         .registry
         .unwrap_or_else(|| workspace.join("patch-registry/registry.json"));
+    let history_log = if args.no_history {
+        None
+    } else {
+        Some(
+            args.history_log
+                .unwrap_or_else(|| default_history_log(&workspace)),
+        )
+    };
+    let ignore_globs = read_ignore_file(&workspace.join(".forksmithignore"))?;
     let ast_rules_dir = args.ast_rules;
     let cocci_rules_dir = args.cocci_rules;
+    let mut only_ids = args.only_ids;
+    if let Some(name) = &args.profile {
+        let registry = Registry::load(&registry_path)?;
+        let config = workspace.join("codex-forksmith.toml");
+        let profiles = load_profiles(&config, &registry)?;
+        let profile = profiles
+            .iter()
+            .find(|profile| profile.id == *name)
+            .with_context(|| format!("no profile {name:?} in {config}"))?;
+        let ids = profile.resolve(&registry);
+        if ids.is_empty() {
+            anyhow::bail!("profile {name:?} selects no patch sets");
+        }
+        for id in ids {
+            if !only_ids.contains(&id) {
+                only_ids.push(id);
+            }
+        }
+    }
 
     let summary = run_update(UpdateOptions {
         workspace_root: workspace.clone(),
@@ -114,19 +493,106 @@ fn cmd_update(args: UpdateArgs) -> Result<()> {
         registry_path,
         ast_rules_dir,
         coccinelle_rules_dir: cocci_rules_dir,
+        grit_rules_dir: args.grit_rules,
+        ast_enabled: !args.no_ast,
+        cocci_enabled: !args.no_cocci,
+        ast_grep_bin: args.ast_grep_bin,
+        coccinelle_bin: args.coccinelle_bin,
+        coccinelle_iso_file: args.cocci_iso_file,
+        coccinelle_macro_file: args.cocci_macro_file,
+        upstream_remote: args.remote,
         upstream_branch: args.branch,
+        upstream_rev: args.rev,
         cargo_check: !args.skip_cargo_check,
+        cargo_check_dir: args.cargo_check_dir,
+        cargo_check_args: args.cargo_check_args,
         output_zip: args.output_zip,
+        sarif_out: args.sarif_out,
+        webhook_url: args.webhook_url,
+        history_log,
+        no_cache: args.no_cache,
+        zip_compression: args.zip_compression,
+        metrics_out: args.metrics_out,
+        assert_nonzero_tags: args.assert_matches_tags,
+        max_matches_per_set: args.max_matches,
+        keep_going: args.keep_going,
+        summary_out: args.summary_out,
+        ignore_globs,
+        use_worktree: args.use_worktree,
+        only_ids,
+        fail_on_warnings: args.fail_on_warnings,
+        commit: args.commit.then_some(CommitOptions {
+            message: args.commit_message,
+            author: args.commit_author,
+        }),
+        rules_hash: args.rules_hash,
+        force: args.force,
     })?;
 
+    if let Some(path) = &args.junit_out {
+        write_report(path, &summary.to_junit_xml())?;
+    }
+    if let Some(path) = &args.markdown_out {
+        write_report(path, &summary.to_markdown())?;
+    }
+
+    write_github_outputs(&summary)?;
+
     if args.json {
         println!("{}", serde_json::to_string_pretty(&summary)?);
     } else {
         print_summary(&summary);
     }
+    if summary.has_failures() {
+        anyhow::bail!("one or more patch sets failed");
+    }
+    if !args.skip_cargo_check && !summary.cargo_check_passed {
+        anyhow::bail!("cargo check failed");
+    }
     Ok(())
 }
 
+/// Appends step outputs and the Markdown step summary when running under
+/// GitHub Actions; a no-op when the env vars are unset.
+fn write_github_outputs(summary: &UpdateSummary) -> Result<()> {
+    if let Some(path) = env::var_os("GITHUB_OUTPUT") {
+        let applied = summary
+            .patch_sets
+            .iter()
+            .filter(|set| matches!(set.result, Some(PatchResult::Applied { .. })))
+            .count();
+        let outputs = format!(
+            "vendor_rev_after={}\ncargo_check_passed={}\npatches_applied={}\nwarnings_count={}\n",
+            summary.vendor_rev_after.as_deref().unwrap_or(""),
+            summary.cargo_check_passed,
+            applied,
+            summary.warnings.len()
+        );
+        append_to(path.as_ref(), &outputs)?;
+    }
+    if let Some(path) = env::var_os("GITHUB_STEP_SUMMARY") {
+        append_to(path.as_ref(), &summary.to_markdown())?;
+    }
+    Ok(())
+}
+
+fn append_to(path: &Path, contents: &str) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    file.write_all(contents.as_bytes())
+        .with_context(|| format!("appending to {}", path.display()))
+}
+
+fn write_report(path: &Utf8Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("creating {parent}"))?;
+    }
+    fs::write(path, contents).with_context(|| format!("writing {path}"))
+}
+
 fn cmd_registry(args: RegistryArgs) -> Result<()> {
     let workspace = default_workspace()
         .unwrap_or_else(|| Utf8PathBuf::from_path_buf(env::current_dir().unwrap()).unwrap());
@@ -136,13 +602,62 @@ fn cmd_registry(args: RegistryArgs) -> Result<()> {
     let store = RegistryStore::new(path);
     let mut registry = store.load()?;
     match args.command {
-        RegistryCommand::List => {
+        RegistryCommand::List { stale_days, wide } => {
+            let threshold = Duration::from_secs(stale_days * 24 * 60 * 60);
+            let mut headers = vec![
+                "ID",
+                "ENGINE",
+                "ENABLED",
+                "LAST STATUS",
+                "MATCHES",
+                "STALE",
+                "TAGS",
+            ];
+            if wide {
+                headers.extend(["CREATED", "LAST APPLIED", "DESCRIPTION", "NOTES"]);
+            }
+            let mut table = Table::new(headers);
             for set in &registry.patch_sets {
-                println!(
-                    "{} [{}] enabled={} notes={:?}",
-                    set.id, set.description, set.enabled, set.notes
-                );
+                let status = match &set.last_result {
+                    Some(PatchResult::Applied { .. }) => "applied",
+                    Some(PatchResult::Skipped { .. }) => "skipped",
+                    Some(PatchResult::Failed { .. }) => "failed",
+                    None => "-",
+                };
+                let mut cells = vec![
+                    set.id.clone(),
+                    rule_engines(&set.rules),
+                    set.enabled.to_string(),
+                    status.to_string(),
+                    set.last_match_count
+                        .map_or_else(|| "-".into(), |n| n.to_string()),
+                    set.is_stale(threshold).to_string(),
+                    if set.tags.is_empty() {
+                        "-".into()
+                    } else {
+                        set.tags.join(", ")
+                    },
+                ];
+                if wide {
+                    for at in [set.created_at, set.last_applied_at] {
+                        cells.push(at.map_or_else(
+                            || "-".into(),
+                            |at| at.format("%Y-%m-%d %H:%M").to_string(),
+                        ));
+                    }
+                    cells.push(set.description.clone());
+                    cells.push(set.notes.clone().unwrap_or_else(|| "-".into()));
+                }
+                let style = if !set.enabled {
+                    RowStyle::Dim
+                } else if status == "failed" || set.last_match_count == Some(0) {
+                    RowStyle::Red
+                } else {
+                    RowStyle::Plain
+                };
+                table.row(cells, style);
             }
+            print!("{}", table.render(table::color_enabled()));
         }
         RegistryCommand::Enable { id } => {
             registry.toggle(&id, true)?;
@@ -154,6 +669,66 @@ fn cmd_registry(args: RegistryArgs) -> Result<()> {
             store.save(&registry)?;
             println!("disabled {id}");
         }
+        RegistryCommand::Touch { id, all } => {
+            let ids: Vec<String> = if all {
+                registry
+                    .patch_sets
+                    .iter()
+                    .map(|set| set.id.clone())
+                    .collect()
+            } else {
+                id.into_iter().collect()
+            };
+            for id in &ids {
+                registry.reset_run_state(id)?;
+            }
+            store.save(&registry)?;
+            println!("reset run state of {} patch set(s)", ids.len());
+        }
+        RegistryCommand::Stats { json } => {
+            let stats = registry.stats();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                println!("{stats}");
+            }
+        }
+        RegistryCommand::Validate {
+            ast_rules,
+            cocci_rules,
+        } => {
+            let issues = registry.validate(ast_rules.as_deref(), cocci_rules.as_deref());
+            if issues.is_empty() {
+                println!("registry ok ({} patch sets)", registry.patch_sets.len());
+            } else {
+                for issue in &issues {
+                    eprintln!("{issue}");
+                }
+                std::process::exit(1);
+            }
+        }
+        RegistryCommand::Schema => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Registry::json_schema())?
+            );
+        }
+        RegistryCommand::Diff { from, to, json } => {
+            for path in [&from, &to] {
+                if !path.exists() {
+                    anyhow::bail!("registry {path} does not exist");
+                }
+            }
+            let diff = Registry::load(&from)?.diff(&Registry::load(&to)?);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diff)?);
+            } else {
+                print!("{diff}");
+                if diff.is_empty() {
+                    println!();
+                }
+            }
+        }
     }
     Ok(())
 }
@@ -163,19 +738,140 @@ fn cmd_doctor(args: DoctorArgs) -> Result<()> {
         .workspace
         .or_else(default_workspace)
         .unwrap_or_else(|| Utf8PathBuf::from_path_buf(env::current_dir().unwrap()).unwrap());
+    let fixes = if args.fix {
+        if !workspace.exists() {
+            anyhow::bail!("workspace {workspace} does not exist; run `init` instead");
+        }
+        let fixes = fix_workspace(&workspace)?;
+        for fix in &fixes {
+            tracing::info!("doctor --fix: {fix}");
+        }
+        fixes
+    } else {
+        Vec::new()
+    };
     let checks = DoctorReport {
         workspace_exists: workspace.exists(),
         vendor_exists: workspace.join("vendor/codex").exists(),
         registry_exists: workspace.join("patch-registry/registry.json").exists(),
+        fixes,
     };
     println!("{}", serde_json::to_string_pretty(&checks)?);
     Ok(())
 }
 
+fn cmd_init(args: InitArgs) -> Result<()> {
+    let workspace = match args.workspace {
+        Some(workspace) => workspace,
+        None => Utf8PathBuf::from_path_buf(env::current_dir()?)
+            .map_err(|p| anyhow::anyhow!("cwd {} is not UTF-8", p.display()))?,
+    };
+    let report = init_workspace(&InitOptions {
+        workspace: workspace.clone(),
+        vendor_url: args.vendor_url,
+        force: args.force,
+    })?;
+    for path in &report.written {
+        println!("created {}", workspace.join(path));
+    }
+    if report.kept_vendor {
+        println!("kept existing {}", workspace.join("vendor/codex"));
+    }
+    Ok(())
+}
+
+fn cmd_history(args: HistoryArgs) -> Result<()> {
+    let workspace = args
+        .workspace
+        .or_else(default_workspace)
+        .unwrap_or_else(|| Utf8PathBuf::from_path_buf(env::current_dir().unwrap()).unwrap());
+    let path = args
+        .history_log
+        .unwrap_or_else(|| default_history_log(&workspace));
+    match args.command {
+        HistoryCommand::Prune { keep } => {
+            let dropped = prune_history(&path, keep)?;
+            println!("dropped {dropped} history entries from {path} (kept at most {keep})");
+        }
+    }
+    Ok(())
+}
+
+fn cmd_log(args: LogArgs) -> Result<()> {
+    let workspace = args
+        .workspace
+        .or_else(default_workspace)
+        .unwrap_or_else(|| Utf8PathBuf::from_path_buf(env::current_dir().unwrap()).unwrap());
+    let path = args
+        .history_log
+        .unwrap_or_else(|| default_history_log(&workspace));
+    let filter = HistoryFilter {
+        since: args.since,
+        failures_only: args.failures_only,
+    };
+    let entries = read_history(&path, &filter)?;
+    let skip = entries.len().saturating_sub(args.tail);
+    for entry in &entries[skip..] {
+        if args.json {
+            println!("{}", serde_json::to_string(entry)?);
+        } else {
+            println!(
+                "{}  applied={} skipped={} failed={} cargo_check={} rev={}..{} {}ms",
+                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                entry.applied,
+                entry.skipped,
+                entry.failed,
+                match entry.cargo_check_passed {
+                    Some(true) => "pass",
+                    Some(false) => "fail",
+                    None => "skipped",
+                },
+                short_rev(entry.vendor_rev_before.as_deref()),
+                short_rev(entry.vendor_rev_after.as_deref()),
+                entry.duration_ms,
+            );
+        }
+    }
+    Ok(())
+}
+
+fn short_rev(rev: Option<&str>) -> &str {
+    rev.map_or("-", |rev| &rev[..rev.len().min(10)])
+}
+
+/// Engines implied by the rule file extensions, e.g. `ast-grep,coccinelle`.
+fn rule_engines(rules: &[String]) -> String {
+    let mut engines = BTreeSet::new();
+    for rule in rules {
+        engines.insert(match Utf8Path::new(rule).extension() {
+            Some("cocci") => "coccinelle",
+            Some("grit") => "grit",
+            _ => "ast-grep",
+        });
+    }
+    if engines.is_empty() {
+        "-".into()
+    } else {
+        engines.into_iter().collect::<Vec<_>>().join(",")
+    }
+}
+
+fn default_history_log(workspace: &Utf8Path) -> Utf8PathBuf {
+    workspace.join("patch-registry/history.jsonl")
+}
+
+/// The nearest ancestor of the cwd holding `codex-forksmith.toml`, else the
+/// conventional checkout under `~/development`.
 fn default_workspace() -> Option<Utf8PathBuf> {
-    let home = env::var("HOME").ok()?;
-    let new_path = Utf8PathBuf::from(format!("{home}/development/codex-forksmith"));
-    let legacy_path = Utf8PathBuf::from(format!("{home}/development/codex-patcher-updater"));
+    discover_workspace().or_else(|| home_workspace(|key| env::var(key).ok()))
+}
+
+/// `~/development/codex-forksmith`, or the legacy `codex-patcher-updater`,
+/// whichever exists; `var` looks up env vars.
+fn home_workspace(var: impl Fn(&str) -> Option<String>) -> Option<Utf8PathBuf> {
+    let development = home_dir(var)?.join("development");
+    let new_path = development.join("codex-forksmith");
+    let legacy_path = development.join("codex-patcher-updater");
     if new_path.exists() {
         Some(new_path)
     } else if legacy_path.exists() {
@@ -185,7 +881,34 @@ fn default_workspace() -> Option<Utf8PathBuf> {
     }
 }
 
+/// `$HOME`, else `%USERPROFILE%` or `%HOMEDRIVE%%HOMEPATH%` so the default
+/// also resolves on Windows.
+fn home_dir(var: impl Fn(&str) -> Option<String>) -> Option<Utf8PathBuf> {
+    let non_empty = |key: &str| var(key).filter(|value| !value.is_empty());
+    non_empty("HOME")
+        .or_else(|| non_empty("USERPROFILE"))
+        .or_else(|| {
+            Some(format!(
+                "{}{}",
+                non_empty("HOMEDRIVE")?,
+                non_empty("HOMEPATH")?
+            ))
+        })
+        .map(Utf8PathBuf::from)
+}
+
+/// Walks up from the cwd like cargo looking for `Cargo.toml`.
+fn discover_workspace() -> Option<Utf8PathBuf> {
+    let cwd = Utf8PathBuf::from_path_buf(env::current_dir().ok()?).ok()?;
+    cwd.ancestors()
+        .find(|dir| dir.join("codex-forksmith.toml").is_file())
+        .map(Utf8Path::to_path_buf)
+}
+
 fn print_summary(summary: &UpdateSummary) {
+    if summary.up_to_date {
+        println!("up to date: upstream, rules and patch sets unchanged since the last passing run");
+    }
     println!("vendor before: {:?}", summary.vendor_rev_before);
     println!("vendor after : {:?}", summary.vendor_rev_after);
     if !summary.ast_notes.is_empty() {
@@ -200,10 +923,41 @@ fn print_summary(summary: &UpdateSummary) {
             println!("  - {note}");
         }
     }
+    if !summary.grit_notes.is_empty() {
+        println!("grit:");
+        for note in &summary.grit_notes {
+            println!("  - {note}");
+        }
+    }
     println!("cargo check: {}", summary.cargo_check_passed);
-    if !summary.warnings.is_empty() {
-        println!("warnings:");
-        for w in &summary.warnings {
+    if let Some(stat) = &summary.diff_stat {
+        println!(
+            "diff: {} files, +{} -{}",
+            stat.files_changed, stat.insertions, stat.deletions
+        );
+    }
+    if let Some(sha) = &summary.commit_sha {
+        println!("committed  : {sha}");
+    }
+    for error in &summary.cargo_errors {
+        println!("  - {error}");
+    }
+    print_warnings(&summary.warnings);
+}
+
+/// Warnings grouped by severity, most severe first.
+fn print_warnings(all: &[Warning]) {
+    for level in [
+        WarningLevel::Critical,
+        WarningLevel::Runtime,
+        WarningLevel::Config,
+    ] {
+        let warnings: Vec<&Warning> = all.iter().filter(|w| w.severity == level).collect();
+        if warnings.is_empty() {
+            continue;
+        }
+        println!("warnings ({level:?}):");
+        for w in warnings {
             println!("  - {w}");
         }
     }
@@ -214,4 +968,23 @@ struct DoctorReport {
     workspace_exists: bool,
     vendor_exists: bool,
     registry_exists: bool,
+    /// Repairs made by `--fix`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_workspace_falls_back_to_userprofile() {
+        let tmp = tempfile::tempdir().unwrap();
+        let profile = Utf8Path::from_path(tmp.path()).unwrap();
+        let workspace = profile.join("development").join("codex-forksmith");
+        std::fs::create_dir_all(&workspace).unwrap();
+        let var = |key: &str| (key == "USERPROFILE").then(|| profile.to_string());
+        assert_eq!(home_workspace(var), Some(workspace));
+        assert_eq!(home_workspace(|_| None), None);
+    }
 }
@@ -1,8 +1,9 @@
 use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::process::Command;
-use std::time::{Duration, SystemTime};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use camino::Utf8PathBuf;
@@ -12,9 +13,61 @@ use tracing_subscriber::{fmt, EnvFilter};
 
 fn main() -> Result<()> {
     init_tracing();
-    let config = WrapperConfig::from_env()?;
-    maybe_run_update(&config)?;
-    exec_codex(&config)
+    let args = WrapperArgs::parse(env::args().skip(1))?;
+    let mut config = WrapperConfig::from_env()?;
+    if let Some(interval) = args.interval {
+        config.auto_interval = interval;
+    }
+    if args.status {
+        print_status(&config);
+        return Ok(());
+    }
+    maybe_run_update(&config, args.update_now)?;
+    exec_codex(&config, args.forward)
+}
+
+/// Wrapper-only pseudo-args, stripped before the rest reach codex.
+#[derive(Debug, Default, PartialEq)]
+struct WrapperArgs {
+    /// `--forksmith-interval <dur>`: overrides `CODEX_WRAPPER_AUTO_INTERVAL`
+    /// for this launch.
+    interval: Option<Duration>,
+    /// `--forksmith-update-now`: update regardless of the stamp file.
+    update_now: bool,
+    /// `--forksmith-status`: report update state and exit without codex.
+    status: bool,
+    forward: Vec<String>,
+}
+
+impl WrapperArgs {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let interval = if arg == "--forksmith-interval" {
+                Some(
+                    args.next()
+                        .context("--forksmith-interval needs a duration")?,
+                )
+            } else {
+                arg.strip_prefix("--forksmith-interval=")
+                    .map(str::to_string)
+            };
+            if let Some(interval) = interval {
+                parsed.interval = Some(
+                    humantime::parse_duration(&interval)
+                        .with_context(|| format!("invalid --forksmith-interval {interval:?}"))?,
+                );
+            } else if arg == "--forksmith-update-now" {
+                parsed.update_now = true;
+            } else if arg == "--forksmith-status" {
+                parsed.status = true;
+            } else {
+                parsed.forward.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
 }
 
 fn init_tracing() {
@@ -28,89 +81,250 @@ struct WrapperConfig {
     workspace_root: Utf8PathBuf,
     codex_bin: Utf8PathBuf,
     stamp_file: PathBuf,
+    /// Held while an update runs so concurrent launches don't both update.
+    lock_file: PathBuf,
     auto_interval: Duration,
+    webhook_url: Option<String>,
 }
 
 impl WrapperConfig {
     fn from_env() -> Result<Self> {
-        let home = env::var("HOME").context("HOME unset")?;
-        let workspace = env::var("CODEX_WORKSPACE")
-            .or_else(|_| env::var("CODEX_FORKSMITH_WORKSPACE"))
-            .or_else(|_| env::var("CODEX_PATCHER_WORKSPACE"))
-            .or_else(|_| env::var("CODEX_PATCHER_UPDATER_WORKSPACE"))
-            .unwrap_or_else(|_| {
-                let new_path = format!("{home}/development/codex-forksmith");
-                let legacy_path = format!("{home}/development/codex-patcher-updater");
-                if std::path::Path::new(&new_path).exists()
-                    || !std::path::Path::new(&legacy_path).exists()
-                {
-                    new_path
-                } else {
-                    legacy_path
-                }
-            });
-        let updater_bin = env::var("CODEX_FORKSMITH")
-            .or_else(|_| env::var("CODEX_PATCHER_UPDATER"))
-            .or_else(|_| env::var("CODEX_UPDATER"))
-            .unwrap_or_else(|_| {
-                let fork_candidate = format!("{workspace}/target/debug/codex-forksmith");
-                if std::path::Path::new(&fork_candidate).exists() {
+        let config = Self::from_lookup(|key| env::var(key).ok())?;
+        if let Some(stamp_dir) = config.stamp_file.parent() {
+            fs::create_dir_all(stamp_dir)?;
+        }
+        Ok(config)
+    }
+
+    /// Builds the config from `var`, which stands in for the process env.
+    fn from_lookup(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let home = home_dir(&var).context("neither HOME nor USERPROFILE is set")?;
+        let workspace = [
+            "CODEX_WORKSPACE",
+            "CODEX_FORKSMITH_WORKSPACE",
+            "CODEX_PATCHER_WORKSPACE",
+            "CODEX_PATCHER_UPDATER_WORKSPACE",
+        ]
+        .into_iter()
+        .find_map(&var)
+        .map(Utf8PathBuf::from)
+        .unwrap_or_else(|| {
+            let new_path = home.join("development").join("codex-forksmith");
+            let legacy_path = home.join("development").join("codex-patcher-updater");
+            if new_path.exists() || !legacy_path.exists() {
+                new_path
+            } else {
+                legacy_path
+            }
+        });
+        let debug_dir = workspace.join("target").join("debug");
+        let updater_bin = ["CODEX_FORKSMITH", "CODEX_PATCHER_UPDATER", "CODEX_UPDATER"]
+            .into_iter()
+            .find_map(&var)
+            .map(Utf8PathBuf::from)
+            .unwrap_or_else(|| {
+                let fork_candidate = debug_dir.join(exe("codex-forksmith"));
+                if fork_candidate.exists() {
                     fork_candidate
                 } else {
-                    format!("{workspace}/target/debug/codex-updater-cli")
+                    debug_dir.join(exe("codex-updater-cli"))
                 }
             });
-        let codex_bin = env::var("CODEX_BIN")
-            .unwrap_or_else(|_| format!("{workspace}/vendor/codex/target/debug/codex"));
-        let stamp_dir = Utf8PathBuf::from(format!("{home}/.local/share/codex-wrapper"));
-        fs::create_dir_all(&stamp_dir)?;
-        let interval_secs: u64 = env::var("CODEX_WRAPPER_AUTO_INTERVAL")
-            .ok()
+        let codex_bin = var("CODEX_BIN").map(Utf8PathBuf::from).unwrap_or_else(|| {
+            let vendor_bin = workspace
+                .join("vendor/codex/target/debug")
+                .join(exe("codex"));
+            if vendor_bin.exists() {
+                return vendor_bin;
+            }
+            match codex_on_path(var("PATH")) {
+                Some(found) => {
+                    warn!("{vendor_bin} not found; using {found} from PATH");
+                    found
+                }
+                None => vendor_bin,
+            }
+        });
+        let stamp_dir = home.join(".local").join("share").join("codex-wrapper");
+        let interval_secs: u64 = var("CODEX_WRAPPER_AUTO_INTERVAL")
             .and_then(|s| s.parse().ok())
             .unwrap_or(24 * 3600);
         Ok(Self {
-            updater_bin: Utf8PathBuf::from(updater_bin),
-            workspace_root: Utf8PathBuf::from(workspace),
-            codex_bin: Utf8PathBuf::from(codex_bin),
+            updater_bin,
+            workspace_root: workspace,
+            codex_bin,
             stamp_file: stamp_dir.join("last-update").into_std_path_buf(),
+            lock_file: stamp_dir.join("update.lock").into_std_path_buf(),
             auto_interval: Duration::from_secs(interval_secs),
+            webhook_url: var("CODEX_WRAPPER_WEBHOOK"),
         })
     }
 }
 
-fn maybe_run_update(config: &WrapperConfig) -> Result<()> {
-    let needs_update = match fs::metadata(&config.stamp_file) {
-        Ok(meta) => {
-            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-            modified.elapsed().unwrap_or_default() > config.auto_interval
-        }
-        Err(_) => true,
-    };
+/// `$HOME`, else `%USERPROFILE%`, else `%HOMEDRIVE%%HOMEPATH%`; Windows
+/// normally only sets the latter two.
+fn home_dir(var: impl Fn(&str) -> Option<String>) -> Option<Utf8PathBuf> {
+    let non_empty = |key: &str| var(key).filter(|value| !value.is_empty());
+    non_empty("HOME")
+        .or_else(|| non_empty("USERPROFILE"))
+        .or_else(|| {
+            Some(format!(
+                "{}{}",
+                non_empty("HOMEDRIVE")?,
+                non_empty("HOMEPATH")?
+            ))
+        })
+        .map(Utf8PathBuf::from)
+}
+
+/// The first `codex` on `path` that is not this wrapper itself, which is
+/// often installed under the same name.
+fn codex_on_path(path: Option<String>) -> Option<Utf8PathBuf> {
+    let current = env::current_exe().and_then(fs::canonicalize).ok();
+    which::which_in_global("codex", path)
+        .ok()?
+        .filter(|found| fs::canonicalize(found).ok() != current)
+        .find_map(|found| Utf8PathBuf::from_path_buf(found).ok())
+}
+
+fn exe(name: &str) -> String {
+    format!("{name}{}", env::consts::EXE_SUFFIX)
+}
+
+/// Time since the last successful update, from the stamp's mtime.
+fn since_last_update(config: &WrapperConfig) -> Option<Duration> {
+    let modified = fs::metadata(&config.stamp_file).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+fn maybe_run_update(config: &WrapperConfig, force: bool) -> Result<()> {
+    let needs_update =
+        force || since_last_update(config).is_none_or(|age| age > config.auto_interval);
     if !needs_update {
         return Ok(());
     }
+    let Some(_lock) = UpdateLock::acquire(&config.lock_file)? else {
+        info!("another launch is updating; skipping");
+        return Ok(());
+    };
     info!(
         "running codex-forksmith for workspace {}",
         config.workspace_root
     );
-    let status = Command::new(&config.updater_bin)
-        .arg("update")
+    let mut cmd = Command::new(&config.updater_bin);
+    cmd.arg("update")
         .arg("--workspace")
         .arg(&config.workspace_root)
-        .arg("--json")
+        .arg("--json");
+    if let Some(url) = &config.webhook_url {
+        // Passed via env rather than argv so the URL stays out of `ps`.
+        cmd.env("CODEX_FORKSMITH_WEBHOOK", url);
+    }
+    let status = cmd
         .status()
         .with_context(|| format!("launching {}", config.updater_bin))?;
     if status.success() {
-        let now = Utc::now();
-        fs::write(&config.stamp_file, now.to_rfc3339())?;
+        write_atomic(&config.stamp_file, &Utc::now().to_rfc3339())?;
     } else {
         warn!("updater exited with {status}");
     }
     Ok(())
 }
 
-fn exec_codex(config: &WrapperConfig) -> Result<()> {
-    let mut args = env::args().skip(1).collect::<Vec<_>>();
+/// Writes via a sibling temp file and a rename, so a crash never leaves a
+/// truncated stamp behind.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&tmp, contents).with_context(|| format!("writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("renaming into {}", path.display()))
+}
+
+/// A lock older than this is assumed to belong to a crashed wrapper.
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(2 * 3600);
+
+/// Exclusive `update.lock` holding the owner's pid; removed on drop.
+struct UpdateLock {
+    path: PathBuf,
+}
+
+impl UpdateLock {
+    /// `None` when a live lock is held by another process.
+    fn acquire(path: &Path) -> Result<Option<Self>> {
+        for _ in 0..2 {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+            {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())?;
+                    return Ok(Some(Self {
+                        path: path.to_path_buf(),
+                    }));
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    if lock_age(path).is_some_and(|age| age < LOCK_STALE_AFTER) {
+                        return Ok(None);
+                    }
+                    warn!("removing stale lock {}", path.display());
+                    let _ = fs::remove_file(path);
+                }
+                Err(err) => {
+                    return Err(err).with_context(|| format!("creating {}", path.display()))
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for UpdateLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_age(path: &Path) -> Option<Duration> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.elapsed().unwrap_or_default())
+}
+
+fn print_status(config: &WrapperConfig) {
+    let ago = |age: Duration| humantime::format_duration(Duration::from_secs(age.as_secs()));
+    match since_last_update(config) {
+        Some(age) => {
+            let stamp = fs::read_to_string(&config.stamp_file).unwrap_or_default();
+            println!("last update : {} ({} ago)", stamp.trim(), ago(age));
+            match config.auto_interval.checked_sub(age) {
+                Some(left) if !left.is_zero() => println!("next update : in {}", ago(left)),
+                _ => println!("next update : due on next launch"),
+            }
+        }
+        None => {
+            println!("last update : never");
+            println!("next update : due on next launch");
+        }
+    }
+    println!("interval    : {}", ago(config.auto_interval));
+    match lock_age(&config.lock_file) {
+        Some(age) => {
+            let pid = fs::read_to_string(&config.lock_file).unwrap_or_default();
+            let stale = if age >= LOCK_STALE_AFTER {
+                ", stale"
+            } else {
+                ""
+            };
+            println!(
+                "lock        : held by pid {} for {}{stale}",
+                pid.trim(),
+                ago(age)
+            );
+        }
+        None => println!("lock        : free"),
+    }
+}
+
+fn exec_codex(config: &WrapperConfig, mut args: Vec<String>) -> Result<()> {
     let mut cmd = Command::new(&config.codex_bin);
     if args.is_empty() {
         args.push("--help".into());
@@ -120,7 +334,120 @@ fn exec_codex(config: &WrapperConfig) -> Result<()> {
         .status()
         .with_context(|| format!("launching codex binary at {}", config.codex_bin))?;
     if !status.success() {
-        anyhow::bail!("codex exited with {status}");
+        std::process::exit(exit_code(status));
     }
     Ok(())
 }
+
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use camino::Utf8PathBuf;
+
+    use super::{UpdateLock, WrapperArgs, WrapperConfig};
+
+    fn config(vars: &[(&str, &str)]) -> anyhow::Result<WrapperConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        WrapperConfig::from_lookup(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn unix_home_sets_default_workspace() {
+        let cfg = config(&[("HOME", "/home/me")]).unwrap();
+        assert_eq!(
+            cfg.workspace_root,
+            Utf8PathBuf::from("/home/me/development/codex-forksmith")
+        );
+    }
+
+    #[test]
+    fn windows_profile_stands_in_for_home() {
+        let home = Utf8PathBuf::from(r"C:\Users\me");
+        let cfg = config(&[("USERPROFILE", home.as_str())]).unwrap();
+        assert_eq!(
+            cfg.workspace_root,
+            home.join("development").join("codex-forksmith")
+        );
+        let cfg = config(&[("HOMEDRIVE", "C:"), ("HOMEPATH", r"\Users\me")]).unwrap();
+        assert!(cfg.workspace_root.starts_with(&home));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn codex_on_path_is_the_fallback() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("codex");
+        std::fs::write(&bin, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&bin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = dir.path().to_str().unwrap();
+
+        let cfg = config(&[("HOME", "/nonexistent"), ("PATH", path)]).unwrap();
+        assert_eq!(cfg.codex_bin.as_std_path(), bin);
+        let cfg = config(&[
+            ("HOME", "/nonexistent"),
+            ("PATH", path),
+            ("CODEX_BIN", "/x/codex"),
+        ])
+        .unwrap();
+        assert_eq!(cfg.codex_bin, Utf8PathBuf::from("/x/codex"));
+    }
+
+    #[test]
+    fn forksmith_pseudo_args_are_stripped() {
+        let args = |list: &[&str]| WrapperArgs::parse(list.iter().map(|a| a.to_string()));
+        let parsed = args(&[
+            "exec",
+            "--forksmith-interval",
+            "2h",
+            "--forksmith-update-now",
+            "--model=x",
+        ])
+        .unwrap();
+        assert_eq!(parsed.interval, Some(std::time::Duration::from_secs(7200)));
+        assert!(parsed.update_now);
+        assert_eq!(parsed.forward, ["exec", "--model=x"]);
+        assert_eq!(
+            args(&["--forksmith-interval=1d"]).unwrap().interval,
+            Some(std::time::Duration::from_secs(86400))
+        );
+        assert!(args(&["--forksmith-interval"]).is_err());
+        assert!(args(&["--forksmith-interval", "soon"]).is_err());
+    }
+
+    #[test]
+    fn update_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("update.lock");
+        let held = UpdateLock::acquire(&path).unwrap();
+        assert!(held.is_some());
+        assert!(UpdateLock::acquire(&path).unwrap().is_none());
+        drop(held);
+        assert!(!path.exists());
+        assert!(UpdateLock::acquire(&path).unwrap().is_some());
+    }
+
+    #[test]
+    fn missing_home_is_an_error() {
+        assert!(config(&[]).is_err());
+    }
+}
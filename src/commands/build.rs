@@ -1,12 +1,12 @@
-use std::{env, process::Command};
+use std::{env, path::Path, process::Command};
 
 use anyhow::{Context, Result};
+use codex_core::fs_config::ForksmithConfig;
 
-use crate::fs_config::ForksmithConfig;
 use crate::git;
 use which::which;
 
-pub fn run(cfg: &ForksmithConfig) -> Result<()> {
+pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
     let repo = &cfg.repo_path;
     git::ensure_repo(repo)?;
     if !git::is_clean(repo)? {
@@ -15,15 +15,41 @@ pub fn run(cfg: &ForksmithConfig) -> Result<()> {
             repo.display()
         );
     }
+    if let (Some(triple), Some(relpath)) = (&cfg.build_target, &cfg.binary_relpath) {
+        let nested = Path::new("target").join(triple);
+        if !relpath.ancestors().any(|p| p.ends_with(&nested)) {
+            println!(
+                "warning: binary_relpath {} is not under target/{triple}/; cargo --target puts artifacts there",
+                relpath.display()
+            );
+        }
+    }
+    let manifest = cfg.build_workspace.join("Cargo.toml");
+    if !manifest.is_file() {
+        anyhow::bail!(
+            "build workspace {} has no Cargo.toml",
+            cfg.build_workspace.display()
+        );
+    }
+    let args = cfg.cargo_build_args();
+    if dry_run {
+        println!(
+            "(dry-run) would build codex in {} (profile {})",
+            cfg.build_workspace.display(),
+            cfg.build_profile
+        );
+        println!("  cargo {}", args.join(" "));
+        println!("  expected binary: {}", cfg.repo_binary_path().display());
+        return Ok(());
+    }
     println!(
         "building codex in {} (profile {})",
         cfg.build_workspace.display(),
         cfg.build_profile
     );
+    println!("  cargo {}", args.join(" "));
     let mut command = Command::new("cargo");
-    command
-        .args(["build", "--profile", &cfg.build_profile])
-        .current_dir(&cfg.build_workspace);
+    command.args(&args).current_dir(&cfg.build_workspace);
     configure_rustc_wrapper(&mut command);
     let status = command.status().context("running cargo build")?;
     if !status.success() {
@@ -0,0 +1,40 @@
+use anyhow::{bail, Context, Result};
+use codex_core::fs_config::ForksmithConfig;
+
+use crate::git;
+
+/// How many tags to suggest when `rev` does not resolve.
+const TAG_HINT_LIMIT: usize = 5;
+
+pub fn run(cfg: &ForksmithConfig, rev: &str) -> Result<()> {
+    let repo = &cfg.repo_path;
+    git::ensure_repo(repo)?;
+    if !git::is_clean(repo)? {
+        bail!(
+            "repo {} has local changes; commit or stash before checking out {rev}",
+            repo.display()
+        );
+    }
+    let spec = format!("{rev}^{{commit}}");
+    if git::run_git(repo, &["rev-parse", "--verify", "--quiet", &spec]).is_err() {
+        let tags = git::list_tags(repo)?;
+        if tags.is_empty() {
+            bail!("unknown revision {rev} in {}", repo.display());
+        }
+        let shown = &tags[..tags.len().min(TAG_HINT_LIMIT)];
+        bail!(
+            "unknown revision {rev} in {}; recent tags: {}",
+            repo.display(),
+            shown.join(", ")
+        );
+    }
+    git::checkout(repo, rev).with_context(|| format!("checking out {rev}"))?;
+    let branch = git::current_branch(repo)?;
+    let head = git::head_commit(repo)?;
+    if branch == "HEAD" {
+        println!("checked out {rev} (detached HEAD at {head})");
+    } else {
+        println!("checked out {rev} (branch {branch} at {head})");
+    }
+    Ok(())
+}
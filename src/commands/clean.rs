@@ -0,0 +1,61 @@
+use anyhow::{bail, Result};
+use codex_core::fs_config::ForksmithConfig;
+
+use crate::git;
+
+pub fn run(cfg: &ForksmithConfig, hard: bool) -> Result<()> {
+    let repo = &cfg.repo_path;
+    git::ensure_repo(repo)?;
+    let unmerged = git::unmerged_paths(repo)?;
+    let tracked = git::changed_tracked_paths(repo)?;
+    let removed = git::clean_preview(repo)?;
+
+    if !hard {
+        print_preview(&tracked, &removed);
+        if !unmerged.is_empty() {
+            bail!(
+                "repo {} has unmerged paths ({}); rerun with --hard to discard the merge",
+                repo.display(),
+                unmerged.join(", ")
+            );
+        }
+        println!(
+            "(preview) rerun with --hard to reset and clean {}",
+            repo.display()
+        );
+        return Ok(());
+    }
+
+    println!("resetting {} to HEAD...", repo.display());
+    git::reset_hard(repo, "HEAD")?;
+    println!("removing untracked and ignored files...");
+    git::clean_force(repo)?;
+    println!(
+        "CLEAN_RESULT reset_paths={} removed_paths={}",
+        tracked.len(),
+        removed.len()
+    );
+    Ok(())
+}
+
+fn print_preview(tracked: &[String], removed: &[String]) {
+    if tracked.is_empty() && removed.is_empty() {
+        println!("nothing to clean; vendor tree is pristine");
+        return;
+    }
+    if !tracked.is_empty() {
+        println!(
+            "would discard changes to {} tracked file(s):",
+            tracked.len()
+        );
+        for path in tracked {
+            println!("  {path}");
+        }
+    }
+    if !removed.is_empty() {
+        println!("would remove {} untracked/ignored path(s):", removed.len());
+        for path in removed {
+            println!("  {path}");
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use anyhow::Result;
+use codex_core::fs_config::ForksmithConfig;
+
+use crate::git;
+
+pub fn run(cfg: &ForksmithConfig, stat: bool) -> Result<()> {
+    let repo = &cfg.repo_path;
+    git::ensure_repo(repo)?;
+    let upstream_ref = format!("{}/{}", cfg.upstream_remote, cfg.upstream_branch);
+    let range = format!("{upstream_ref}...HEAD");
+    let output = git::diff(repo, &range, stat)?;
+    if output.is_empty() {
+        println!("no changes relative to {upstream_ref}");
+    } else {
+        println!("{output}");
+    }
+    Ok(())
+}
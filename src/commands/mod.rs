@@ -1,4 +1,7 @@
 pub mod build;
+pub mod checkout;
+pub mod clean;
+pub mod diff;
 pub mod run;
 pub mod status;
 pub mod sync;
@@ -1,39 +1,116 @@
 use std::env;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use codex_core::fs_config::ForksmithConfig;
 
 use crate::commands::build;
-use crate::fs_config::ForksmithConfig;
 
-pub fn run(cfg: &ForksmithConfig, args: &[String]) -> Result<()> {
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Run this codex binary instead of the configured build.
+    pub codex_bin: Option<PathBuf>,
+    /// Never inject `-C <cwd>`.
+    pub no_cd: bool,
+}
+
+pub fn run(cfg: &ForksmithConfig, args: &[String], opts: &RunOptions) -> Result<()> {
+    if let Some(binary) = &opts.codex_bin {
+        if !binary.exists() {
+            bail!("--codex-bin {} does not exist", binary.display());
+        }
+        return launch(binary, args, opts.no_cd);
+    }
     let binary = cfg.repo_binary_path();
     if !binary.exists() {
         println!(
             "binary {} missing; building via `codex build` before running",
             binary.display()
         );
-        build::run(cfg)?;
+        build::run(cfg, false)?;
     }
-    let final_args = append_default_cwd_arg(args)?;
-    let mut cmd = Command::new(&binary);
+    launch(&binary, args, opts.no_cd)
+}
+
+fn launch(binary: &Path, args: &[String], no_cd: bool) -> Result<()> {
+    let final_args = if no_cd || !wants_cwd_arg(args) {
+        args.to_vec()
+    } else {
+        append_default_cwd_arg(args)?
+    };
+    let mut cmd = Command::new(binary);
     cmd.args(&final_args)
         .stdin(Stdio::inherit())
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
-    let status = cmd
-        .status()
+    let mut child = cmd
+        .spawn()
         .with_context(|| format!("launching {}", binary.display()))?;
+    let status = wait_forwarding_signals(&mut child)
+        .with_context(|| format!("waiting for {}", binary.display()))?;
     if !status.success() {
-        anyhow::bail!("codex exited with {status}");
+        std::process::exit(exit_code(status));
     }
     Ok(())
 }
 
-fn append_default_cwd_arg(args: &[String]) -> Result<Vec<String>> {
-    if contains_cwd_flag(args) {
-        return Ok(args.to_vec());
+/// Waits for codex while relaying SIGINT/SIGTERM to it, so forksmith never
+/// exits ahead of (and orphans) an interactive session.
+#[cfg(unix)]
+fn wait_forwarding_signals(child: &mut Child) -> Result<ExitStatus> {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGINT, SIGTERM]).context("installing signal handlers")?;
+    let handle = signals.handle();
+    let pid = child.id() as libc::pid_t;
+    let forwarder = std::thread::spawn(move || {
+        for signal in signals.forever() {
+            // SAFETY: kill(2) has no memory-safety preconditions; pid is our child.
+            unsafe {
+                libc::kill(pid, signal);
+            }
+        }
+    });
+    let status = child.wait();
+    handle.close();
+    let _ = forwarder.join();
+    Ok(status?)
+}
+
+#[cfg(not(unix))]
+fn wait_forwarding_signals(child: &mut Child) -> Result<ExitStatus> {
+    Ok(child.wait()?)
+}
+
+/// Maps a child's exit status to the code we should exit with, using the
+/// shell's 128+signal convention when the child was killed by a signal.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
     }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
+
+/// Flags that only print information, so a `-C` would be noise.
+const INFO_FLAGS: &[&str] = &["--version", "-V", "--help", "-h"];
+
+/// `-C <cwd>` is injected unless the args already set `-C`/`--cd`/`--cd=`,
+/// or consist only of informational flags like `--version` and `--help`.
+fn wants_cwd_arg(args: &[String]) -> bool {
+    let info_only = !args.is_empty() && args.iter().all(|a| INFO_FLAGS.contains(&a.as_str()));
+    !info_only && !contains_cwd_flag(args)
+}
+
+fn append_default_cwd_arg(args: &[String]) -> Result<Vec<String>> {
     let cwd = env::current_dir().context("resolving current directory")?;
     let mut final_args = Vec::with_capacity(args.len() + 2);
     final_args.push("-C".to_string());
@@ -43,8 +120,7 @@ fn append_default_cwd_arg(args: &[String]) -> Result<Vec<String>> {
 }
 
 fn contains_cwd_flag(args: &[String]) -> bool {
-    let mut iter = args.iter();
-    while let Some(arg) = iter.next() {
+    for arg in args {
         if arg == "-C" || arg == "--cd" {
             return true;
         }
@@ -54,3 +130,22 @@ fn contains_cwd_flag(args: &[String]) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::wants_cwd_arg;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn cwd_injection_skips_info_flags_and_explicit_cd() {
+        assert!(wants_cwd_arg(&args(&[])));
+        assert!(wants_cwd_arg(&args(&["resume"])));
+        assert!(!wants_cwd_arg(&args(&["--version"])));
+        assert!(!wants_cwd_arg(&args(&["-h"])));
+        assert!(wants_cwd_arg(&args(&["exec", "--help"])));
+        assert!(!wants_cwd_arg(&args(&["--cd=/tmp", "resume"])));
+    }
+}
@@ -1,30 +1,46 @@
 use std::path::Path;
+use std::process::Command;
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use codex_core::fs_config::ForksmithConfig;
+use serde::Serialize;
 
-use crate::fs_config::ForksmithConfig;
-use crate::git;
+use crate::git::{self, DIRTY_PATH_LIMIT};
 
-pub fn run(cfg: &ForksmithConfig) -> Result<()> {
+pub fn run(cfg: &ForksmithConfig, json: bool) -> Result<()> {
     let report = StatusReport::gather(cfg)?;
-    report.print();
+    if json {
+        report.print_json()?;
+    } else {
+        report.print();
+    }
     if report.should_fail() {
         bail!("status check failed; resolve issues above");
     }
     Ok(())
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct StatusReport {
     pub workspace_root: String,
     pub repo: String,
     pub build_dir: String,
     pub branch: String,
+    /// HEAD points at a commit rather than a branch (e.g. after `checkout`).
+    pub detached: bool,
     pub head: String,
     pub clean: bool,
     pub has_conflicts: bool,
+    /// Changed tracked paths, not counting conflicts.
     pub tracked: usize,
+    /// Tracked paths with changes in the index.
+    pub staged: usize,
     pub untracked: usize,
+    pub conflicted_paths: Vec<String>,
+    /// First few dirty paths; `tracked + untracked` is the full count.
+    pub dirty_paths: Vec<String>,
+    /// Untracked, non-ignored paths a `git clean -fd` would delete.
+    pub would_clean: Vec<String>,
     pub local_ref: String,
     pub upstream_ref: String,
     pub local_ahead: u32,
@@ -33,6 +49,11 @@ pub struct StatusReport {
     pub upstream_behind: u32,
     pub binary_path: String,
     pub binary_exists: bool,
+    /// Trimmed `<binary> --version` output.
+    pub binary_version: Option<String>,
+    /// Why `binary_version` is missing when the binary exists.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_version_error: Option<String>,
 }
 
 impl StatusReport {
@@ -43,6 +64,7 @@ impl StatusReport {
         let clean = git::is_clean(repo)?;
         let has_conflicts = git::has_unmerged_paths(repo)?;
         let snapshot = git::status_snapshot(repo)?;
+        let would_clean = git::untracked_preview(repo)?;
         let local_ref = format!("{}/{}", cfg.local_remote, cfg.local_branch);
         let upstream_ref = format!("{}/{}", cfg.upstream_remote, cfg.upstream_branch);
         let head = git::head_commit(repo)?;
@@ -50,16 +72,29 @@ impl StatusReport {
         let (ahead_upstream, behind_upstream) = divergence(repo, "HEAD", &upstream_ref)?;
         let binary_path = cfg.repo_binary_path();
         let binary_exists = binary_path.exists();
+        let (binary_version, binary_version_error) = if binary_exists {
+            match binary_version(&binary_path) {
+                Ok(version) => (Some(version), None),
+                Err(err) => (None, Some(format!("{err:#}"))),
+            }
+        } else {
+            (None, None)
+        };
         Ok(Self {
             workspace_root: cfg.workspace_root.display().to_string(),
             repo: repo.display().to_string(),
             build_dir: cfg.build_workspace.display().to_string(),
+            detached: branch == "HEAD",
             branch,
             head,
             clean,
             has_conflicts,
             tracked: snapshot.tracked,
+            staged: snapshot.staged,
             untracked: snapshot.untracked,
+            conflicted_paths: snapshot.conflicted,
+            dirty_paths: snapshot.dirty,
+            would_clean,
             local_ref,
             upstream_ref,
             local_ahead: ahead_local,
@@ -68,6 +103,8 @@ impl StatusReport {
             upstream_behind: behind_upstream,
             binary_path: binary_path.display().to_string(),
             binary_exists,
+            binary_version,
+            binary_version_error,
         })
     }
 
@@ -75,15 +112,41 @@ impl StatusReport {
         println!("workspace     = {}", self.workspace_root);
         println!("repo          = {}", self.repo);
         println!("build_dir     = {}", self.build_dir);
-        println!("branch        = {}", self.branch);
+        if self.detached {
+            println!("branch        = (detached HEAD)");
+        } else {
+            println!("branch        = {}", self.branch);
+        }
         println!("head          = {}", self.head);
         println!("clean         = {}", self.clean);
         println!(
-            "dirty_counts  = tracked {} untracked {}",
-            self.tracked, self.untracked
+            "dirty_counts  = tracked {} (staged {}) untracked {} conflicted {}",
+            self.tracked,
+            self.staged,
+            self.untracked,
+            self.conflicted_paths.len()
         );
+        for line in path_lines(&self.dirty_paths, self.tracked + self.untracked) {
+            println!("                {line}");
+        }
+        if !self.would_clean.is_empty() {
+            println!(
+                "would_clean   = {} untracked path(s) lost on clean",
+                self.would_clean.len()
+            );
+            let total = self.would_clean.len();
+            let shown = &self.would_clean[..total.min(DIRTY_PATH_LIMIT)];
+            for line in path_lines(shown, total) {
+                println!("                {line}");
+            }
+        }
         if self.has_conflicts {
             println!("conflicts     = true (resolve git merge conflicts)");
+            let total = self.conflicted_paths.len();
+            let shown = &self.conflicted_paths[..total.min(DIRTY_PATH_LIMIT)];
+            for line in path_lines(shown, total) {
+                println!("                {line}");
+            }
         }
         println!(
             "local_ref     = {} (ahead {}, behind {})",
@@ -97,6 +160,26 @@ impl StatusReport {
             "binary        = {} (exists={})",
             self.binary_path, self.binary_exists
         );
+        if let Some(version) = &self.binary_version {
+            println!("binary_version= {version}");
+        } else if let Some(err) = &self.binary_version_error {
+            println!("binary_version= unknown ({err})");
+        }
+    }
+
+    pub fn print_json(&self) -> Result<()> {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            #[serde(flatten)]
+            report: &'a StatusReport,
+            should_fail: bool,
+        }
+        let json = Json {
+            report: self,
+            should_fail: self.should_fail(),
+        };
+        println!("{}", serde_json::to_string_pretty(&json)?);
+        Ok(())
     }
 
     pub fn should_fail(&self) -> bool {
@@ -104,11 +187,31 @@ impl StatusReport {
     }
 }
 
+/// `paths` one per line, followed by "+K more" when `total` exceeds them.
+fn path_lines(paths: &[String], total: usize) -> Vec<String> {
+    let mut lines: Vec<String> = paths.to_vec();
+    if total > paths.len() {
+        lines.push(format!("+{} more", total - paths.len()));
+    }
+    lines
+}
+
+fn binary_version(binary: &Path) -> Result<String> {
+    let output = Command::new(binary)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("running {} --version", binary.display()))?;
+    if !output.status.success() {
+        bail!("--version exited with {}", output.status);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 fn divergence(repo: &Path, base: &str, other: &str) -> Result<(u32, u32)> {
     match git::divergence(repo, base, other) {
         Ok(v) => Ok(v),
         Err(err) => {
-            println!("warning: unable to compute divergence for {other}: {err:#}");
+            eprintln!("warning: unable to compute divergence for {other}: {err:#}");
             Ok((0, 0))
         }
     }
@@ -116,7 +219,7 @@ fn divergence(repo: &Path, base: &str, other: &str) -> Result<(u32, u32)> {
 
 #[cfg(test)]
 mod tests {
-    use super::StatusReport;
+    use super::{path_lines, StatusReport};
 
     #[test]
     fn status_report_flags_failures() {
@@ -129,17 +232,29 @@ mod tests {
         assert!(report.should_fail());
     }
 
+    #[test]
+    fn path_lines_notes_truncation() {
+        let paths = vec!["a.rs".to_string(), "b.rs".to_string()];
+        assert_eq!(path_lines(&paths, 2), vec!["a.rs", "b.rs"]);
+        assert_eq!(path_lines(&paths, 5), vec!["a.rs", "b.rs", "+3 more"]);
+    }
+
     fn sample_report() -> StatusReport {
         StatusReport {
             workspace_root: ".".into(),
             repo: "vendor/codex".into(),
             build_dir: "vendor/codex/codex-rs".into(),
             branch: "main".into(),
+            detached: false,
             head: "deadbeef".into(),
             clean: true,
             has_conflicts: false,
             tracked: 0,
+            staged: 0,
             untracked: 0,
+            conflicted_paths: Vec::new(),
+            dirty_paths: Vec::new(),
+            would_clean: Vec::new(),
             local_ref: "origin/main".into(),
             upstream_ref: "upstream/main".into(),
             local_ahead: 0,
@@ -148,6 +263,8 @@ mod tests {
             upstream_behind: 0,
             binary_path: "codex-rs/target/release/codex".into(),
             binary_exists: true,
+            binary_version: None,
+            binary_version_error: None,
         }
     }
 }
@@ -1,11 +1,31 @@
 use std::collections::BTreeSet;
+use std::path::Path;
 
 use anyhow::{bail, Context, Result};
+use codex_core::fs_config::ForksmithConfig;
 
-use crate::fs_config::ForksmithConfig;
 use crate::git;
 
-pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    pub dry_run: bool,
+    /// Rebase local commits onto upstream when a fast-forward is impossible.
+    pub rebase: bool,
+    /// Merge upstream when a fast-forward is impossible, leaving conflicts
+    /// in the tree for the user to resolve.
+    pub merge: bool,
+    /// Fetch only this remote instead of both the local and upstream remotes.
+    pub remote: Option<String>,
+    /// Delete remote-tracking refs whose upstream branch is gone.
+    pub prune: bool,
+}
+
+pub fn run(cfg: &ForksmithConfig, opts: &SyncOptions) -> Result<()> {
+    let dry_run = opts.dry_run;
+    if opts.rebase && opts.merge {
+        bail!("--rebase and --merge are mutually exclusive");
+    }
+    let rebase = !opts.merge && (opts.rebase || cfg.sync_rebase);
     let repo = &cfg.repo_path;
     git::ensure_repo(repo)?;
     let clean = git::is_clean(repo)?;
@@ -19,10 +39,35 @@ pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
         println!("(dry-run) repo has local changes; would require a clean tree before syncing");
     }
     let mut fetched = BTreeSet::new();
-    for remote in [&cfg.local_remote, &cfg.upstream_remote] {
+    let mut pruned = 0;
+    let remotes = match &opts.remote {
+        Some(remote) => {
+            if !git::has_remote(repo, remote)? {
+                bail!("unknown remote {remote} in {}", repo.display());
+            }
+            vec![remote]
+        }
+        None => vec![&cfg.local_remote, &cfg.upstream_remote],
+    };
+    for remote in remotes {
         if git::has_remote(repo, remote)? {
             println!("fetching {remote}...");
-            git::fetch(repo, remote).with_context(|| format!("fetching {remote}"))?;
+            if opts.prune && dry_run {
+                let stale = git::prunable_refs(repo, remote)
+                    .with_context(|| format!("checking stale refs of {remote}"))?;
+                println!("(dry-run) would prune {stale} stale ref(s) of {remote}");
+                pruned += stale;
+                git::fetch(repo, remote).with_context(|| format!("fetching {remote}"))?;
+            } else if opts.prune {
+                let count =
+                    git::fetch_prune(repo, remote).with_context(|| format!("fetching {remote}"))?;
+                if count > 0 {
+                    println!("pruned {count} stale ref(s) of {remote}");
+                }
+                pruned += count;
+            } else {
+                git::fetch(repo, remote).with_context(|| format!("fetching {remote}"))?;
+            }
             fetched.insert(remote.to_string());
         } else {
             println!("remote {remote} missing; skipping fetch");
@@ -32,11 +77,44 @@ pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
     let branch = git::current_branch(repo)?;
     let upstream_ref = format!("{}/{}", cfg.upstream_remote, cfg.upstream_branch);
     let local_ref = format!("{}/{}", cfg.local_remote, cfg.local_branch);
+    ensure_remote_ref(repo, "upstream", &cfg.upstream_remote, &cfg.upstream_branch)?;
+    ensure_remote_ref(repo, "local", &cfg.local_remote, &cfg.local_branch)?;
     println!("current branch: {branch}");
 
-    let (_, behind_upstream) = git::divergence(repo, "HEAD", &upstream_ref)?;
+    let (ahead_upstream, behind_upstream) = git::divergence(repo, "HEAD", &upstream_ref)?;
     let mut ff_applied = false;
-    if behind_upstream > 0 {
+    let mut rebased = false;
+    let mut merge_applied = false;
+    let mut conflicts = Vec::new();
+    if behind_upstream > 0 && ahead_upstream > 0 && opts.merge {
+        if dry_run {
+            println!(
+                "(dry-run) would merge {upstream_ref} (+{behind_upstream}) into {ahead_upstream} local commit(s)"
+            );
+        } else {
+            println!("merging {upstream_ref} ({behind_upstream} commits)...");
+            if let Err(err) = git::merge(repo, &upstream_ref) {
+                conflicts = git::unmerged_paths(repo)?;
+                if conflicts.is_empty() {
+                    return Err(err.context(format!("merging {upstream_ref} failed")));
+                }
+            } else {
+                merge_applied = true;
+            }
+        }
+    } else if behind_upstream > 0 && ahead_upstream > 0 && rebase {
+        if dry_run {
+            println!(
+                "(dry-run) would rebase {ahead_upstream} local commit(s) onto {upstream_ref} (+{behind_upstream})"
+            );
+        } else {
+            println!(
+                "rebasing {ahead_upstream} local commit(s) onto {upstream_ref} ({behind_upstream} commits)..."
+            );
+            rebase_onto(repo, &upstream_ref)?;
+            rebased = true;
+        }
+    } else if behind_upstream > 0 {
         if dry_run {
             println!("(dry-run) would fast-forward to {upstream_ref} (+{behind_upstream})");
         } else {
@@ -48,6 +126,25 @@ pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
         println!("already up to date with {upstream_ref}");
     }
 
+    if !conflicts.is_empty() {
+        println!(
+            "merge of {upstream_ref} left {} conflicted path(s):",
+            conflicts.len()
+        );
+        for path in &conflicts {
+            println!("  {path}");
+        }
+        println!(
+            "SYNC_RESULT dry_run={} fetched={} pruned={} ff_applied=false rebased=false merge_applied=false conflicts={} behind_upstream={}",
+            dry_run,
+            fetched.into_iter().collect::<Vec<_>>().join(","),
+            pruned,
+            conflicts.len(),
+            behind_upstream
+        );
+        bail!("resolve the conflicts and commit, or run `git merge --abort`");
+    }
+
     let (ahead_local, behind_local) = git::divergence(repo, "HEAD", &local_ref)?;
     if behind_local > 0 {
         println!("local remote {local_ref} is ahead by {behind_local} commit(s); push soon");
@@ -55,20 +152,105 @@ pub fn run(cfg: &ForksmithConfig, dry_run: bool) -> Result<()> {
         println!("local remote {local_ref} matches or lags HEAD");
     }
 
-    if !dry_run && ahead_local > 0 {
+    if rebased && behind_local > 0 {
+        println!(
+            "history was rewritten by the rebase; push to {local_ref} with `git push --force-with-lease`"
+        );
+    } else if !dry_run && ahead_local > 0 {
         println!("pushing HEAD to {local_ref} ({ahead_local} commit(s))...");
         git::push(repo, &cfg.local_remote, &cfg.local_branch)?;
     }
 
-    let upstream_behind_after = if ff_applied { 0 } else { behind_upstream };
+    let upstream_behind_after = if ff_applied || rebased || merge_applied {
+        0
+    } else {
+        behind_upstream
+    };
     println!(
-        "SYNC_RESULT dry_run={} fetched={} ff_applied={} ahead_local={} behind_local={} behind_upstream={}",
+        "SYNC_RESULT dry_run={} fetched={} pruned={} ff_applied={} rebased={} merge_applied={} conflicts=0 ahead_local={} behind_local={} behind_upstream={}",
         dry_run,
         fetched.into_iter().collect::<Vec<_>>().join(","),
+        pruned,
         ff_applied,
+        rebased,
+        merge_applied,
         ahead_local,
         behind_local,
         upstream_behind_after
     );
     Ok(())
 }
+
+/// Fails with the remote's known branches (and the closest one) when
+/// `<remote>/<branch>` does not resolve.
+fn ensure_remote_ref(repo: &Path, role: &str, remote: &str, branch: &str) -> Result<()> {
+    let reference = format!("{remote}/{branch}");
+    if git::ref_exists(repo, &reference)? {
+        return Ok(());
+    }
+    if !git::has_remote(repo, remote)? {
+        bail!("{role} ref {reference} not found; remote {remote} is not configured");
+    }
+    let branches = git::remote_branches(repo, remote)?;
+    if branches.is_empty() {
+        bail!("{role} ref {reference} not found; {remote} has no fetched branches");
+    }
+    let hint = closest_branch(branch, &branches)
+        .map(|name| format!("did you mean {remote}/{name}? "))
+        .unwrap_or_default();
+    bail!(
+        "{role} ref {reference} not found; {hint}available branches on {remote}: {}",
+        branches.join(", ")
+    );
+}
+
+/// The candidate within edit distance 2 of `branch`, if any.
+fn closest_branch<'a>(branch: &str, candidates: &'a [String]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|name| (edit_distance(branch, name), name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name.as_str())
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            row[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+fn rebase_onto(repo: &Path, upstream_ref: &str) -> Result<()> {
+    if let Err(err) = git::rebase(repo, upstream_ref) {
+        let conflicts = git::unmerged_paths(repo).unwrap_or_default();
+        git::rebase_abort(repo).context("aborting failed rebase")?;
+        if conflicts.is_empty() {
+            return Err(err.context(format!("rebase onto {upstream_ref} failed; aborted")));
+        }
+        bail!(
+            "rebase onto {upstream_ref} hit conflicts in {}; aborted and left the branch unchanged",
+            conflicts.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::closest_branch;
+
+    #[test]
+    fn closest_branch_suggests_typo_fix() {
+        let branches = vec!["main".to_string(), "release".to_string()];
+        assert_eq!(closest_branch("mian", &branches), Some("main"));
+        assert_eq!(closest_branch("feature-x", &branches), None);
+    }
+}
@@ -7,10 +7,32 @@ use std::{
 
 #[derive(Debug, Deserialize, Default)]
 pub struct VendorSection {
+    #[serde(default)]
+    pub id: Option<String>,
     #[serde(default)]
     pub root: Option<String>,
     #[serde(default)]
     pub branch: Option<String>,
+    #[serde(default)]
+    pub rev: Option<String>,
+    /// Registry file for this vendor; defaults to `[patch_registry] path` for
+    /// the first vendor and `patch-registry/<id>.json` for the rest.
+    #[serde(default)]
+    pub registry: Option<String>,
+}
+
+/// `[vendor]` (one table) or `[[vendor]]` (an array of them).
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum VendorSections {
+    One(VendorSection),
+    Many(Vec<VendorSection>),
+}
+
+impl Default for VendorSections {
+    fn default() -> Self {
+        Self::One(VendorSection::default())
+    }
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -48,25 +70,87 @@ pub struct ForkSection {
     #[serde(default)]
     pub merge_strategy_option: Option<String>,
     #[serde(default)]
+    pub on_conflict_cmd: Option<String>,
+    #[serde(default)]
     pub silence_local_ahead_warning: Option<bool>,
 }
 
+#[derive(Debug, Deserialize, Default)]
+pub struct DevSection {
+    #[serde(default)]
+    pub watch_commands: Option<Vec<String>>,
+    #[serde(default)]
+    pub watch_ignore: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct LegacyPatchesSection {
+    #[serde(default)]
+    pub dir: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Default)]
+pub struct EnginesSection {
+    /// ast-grep binary to use instead of the one on `PATH`.
+    #[serde(default)]
+    pub ast_grep_bin: Option<String>,
+    /// coccinelle-for-rust binary to use instead of the one on `PATH`.
+    #[serde(default)]
+    pub coccinelle_bin: Option<String>,
+    /// Passed to every coccinelle rule as `--iso-file`.
+    #[serde(default)]
+    pub coccinelle_iso_file: Option<String>,
+    /// Passed to every coccinelle rule as `--macro-file`.
+    #[serde(default)]
+    pub coccinelle_macro_file: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Default)]
 pub struct RawConfig {
     #[serde(default)]
-    pub vendor: VendorSection,
+    pub vendor: VendorSections,
     #[serde(default)]
     pub patch_registry: PatchRegistrySection,
     #[serde(default)]
     pub fork: ForkSection,
+    #[serde(default)]
+    pub dev: DevSection,
+    #[serde(default)]
+    pub legacy_patches: LegacyPatchesSection,
+    #[serde(default)]
+    pub engines: EnginesSection,
+}
+
+/// One vendored repo. The first entry is the primary vendor: its settings
+/// populate the top-level `vendor_*` fields, and `[fork]` and legacy
+/// patches only apply to it.
+#[derive(Debug, Clone)]
+pub struct VendorConfig {
+    pub id: String,
+    pub root: String,
+    pub branch: String,
+    pub rev: Option<String>,
+    pub registry_path: String,
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
+    pub vendors: Vec<VendorConfig>,
     pub vendor_root: String,
     pub vendor_branch: String,
+    pub vendor_rev: Option<String>,
     pub patch_registry_path: String,
+    pub legacy_patches_dir: String,
     pub fork: ForkConfig,
+    pub dev: DevConfig,
+    /// `[engines] ast_grep_bin`; `AST_GREP_BIN` takes precedence.
+    pub ast_grep_bin: Option<String>,
+    /// `[engines] coccinelle_bin`; `COCCINELLE_BIN` takes precedence.
+    pub coccinelle_bin: Option<String>,
+    /// `[engines] coccinelle_iso_file` / `coccinelle_macro_file`, relative to
+    /// the workspace root.
+    pub coccinelle_iso_file: Option<String>,
+    pub coccinelle_macro_file: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -84,9 +168,18 @@ pub struct ForkConfig {
     pub auto_route_upstream: bool,
     pub merge_strategy: Option<String>,
     pub merge_strategy_option: Option<String>,
+    /// Shell command run in the vendor repo when an auto-merge leaves
+    /// unmerged paths; it should resolve and `git add` them.
+    pub on_conflict_cmd: Option<String>,
     pub silence_local_ahead_warning: bool,
 }
 
+#[derive(Debug, Clone)]
+pub struct DevConfig {
+    pub watch_commands: Vec<String>,
+    pub watch_ignore: Vec<String>,
+}
+
 impl Config {
     pub fn load(root: &Path) -> Result<Self> {
         let path = root.join("codex-forksmith.toml");
@@ -95,27 +188,95 @@ impl Config {
         let raw: RawConfig =
             toml::from_str(&contents).with_context(|| "Failed to parse codex-forksmith.toml")?;
 
-        let vendor_root = raw
-            .vendor
-            .root
-            .unwrap_or_else(|| "vendor/codex".to_string());
-        let vendor_branch = raw.vendor.branch.unwrap_or_else(|| "main".to_string());
-
-        let patch_registry_path = raw
+        let sections = match raw.vendor {
+            VendorSections::One(section) => vec![section],
+            VendorSections::Many(sections) => sections,
+        };
+        if sections.is_empty() {
+            anyhow::bail!("codex-forksmith.toml needs at least one [[vendor]] entry");
+        }
+        let default_registry = raw
             .patch_registry
             .path
             .unwrap_or_else(|| "patch-registry/registry.json".to_string());
+        let mut vendors: Vec<VendorConfig> = Vec::with_capacity(sections.len());
+        for (idx, section) in sections.into_iter().enumerate() {
+            let root = match (section.root, idx) {
+                (Some(root), _) => root,
+                (None, 0) => "vendor/codex".to_string(),
+                (None, _) => anyhow::bail!("[[vendor]] entry {} is missing `root`", idx + 1),
+            };
+            let id = section.id.unwrap_or_else(|| {
+                Path::new(&root)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| root.clone())
+            });
+            if vendors.iter().any(|v| v.id == id) {
+                anyhow::bail!("duplicate vendor id {id:?} in codex-forksmith.toml");
+            }
+            let registry_path = section.registry.unwrap_or_else(|| {
+                if idx == 0 {
+                    default_registry.clone()
+                } else {
+                    format!("patch-registry/{id}.json")
+                }
+            });
+            vendors.push(VendorConfig {
+                id,
+                root,
+                branch: section.branch.unwrap_or_else(|| "main".to_string()),
+                rev: section.rev,
+                registry_path,
+            });
+        }
+        let primary = vendors[0].clone();
+        let vendor_root = primary.root;
+        let vendor_branch = primary.branch;
+        let patch_registry_path = primary.registry_path;
+
+        let legacy_patches_dir = raw
+            .legacy_patches
+            .dir
+            .unwrap_or_else(|| "legacy-patches".to_string());
 
         let fork = ForkConfig::from_section(&raw.fork, &vendor_branch);
+        let dev = DevConfig::from_section(&raw.dev);
 
         Ok(Config {
+            vendors,
             vendor_root,
             vendor_branch,
+            vendor_rev: primary.rev,
             patch_registry_path,
+            legacy_patches_dir,
             fork,
+            dev,
+            ast_grep_bin: raw.engines.ast_grep_bin,
+            coccinelle_bin: raw.engines.coccinelle_bin,
+            coccinelle_iso_file: raw.engines.coccinelle_iso_file,
+            coccinelle_macro_file: raw.engines.coccinelle_macro_file,
         })
     }
 
+    /// This config with the `vendor_*` fields pointed at `vendor`; `[fork]` is
+    /// turned off unless `vendor` is the primary one.
+    pub fn for_vendor(&self, vendor: &VendorConfig) -> Config {
+        let mut cfg = self.clone();
+        cfg.vendor_root = vendor.root.clone();
+        cfg.vendor_branch = vendor.branch.clone();
+        cfg.vendor_rev = vendor.rev.clone();
+        cfg.patch_registry_path = vendor.registry_path.clone();
+        if !self.is_primary(vendor) {
+            cfg.fork.enabled = false;
+        }
+        cfg
+    }
+
+    pub fn is_primary(&self, vendor: &VendorConfig) -> bool {
+        self.vendors.first().is_some_and(|v| v.id == vendor.id)
+    }
+
     pub fn vendor_dir(&self, root: &Path) -> PathBuf {
         root.join(&self.vendor_root)
     }
@@ -123,6 +284,10 @@ impl Config {
     pub fn registry_path(&self, root: &Path) -> PathBuf {
         root.join(&self.patch_registry_path)
     }
+
+    pub fn legacy_patches_dir(&self, root: &Path) -> PathBuf {
+        root.join(&self.legacy_patches_dir)
+    }
 }
 
 impl ForkConfig {
@@ -153,7 +318,56 @@ impl ForkConfig {
             auto_route_upstream: section.auto_route_upstream.unwrap_or(false),
             merge_strategy: section.merge_strategy.clone(),
             merge_strategy_option: section.merge_strategy_option.clone(),
+            on_conflict_cmd: section.on_conflict_cmd.clone(),
             silence_local_ahead_warning: section.silence_local_ahead_warning.unwrap_or(false),
         }
     }
 }
+
+impl DevConfig {
+    fn from_section(section: &DevSection) -> Self {
+        Self {
+            watch_commands: section.watch_commands.clone().unwrap_or_else(|| {
+                vec![
+                    "cargo fmt".to_string(),
+                    "cargo clippy --all-targets --all-features -- -D warnings".to_string(),
+                ]
+            }),
+            watch_ignore: section.watch_ignore.clone().unwrap_or_default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+
+    fn load(toml: &str) -> Config {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("codex-forksmith.toml"), toml).unwrap();
+        Config::load(tmp.path()).unwrap()
+    }
+
+    #[test]
+    fn single_vendor_table_is_one_element_list() {
+        let cfg = load("[vendor]\nroot = \"vendor/codex\"\nbranch = \"dev\"\n");
+        assert_eq!(cfg.vendors.len(), 1);
+        assert_eq!(cfg.vendors[0].id, "codex");
+        assert_eq!(cfg.vendor_branch, "dev");
+        assert_eq!(cfg.patch_registry_path, "patch-registry/registry.json");
+    }
+
+    #[test]
+    fn vendor_array_gets_per_vendor_registries() {
+        let cfg = load(
+            "[[vendor]]\nroot = \"vendor/codex\"\n\n\
+             [[vendor]]\nid = \"tool\"\nroot = \"vendor/tool\"\nbranch = \"trunk\"\n",
+        );
+        let ids: Vec<&str> = cfg.vendors.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(ids, ["codex", "tool"]);
+        let tool = cfg.for_vendor(&cfg.vendors[1]);
+        assert_eq!(tool.vendor_root, "vendor/tool");
+        assert_eq!(tool.vendor_branch, "trunk");
+        assert_eq!(tool.patch_registry_path, "patch-registry/tool.json");
+    }
+}
@@ -4,18 +4,25 @@ use std::sync::mpsc::{channel, Receiver};
 use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 use notify::{
     Config as NotifyConfig, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
 };
 use pathdiff::diff_paths;
 
+use crate::config::DevConfig;
+
 const DEBOUNCE_WINDOW: Duration = Duration::from_millis(400);
 const IGNORE_PREFIXES: &[&str] = &[".git", "target"];
 const IGNORE_VENDOR: &[&str] = &["vendor/codex"];
 
-pub fn run_watch(root: &Path) -> Result<()> {
-    println!("▶ starting auto fmt + lint watcher in {}", root.display());
-    run_fmt_and_clippy(root)?;
+pub fn run_watch(root: &Path, dev: &DevConfig) -> Result<()> {
+    if dev.watch_commands.is_empty() {
+        return Err(anyhow!("dev.watch_commands is empty; nothing to run"));
+    }
+    let ignore = build_ignore_set(&dev.watch_ignore)?;
+    println!("▶ starting watcher in {}", root.display());
+    run_watch_commands(root, &dev.watch_commands)?;
 
     let (tx, rx) = channel();
     let mut watcher = RecommendedWatcher::new(tx, NotifyConfig::default())
@@ -24,10 +31,27 @@ pub fn run_watch(root: &Path) -> Result<()> {
         .watch(root, RecursiveMode::Recursive)
         .with_context(|| format!("Failed to watch {}", root.display()))?;
 
-    event_loop(root.to_path_buf(), rx)
+    event_loop(root.to_path_buf(), &dev.watch_commands, &ignore, rx)
+}
+
+fn build_ignore_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern)
+            .with_context(|| format!("Invalid dev.watch_ignore glob {pattern:?}"))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .with_context(|| "Failed to compile dev.watch_ignore globs")
 }
 
-fn event_loop(root: PathBuf, rx: Receiver<Result<Event, notify::Error>>) -> Result<()> {
+fn event_loop(
+    root: PathBuf,
+    commands: &[String],
+    ignore: &GlobSet,
+    rx: Receiver<Result<Event, notify::Error>>,
+) -> Result<()> {
     let mut last_run = Instant::now();
     loop {
         let event = rx.recv().with_context(|| "Watcher channel disconnected")?;
@@ -39,7 +63,7 @@ fn event_loop(root: PathBuf, rx: Receiver<Result<Event, notify::Error>>) -> Resu
             }
         };
 
-        if !should_trigger(&root, &event) {
+        if !should_trigger(&root, ignore, &event) {
             continue;
         }
 
@@ -47,24 +71,30 @@ fn event_loop(root: PathBuf, rx: Receiver<Result<Event, notify::Error>>) -> Resu
             continue;
         }
         last_run = Instant::now();
-        if let Err(err) = run_fmt_and_clippy(&root) {
-            eprintln!("[watch] lint run failed: {err}");
+        if let Err(err) = run_watch_commands(&root, commands) {
+            eprintln!("[watch] command run failed: {err}");
         }
     }
 }
 
-fn should_trigger(root: &Path, event: &Event) -> bool {
+fn should_trigger(root: &Path, ignore: &GlobSet, event: &Event) -> bool {
     matches!(
         event.kind,
         EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_)
-    ) && event.paths.iter().any(|path| !is_ignored_path(root, path))
+    ) && event
+        .paths
+        .iter()
+        .any(|path| !is_ignored_path(root, ignore, path))
 }
 
-fn is_ignored_path(root: &Path, path: &Path) -> bool {
+fn is_ignored_path(root: &Path, ignore: &GlobSet, path: &Path) -> bool {
     if path.is_dir() {
         return true;
     }
     let rel = diff_paths(path, root).unwrap_or_else(|| path.to_path_buf());
+    if ignore.is_match(&rel) {
+        return true;
+    }
     let mut comps = rel.components();
     if let Some(Component::Normal(first)) = comps.next() {
         if IGNORE_PREFIXES
@@ -85,32 +115,23 @@ fn is_ignored_path(root: &Path, path: &Path) -> bool {
     false
 }
 
-fn run_fmt_and_clippy(root: &Path) -> Result<()> {
-    println!("▶ running cargo fmt");
-    run_cargo(&["fmt"], root)?;
-    println!("▶ running cargo clippy --all-targets --all-features -D warnings");
-    run_cargo(
-        &[
-            "clippy",
-            "--all-targets",
-            "--all-features",
-            "--",
-            "-D",
-            "warnings",
-        ],
-        root,
-    )
+fn run_watch_commands(root: &Path, commands: &[String]) -> Result<()> {
+    for command in commands {
+        println!("▶ running {command}");
+        run_shell(command, root)?;
+    }
+    Ok(())
 }
 
-fn run_cargo(args: &[&str], root: &Path) -> Result<()> {
-    let label = format!("cargo {}", args.join(" "));
-    let status = Command::new("cargo")
-        .args(args)
+fn run_shell(command: &str, root: &Path) -> Result<()> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
         .current_dir(root)
         .status()
-        .with_context(|| format!("Failed to spawn {label}"))?;
+        .with_context(|| format!("Failed to spawn {command}"))?;
     if !status.success() {
-        return Err(anyhow!("{label} failed with status {:?}", status.code()));
+        return Err(anyhow!("{command} failed with status {:?}", status.code()));
     }
     Ok(())
 }
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+use anyhow::Result;
+use camino::Utf8Path;
+use codex_ast_driver::{AstGrepDriver, AstMode, AstRunOutcome};
+
+use crate::config::Config;
+use crate::engines::{rule_path, utf8_path, EngineOutcome, EngineResult};
+use crate::registry::PatchSet;
+
+pub fn apply(
+    patch: &PatchSet,
+    cfg: &Config,
+    vendor_dir: &Path,
+    dry_run: bool,
+) -> Result<EngineResult> {
+    let vendor = utf8_path(vendor_dir)?;
+    let Some(driver) =
+        AstGrepDriver::detect_with(&vendor, cfg.ast_grep_bin.as_deref().map(Utf8Path::new))?
+    else {
+        anyhow::bail!("ast-grep binary not found; cannot apply {}", patch.id);
+    };
+    let mode = if dry_run {
+        AstMode::DryRun
+    } else {
+        AstMode::Apply
+    };
+
+    let mut matches = 0u32;
+    for rule in &patch.rules {
+        let config_path = utf8_path(&rule_path(vendor_dir, rule))?;
+        match driver.run_with_config(&config_path, &vendor, mode)? {
+            AstRunOutcome::Applied(summary) => {
+                matches += u32::try_from(summary.match_count()).unwrap_or(u32::MAX);
+            }
+            AstRunOutcome::NoMatches { .. } => {}
+            AstRunOutcome::Skipped { reason } => {
+                return Ok(EngineResult::new(EngineOutcome::Skipped { reason }));
+            }
+        }
+    }
+
+    Ok(EngineResult::new(EngineOutcome::from_matches(
+        matches, dry_run,
+    )))
+}
@@ -0,0 +1,61 @@
+use std::path::Path;
+
+use anyhow::Result;
+use camino::{Utf8Path, Utf8PathBuf};
+use codex_cocci_driver::{CocciDriver, CocciStatus};
+
+use crate::config::Config;
+use crate::engines::{rule_path, utf8_path, EngineOutcome, EngineResult};
+use crate::registry::PatchSet;
+
+pub fn apply(
+    patch: &PatchSet,
+    cfg: &Config,
+    vendor_dir: &Path,
+    dry_run: bool,
+) -> Result<EngineResult> {
+    if dry_run {
+        return Ok(EngineResult::new(EngineOutcome::Skipped {
+            reason: "coccinelle has no dry-run mode".to_string(),
+        }));
+    }
+    let vendor = utf8_path(vendor_dir)?;
+    let Some(driver) =
+        CocciDriver::detect_with(&vendor, cfg.coccinelle_bin.as_deref().map(Utf8Path::new))?
+    else {
+        anyhow::bail!(
+            "coccinelle-for-rust binary not found; cannot apply {}",
+            patch.id
+        );
+    };
+    let include = |file: &Option<String>| -> Result<Option<Utf8PathBuf>> {
+        file.as_deref()
+            .map(|file| utf8_path(&rule_path(vendor_dir, file)))
+            .transpose()
+    };
+    let driver = driver
+        .with_iso_file(include(&cfg.coccinelle_iso_file)?)
+        .with_macro_file(include(&cfg.coccinelle_macro_file)?);
+    for missing in driver.missing_includes() {
+        eprintln!("warning: coccinelle include {missing} missing; running rules without it");
+    }
+
+    let mut applied = 0u32;
+    for rule in &patch.rules {
+        let rule = utf8_path(&rule_path(vendor_dir, rule))?;
+        let report = driver.run_rule(&rule, &vendor)?;
+        match report.status {
+            CocciStatus::Applied => applied += 1,
+            CocciStatus::NoChange => {}
+            CocciStatus::Error => anyhow::bail!(
+                "coccinelle rule {} failed (exit {:?}): {}",
+                report.rule,
+                report.exit_code,
+                report.stderr.trim()
+            ),
+        }
+    }
+    Ok(EngineResult::new(EngineOutcome::from_matches(
+        applied, false,
+    )))
+}
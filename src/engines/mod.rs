@@ -1,13 +1,100 @@
-use std::path::Path;
+use std::fmt;
+use std::path::{Path, PathBuf};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use camino::Utf8PathBuf;
 
 use crate::config::Config;
-use crate::registry::PatchSet;
+use crate::registry::{EngineKind, PatchSet};
 
 pub struct EngineResult {
-    pub matches: Option<u32>,
-    pub status: String,
+    pub outcome: EngineOutcome,
+    /// Shown after the rendered outcome, e.g. the fuzzy strategy a patch
+    /// needed.
+    pub detail: Option<String>,
+    pub hunks: Option<u32>,
+    /// Lines (added, removed), as reported by `git apply --numstat`.
+    pub lines_changed: Option<(u32, u32)>,
+}
+
+impl EngineResult {
+    fn new(outcome: EngineOutcome) -> Self {
+        Self {
+            outcome,
+            detail: None,
+            hunks: None,
+            lines_changed: None,
+        }
+    }
+
+    /// The outcome plus any detail, as shown in run summaries.
+    pub fn status(&self) -> String {
+        match &self.detail {
+            Some(detail) => format!("{} ({detail})", self.outcome),
+            None => self.outcome.to_string(),
+        }
+    }
+}
+
+/// What running a patch set's engine did to the vendor tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EngineOutcome {
+    Applied {
+        matches: u32,
+    },
+    NoMatches,
+    DryRun {
+        matches: u32,
+    },
+    /// Every rule was already present (patch engine only).
+    AlreadyApplied,
+    /// The set was undone by `unapply`.
+    Reversed {
+        hunks: u32,
+    },
+    /// The engine did not run, e.g. no dry-run support.
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+impl EngineOutcome {
+    /// `Applied` or `DryRun`, or `NoMatches` when nothing matched.
+    pub fn from_matches(matches: u32, dry_run: bool) -> Self {
+        match (matches, dry_run) {
+            (0, _) => Self::NoMatches,
+            (matches, true) => Self::DryRun { matches },
+            (matches, false) => Self::Applied { matches },
+        }
+    }
+
+    /// Match count recorded in the registry; `None` when the engine did not
+    /// get as far as matching.
+    pub fn matches(&self) -> Option<u32> {
+        match self {
+            Self::Applied { matches } | Self::DryRun { matches } => Some(*matches),
+            Self::NoMatches | Self::AlreadyApplied => Some(0),
+            Self::Reversed { hunks } => Some(*hunks),
+            Self::Skipped { .. } | Self::Failed { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for EngineOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Applied { matches } => write!(f, "applied: {matches} matches"),
+            Self::NoMatches => f.write_str("no-matches"),
+            Self::DryRun { matches } => write!(f, "dry-run: {matches} matches"),
+            Self::AlreadyApplied => f.write_str("already-applied"),
+            Self::Reversed { hunks } => write!(f, "reversed: {hunks} hunks"),
+            Self::Skipped { reason } => write!(f, "skipped: {reason}"),
+            Self::Failed { error } => write!(f, "failed: {error}"),
+        }
+    }
 }
 
 pub fn apply_patchset(
@@ -15,8 +102,45 @@ pub fn apply_patchset(
     cfg: &Config,
     vendor_dir: &Path,
     dry_run: bool,
+    reverse: bool,
 ) -> Result<EngineResult> {
-    patch::apply(patch, cfg, vendor_dir, dry_run)
+    if reverse {
+        return match patch.engine {
+            EngineKind::Patch => patch::unapply(patch, cfg, vendor_dir, dry_run),
+            ref other => Err(anyhow!(
+                "{} uses the {other:?} engine, which cannot be unapplied; reset the vendor tree instead",
+                patch.id
+            )),
+        };
+    }
+    match patch.engine {
+        EngineKind::Patch => patch::apply(patch, cfg, vendor_dir, dry_run),
+        EngineKind::AstGrep => ast_grep::apply(patch, cfg, vendor_dir, dry_run),
+        EngineKind::Coccinelle => coccinelle::apply(patch, cfg, vendor_dir, dry_run),
+        EngineKind::GritQl => Ok(EngineResult::new(EngineOutcome::Skipped {
+            reason: "gritql is not supported by the legacy engine".to_string(),
+        })),
+    }
+}
+
+/// Resolves a registry rule path; relative rules live under the workspace root.
+pub(crate) fn rule_path(vendor_dir: &Path, rule: &str) -> PathBuf {
+    let path = PathBuf::from(rule);
+    if path.is_absolute() {
+        return path;
+    }
+    let workspace_root = vendor_dir
+        .parent()
+        .and_then(|p| p.parent())
+        .unwrap_or(vendor_dir);
+    workspace_root.join(path)
+}
+
+pub(crate) fn utf8_path(path: &Path) -> Result<Utf8PathBuf> {
+    Utf8PathBuf::from_path_buf(path.to_path_buf())
+        .map_err(|p| anyhow!("path {} is not valid UTF-8", p.display()))
 }
 
+pub mod ast_grep;
+pub mod coccinelle;
 pub mod patch;
@@ -1,12 +1,12 @@
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
 
 use anyhow::{Context, Result};
 
 use crate::config::Config;
-use crate::engines::EngineResult;
+use crate::engines::{rule_path, EngineOutcome, EngineResult};
 use crate::registry::PatchSet;
 
 pub fn apply(
@@ -15,64 +15,354 @@ pub fn apply(
     vendor_dir: &Path,
     dry_run: bool,
 ) -> Result<EngineResult> {
-    let workspace_root = vendor_dir
-        .parent()
-        .and_then(|p| p.parent())
-        .unwrap_or(vendor_dir);
-
     let mut applied = 0u32;
+    let mut already_applied = 0u32;
+    let mut hunks = 0u32;
+    let (mut added, mut removed) = (0u32, 0u32);
+    let mut fuzziest = ApplyStrategy::ThreeWay;
 
     for rule in &patch.rules {
-        let path = PathBuf::from(rule);
-        let patch_path = if path.is_absolute() {
-            path
-        } else {
-            workspace_root.join(path)
-        };
+        let patch_path = rule_path(vendor_dir, rule);
         let data = fs::read(&patch_path)
             .with_context(|| format!("failed to read patch {}", patch_path.display()))?;
 
-        let mut cmd = Command::new("git");
-        cmd.arg("apply")
-            .arg("--3way")
-            .arg("--allow-empty")
-            .arg("--whitespace=nowarn")
-            .current_dir(vendor_dir)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped());
-        if dry_run {
-            cmd.arg("--check");
+        let reverse_check = git_apply(vendor_dir, &data, &["--reverse", "--check"])
+            .with_context(|| format!("spawning git apply for {}", patch_path.display()))?;
+        if reverse_check.status.success() {
+            already_applied += 1;
+            continue;
         }
 
-        let mut child = cmd
-            .spawn()
-            .with_context(|| format!("spawning git apply for {}", patch_path.display()))?;
+        let strategy = match apply_with_fallback(vendor_dir, &data, dry_run)
+            .with_context(|| format!("applying {}", patch_path.display()))?
         {
-            let stdin = child
-                .stdin
-                .as_mut()
-                .context("patch runner failed to open stdin")?;
-            stdin.write_all(&data)?;
-        }
-        let output = child.wait_with_output()?;
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            anyhow::bail!(
-                "git apply failed for {}: {}",
+            Ok(strategy) => strategy,
+            Err(stderr) => anyhow::bail!("git apply failed for {}: {stderr}", patch_path.display()),
+        };
+        if strategy != ApplyStrategy::ThreeWay {
+            eprintln!(
+                "  warning: {} did not apply cleanly; used {}",
                 patch_path.display(),
-                stderr.trim()
+                strategy.describe()
             );
         }
+        fuzziest = fuzziest.max(strategy);
+        let (rule_added, rule_removed) = numstat(vendor_dir, &data)?;
+        added += rule_added;
+        removed += rule_removed;
+        hunks += count_hunks(&data);
         applied += 1;
     }
 
+    let outcome = if applied == 0 && already_applied > 0 {
+        EngineOutcome::AlreadyApplied
+    } else {
+        EngineOutcome::from_matches(applied, dry_run)
+    };
+    Ok(EngineResult {
+        outcome,
+        detail: (fuzziest != ApplyStrategy::ThreeWay)
+            .then(|| format!("via {}", fuzziest.describe())),
+        hunks: Some(hunks),
+        lines_changed: Some((added, removed)),
+    })
+}
+
+pub fn unapply(
+    patch: &PatchSet,
+    _cfg: &Config,
+    vendor_dir: &Path,
+    dry_run: bool,
+) -> Result<EngineResult> {
+    let mut patches = Vec::with_capacity(patch.rules.len());
+    let mut not_applied = Vec::new();
+    for rule in &patch.rules {
+        let patch_path = rule_path(vendor_dir, rule);
+        let data = fs::read(&patch_path)
+            .with_context(|| format!("failed to read patch {}", patch_path.display()))?;
+        let check = git_apply(vendor_dir, &data, &["--reverse", "--check"])
+            .with_context(|| format!("spawning git apply for {}", patch_path.display()))?;
+        if !check.status.success() {
+            not_applied.push(rule.as_str());
+        }
+        patches.push((patch_path, data));
+    }
+    if !not_applied.is_empty() {
+        anyhow::bail!(
+            "{} is not currently applied (cannot reverse {}); vendor tree left untouched",
+            patch.id,
+            not_applied.join(", ")
+        );
+    }
+
+    let mut hunks = 0u32;
+    let (mut added, mut removed) = (0u32, 0u32);
+    for (patch_path, data) in &patches {
+        if !dry_run {
+            let output = git_apply(vendor_dir, data, &["--reverse"])
+                .with_context(|| format!("spawning git apply for {}", patch_path.display()))?;
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                anyhow::bail!(
+                    "git apply --reverse failed for {}: {}",
+                    patch_path.display(),
+                    stderr.trim()
+                );
+            }
+        }
+        let (rule_added, rule_removed) = numstat(vendor_dir, data)?;
+        added += rule_added;
+        removed += rule_removed;
+        hunks += count_hunks(data);
+    }
+
     Ok(EngineResult {
-        matches: Some(applied),
-        status: if dry_run {
-            "dry-run".to_string()
+        outcome: if dry_run {
+            EngineOutcome::DryRun { matches: hunks }
         } else {
-            "applied".to_string()
+            EngineOutcome::Reversed { hunks }
         },
+        detail: None,
+        hunks: Some(hunks),
+        // Reversing swaps the direction of the patch's own numstat.
+        lines_changed: Some((removed, added)),
     })
 }
+
+/// How a patch finally applied, from strictest to fuzziest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum ApplyStrategy {
+    ThreeWay,
+    ReducedContext,
+    Fuzz,
+}
+
+impl ApplyStrategy {
+    pub(crate) fn describe(self) -> &'static str {
+        match self {
+            Self::ThreeWay => "git apply --3way",
+            Self::ReducedContext => "git apply --3way -C1",
+            Self::Fuzz => "patch -p1 --fuzz=3",
+        }
+    }
+}
+
+/// Applies `data` with `git apply --3way`, and only if that fails retries
+/// with one line of context and then `patch --fuzz=3`. A failed attempt that
+/// left merge conflicts in the tree is not retried. The inner `Err` carries
+/// the output of every attempt.
+pub(crate) fn apply_with_fallback(
+    vendor_dir: &Path,
+    data: &[u8],
+    dry_run: bool,
+) -> Result<std::result::Result<ApplyStrategy, String>> {
+    let mut failures = Vec::new();
+    for (strategy, args) in [
+        (ApplyStrategy::ThreeWay, vec!["--3way"]),
+        (ApplyStrategy::ReducedContext, vec!["--3way", "-C1"]),
+    ] {
+        let mut args = args;
+        if dry_run {
+            args.push("--check");
+        }
+        let output = git_apply(vendor_dir, data, &args).context("spawning git apply")?;
+        if output.status.success() {
+            return Ok(Ok(strategy));
+        }
+        failures.push(format!(
+            "{}: {}",
+            strategy.describe(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+        if has_conflicts(vendor_dir)? {
+            return Ok(Err(failures.join("\n")));
+        }
+    }
+
+    let mut command = Command::new("patch");
+    command.args([
+        "-p1",
+        "--fuzz=3",
+        "--forward",
+        "--batch",
+        "--no-backup-if-mismatch",
+        "--reject-file=-",
+    ]);
+    if dry_run {
+        command.arg("--dry-run");
+    }
+    match run_with_stdin(command.current_dir(vendor_dir), data) {
+        Ok(output) if output.status.success() => return Ok(Ok(ApplyStrategy::Fuzz)),
+        Ok(output) => failures.push(format!(
+            "{}: {}",
+            ApplyStrategy::Fuzz.describe(),
+            String::from_utf8_lossy(&output.stdout).trim()
+        )),
+        Err(err) => failures.push(format!("{}: {err:#}", ApplyStrategy::Fuzz.describe())),
+    }
+    Ok(Err(failures.join("\n")))
+}
+
+fn has_conflicts(vendor_dir: &Path) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["diff", "--name-only", "--diff-filter=U"])
+        .current_dir(vendor_dir)
+        .output()
+        .context("listing unmerged paths")?;
+    Ok(!output.stdout.is_empty())
+}
+
+fn count_hunks(data: &[u8]) -> u32 {
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter(|line| line.starts_with("@@ "))
+        .count() as u32
+}
+
+/// Sums `git apply --numstat` (added, removed) lines across the patch's files.
+fn numstat(vendor_dir: &Path, data: &[u8]) -> Result<(u32, u32)> {
+    let output =
+        git_apply(vendor_dir, data, &["--numstat"]).context("running git apply --numstat")?;
+    let (mut added, mut removed) = (0u32, 0u32);
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut cols = line.split('\t');
+        // Binary files report "-" for both columns.
+        added += cols.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        removed += cols.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+    }
+    Ok((added, removed))
+}
+
+fn git_apply(vendor_dir: &Path, data: &[u8], args: &[&str]) -> Result<Output> {
+    let mut command = Command::new("git");
+    command
+        .arg("apply")
+        .args(args)
+        .arg("--allow-empty")
+        .arg("--whitespace=nowarn")
+        .current_dir(vendor_dir);
+    run_with_stdin(&mut command, data)
+}
+
+fn run_with_stdin(command: &mut Command, data: &[u8]) -> Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    {
+        let stdin = child
+            .stdin
+            .as_mut()
+            .context("patch runner failed to open stdin")?;
+        stdin.write_all(data)?;
+    }
+    Ok(child.wait_with_output()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::apply;
+    use crate::config::Config;
+    use crate::engines::EngineOutcome;
+    use crate::registry::{EngineKind, PatchSet};
+
+    const PATCH: &str = "\
+diff --git a/hello.txt b/hello.txt
+--- a/hello.txt
++++ b/hello.txt
+@@ -1 +1 @@
+-hello
++hello, forksmith
+";
+
+    const DRIFTED_PATCH: &str = "\
+diff --git a/hello.txt b/hello.txt
+--- a/hello.txt
++++ b/hello.txt
+@@ -1,4 +1,4 @@
+ one
+ two
+-hello
++hello, forksmith
+ three
+";
+
+    #[test]
+    fn second_pass_reports_already_applied() {
+        let root = tempfile::tempdir().unwrap();
+        let vendor = root.path().join("vendor/codex");
+        fs::create_dir_all(&vendor).unwrap();
+        fs::write(root.path().join("codex-forksmith.toml"), "").unwrap();
+        fs::write(root.path().join("hello.patch"), PATCH).unwrap();
+        fs::write(vendor.join("hello.txt"), "hello\n").unwrap();
+        git(&vendor, &["init", "-q"]);
+        git(&vendor, &["add", "."]);
+        git(&vendor, &["commit", "-qm", "init"]);
+
+        let cfg = Config::load(root.path()).unwrap();
+        let set = sample_set("hello.patch");
+
+        let first = apply(&set, &cfg, &vendor, false).unwrap();
+        assert_eq!(first.outcome, EngineOutcome::Applied { matches: 1 });
+        assert_eq!(first.status(), "applied: 1 matches");
+        assert_eq!(first.hunks, Some(1));
+        assert_eq!(first.lines_changed, Some((1, 1)));
+
+        let second = apply(&set, &cfg, &vendor, false).unwrap();
+        assert_eq!(second.outcome, EngineOutcome::AlreadyApplied);
+        assert_eq!(second.outcome.matches(), Some(0));
+    }
+
+    #[test]
+    fn drifted_context_falls_back_to_reduced_context() {
+        let root = tempfile::tempdir().unwrap();
+        let vendor = root.path().join("vendor/codex");
+        fs::create_dir_all(&vendor).unwrap();
+        fs::write(root.path().join("codex-forksmith.toml"), "").unwrap();
+        fs::write(root.path().join("hello.patch"), DRIFTED_PATCH).unwrap();
+        fs::write(vendor.join("hello.txt"), "ONE\ntwo\nhello\nthree\n").unwrap();
+        git(&vendor, &["init", "-q"]);
+        git(&vendor, &["add", "."]);
+        git(&vendor, &["commit", "-qm", "init"]);
+
+        let cfg = Config::load(root.path()).unwrap();
+        let result = apply(&sample_set("hello.patch"), &cfg, &vendor, false).unwrap();
+        assert_eq!(
+            result.status(),
+            "applied: 1 matches (via git apply --3way -C1)"
+        );
+        let patched = fs::read_to_string(vendor.join("hello.txt")).unwrap();
+        assert_eq!(patched, "ONE\ntwo\nhello, forksmith\nthree\n");
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    fn sample_set(rule: &str) -> PatchSet {
+        PatchSet {
+            id: "patch:hello".into(),
+            description: "hello".into(),
+            engine: EngineKind::Patch,
+            enabled: true,
+            rules: vec![rule.into()],
+            tags: Vec::new(),
+            engine_confidence: None,
+            last_applied_commit: None,
+            last_match_count: None,
+            last_status: None,
+            last_run_ts: None,
+        }
+    }
+}
@@ -1,14 +1,14 @@
 mod commands;
-mod fs_config;
 mod git;
 
 use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use clap::{error::ErrorKind, Parser, Subcommand};
 
-use commands::{build, run as run_cmd, status, sync};
-use fs_config::ForksmithConfig;
+use codex_core::fs_config::{self, ConfigOverrides, ForksmithConfig};
+use commands::{build, checkout, clean, diff, run as run_cmd, status, sync};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -17,6 +17,23 @@ use fs_config::ForksmithConfig;
     about = "Forksmith v2 control plane"
 )]
 struct Cli {
+    /// Config file to load (default: nearest codex-forksmith.toml in the cwd or a parent)
+    #[arg(
+        long = "forksmith-config",
+        global = true,
+        value_name = "PATH",
+        env = fs_config::CONFIG_ENV
+    )]
+    config: Option<PathBuf>,
+    /// Vendor repo to operate on instead of `[repo] path`
+    #[arg(long, global = true, value_name = "PATH")]
+    repo: Option<PathBuf>,
+    /// Local branch to use instead of `[repo] local_branch`
+    #[arg(long, global = true, value_name = "BRANCH")]
+    local_branch: Option<String>,
+    /// Upstream branch to use instead of `[repo] upstream_branch`
+    #[arg(long, global = true, value_name = "BRANCH")]
+    upstream_branch: Option<String>,
     /// Force the loader to run `codex status` instead of launching the binary
     #[arg(long = "loader-status", action = clap::ArgAction::SetTrue)]
     loader_status: bool,
@@ -43,17 +60,67 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Show git + fork status for vendor/codex
-    Status,
+    Status {
+        /// Print the report as JSON instead of the aligned table
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        json: bool,
+    },
     /// Fetch remotes and prep for merges
     Sync {
         /// Show what would happen without mutating the repo
         #[arg(long, action = clap::ArgAction::SetTrue)]
         dry_run: bool,
+        /// Rebase local commits onto upstream when fast-forward is impossible
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        rebase: bool,
+        /// Merge upstream when fast-forward is impossible; conflicts are left
+        /// in the tree
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "rebase")]
+        merge: bool,
+        /// Fetch only this remote (default: both local and upstream)
+        #[arg(long, value_name = "NAME")]
+        remote: Option<String>,
+        /// Delete remote-tracking refs for branches removed upstream
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        prune: bool,
     },
     /// Build codex inside vendor/codex
-    Build,
+    Build {
+        /// Print the cargo command and expected binary path without building
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        dry_run: bool,
+        /// Build in this directory (relative to the repo) instead of `[build] workspace`
+        #[arg(long, value_name = "RELPATH")]
+        build_dir: Option<PathBuf>,
+    },
+    /// Reset vendor/codex to a pristine checkout (preview unless --hard)
+    Clean {
+        /// Run git reset --hard and git clean -fdx instead of previewing
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        hard: bool,
+    },
+    /// Check out a tag or commit (detached HEAD) after ensuring a clean tree
+    Checkout {
+        /// Tag, commit, or branch to check out
+        rev: String,
+    },
+    /// Show the fork's changes relative to the upstream branch
+    Diff {
+        /// Print a diffstat summary instead of the full patch
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        stat: bool,
+    },
     /// Run the codex binary with passthrough args
     Run {
+        /// Run this codex binary instead of the configured build (skips auto-build)
+        #[arg(long, value_name = "PATH")]
+        codex_bin: Option<PathBuf>,
+        /// Do not inject `-C <cwd>` into the codex args
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        no_cd: bool,
+        /// Build and run from this directory (relative to the repo) instead of `[build] workspace`
+        #[arg(long, value_name = "RELPATH")]
+        build_dir: Option<PathBuf>,
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         args: Vec<String>,
     },
@@ -98,45 +165,131 @@ fn handle_cli(cli: Cli) -> Result<()> {
         anyhow::bail!("--loader-* flags cannot be combined with other codex commands");
     }
 
+    let overrides = ConfigOverrides {
+        repo: cli.repo.clone(),
+        local_branch: cli.local_branch.clone(),
+        upstream_branch: cli.upstream_branch.clone(),
+    };
+    let config_path = cli.config.as_deref();
     if cli.loader_status {
-        let cfg = ForksmithConfig::load_default()?;
-        return status::run(&cfg);
+        let cfg = load_config(config_path, &overrides)?;
+        return status::run(&cfg, false);
     }
     if cli.loader_sync {
-        let cfg = ForksmithConfig::load_default()?;
-        return sync::run(&cfg, cli.loader_sync_dry_run);
+        let cfg = load_config(config_path, &overrides)?;
+        let opts = sync::SyncOptions {
+            dry_run: cli.loader_sync_dry_run,
+            ..Default::default()
+        };
+        return sync::run(&cfg, &opts);
     }
     if cli.loader_build {
-        let cfg = ForksmithConfig::load_default()?;
-        return build::run(&cfg);
+        let cfg = load_config(config_path, &overrides)?;
+        return build::run(&cfg, false);
     }
 
     match cli.command {
         Some(command) => {
-            let cfg = ForksmithConfig::load_default()?;
+            let cfg = load_config(config_path, &overrides)?;
             match command {
-                Commands::Status => status::run(&cfg),
-                Commands::Sync { dry_run } => sync::run(&cfg, dry_run),
-                Commands::Build => build::run(&cfg),
-                Commands::Run { args } => run_cmd::run(&cfg, &args),
+                Commands::Status { json } => status::run(&cfg, json),
+                Commands::Sync {
+                    dry_run,
+                    rebase,
+                    merge,
+                    remote,
+                    prune,
+                } => sync::run(
+                    &cfg,
+                    &sync::SyncOptions {
+                        dry_run,
+                        rebase,
+                        merge,
+                        remote,
+                        prune,
+                    },
+                ),
+                Commands::Build { dry_run, build_dir } => {
+                    build::run(&with_build_dir(cfg, build_dir.as_deref())?, dry_run)
+                }
+                Commands::Clean { hard } => clean::run(&cfg, hard),
+                Commands::Checkout { rev } => checkout::run(&cfg, &rev),
+                Commands::Diff { stat } => diff::run(&cfg, stat),
+                Commands::Run {
+                    codex_bin,
+                    no_cd,
+                    build_dir,
+                    args,
+                } => run_cmd::run(
+                    &with_build_dir(cfg, build_dir.as_deref())?,
+                    &args,
+                    &run_cmd::RunOptions { codex_bin, no_cd },
+                ),
             }
         }
         None => {
-            let cfg = ForksmithConfig::load_default()?;
-            run_cmd::run(&cfg, &[])
+            let cfg = load_config(config_path, &overrides)?;
+            run_cmd::run(&cfg, &[], &Default::default())
         }
     }
 }
 
-fn run_passthrough(raw_args: &[String]) -> Result<()> {
-    let run_args = if raw_args.len() > 1 {
-        raw_args[1..].to_vec()
-    } else {
-        Vec::new()
+fn load_config(path: Option<&Path>, overrides: &ConfigOverrides) -> Result<ForksmithConfig> {
+    let cfg = match path {
+        Some(path) => ForksmithConfig::load_from_path(path)?,
+        None => ForksmithConfig::load_default()?,
     };
+    cfg.with_overrides(overrides)
+}
 
-    let cfg = ForksmithConfig::load_default()?;
-    run_cmd::run(&cfg, &run_args)
+fn with_build_dir(cfg: ForksmithConfig, build_dir: Option<&Path>) -> Result<ForksmithConfig> {
+    match build_dir {
+        Some(dir) => cfg.with_build_dir(dir),
+        None => Ok(cfg),
+    }
+}
+
+fn run_passthrough(raw_args: &[String]) -> Result<()> {
+    let (config_path, overrides, run_args) = split_global_flags(raw_args.get(1..).unwrap_or(&[]))?;
+    let cfg = load_config(config_path.as_deref(), &overrides)?;
+    run_cmd::run(&cfg, &run_args, &Default::default())
+}
+
+/// Pulls the global `--forksmith-config`, `--repo`, `--local-branch` and
+/// `--upstream-branch` flags (`--flag value` or `--flag=value`) out of
+/// passthrough args; everything else is left for codex in order.
+fn split_global_flags(args: &[String]) -> Result<(Option<PathBuf>, ConfigOverrides, Vec<String>)> {
+    let mut config = None;
+    let mut overrides = ConfigOverrides::default();
+    let mut rest = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        if !matches!(
+            flag,
+            "--forksmith-config" | "--repo" | "--local-branch" | "--upstream-branch"
+        ) {
+            rest.push(arg.clone());
+            continue;
+        }
+        let value = match inline {
+            Some(value) => value,
+            None => iter
+                .next()
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("{flag} requires a value"))?,
+        };
+        match flag {
+            "--forksmith-config" => config = Some(PathBuf::from(value)),
+            "--repo" => overrides.repo = Some(PathBuf::from(value)),
+            "--local-branch" => overrides.local_branch = Some(value),
+            _ => overrides.upstream_branch = Some(value),
+        }
+    }
+    Ok((config, overrides, rest))
 }
 
 fn print_top_level_help() {
@@ -145,6 +298,8 @@ fn print_top_level_help() {
     println!("  codex status             # inspect workspace + vendor state");
     println!("  codex sync               # refresh remotes (add --dry-run to preview)");
     println!("  codex build              # build vendor/codex binary (cargo --profile release)");
+    println!("  codex diff [--stat]      # show the fork's delta from upstream");
+    println!("  codex clean [--hard]     # preview (or perform) a vendor reset + clean");
     println!(
         "  codex resume             # run the codex binary (shorthand for `codex run -- resume`)"
     );
@@ -153,3 +308,33 @@ fn print_top_level_help() {
     println!("  codex --loader-build     # invoke build via loader passthrough\n");
     println!("For full help: codex --help or codex help <command>.");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passthrough_strips_forksmith_flags_and_keeps_codex_config() {
+        let args: Vec<String> = [
+            "--forksmith-config",
+            "ws.toml",
+            "--config",
+            "model=o3",
+            "--repo=/tmp/codex",
+            "resume",
+            "--upstream-branch",
+            "next",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        let (config, overrides, rest) = split_global_flags(&args).unwrap();
+        assert_eq!(config, Some(PathBuf::from("ws.toml")));
+        assert_eq!(overrides.repo, Some(PathBuf::from("/tmp/codex")));
+        assert_eq!(overrides.upstream_branch.as_deref(), Some("next"));
+        assert_eq!(overrides.local_branch, None);
+        assert_eq!(rest, ["--config", "model=o3", "resume"]);
+
+        assert!(split_global_flags(&["--local-branch".to_string()]).is_err());
+    }
+}
@@ -2,6 +2,8 @@ use std::path::Path;
 use std::process::Command;
 
 use anyhow::{anyhow, Context, Result};
+use codex_core::git_auth::{redact, remote_git_command};
+use codex_core::DivergenceError;
 
 pub fn ensure_repo(repo: &Path) -> Result<()> {
     if !repo.exists() {
@@ -39,31 +41,30 @@ pub fn head_commit(repo: &Path) -> Result<String> {
 }
 
 pub fn is_clean(repo: &Path) -> Result<bool> {
-    ensure_repo(repo)?;
-    let status = Command::new("git")
-        .args(["status", "--porcelain"])
-        .current_dir(repo)
-        .output()
-        .with_context(|| format!("checking git status in {}", repo.display()))?;
-    if !status.status.success() {
-        anyhow::bail!(
-            "git status failed: {}",
-            String::from_utf8_lossy(&status.stderr)
-        );
-    }
-    Ok(status.stdout.is_empty())
+    let snapshot = status_snapshot(repo)?;
+    Ok(snapshot.tracked == 0 && snapshot.untracked == 0 && snapshot.conflicted.is_empty())
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Dirty paths kept in a [`StatusSnapshot`]; the counts still cover all of them.
+pub const DIRTY_PATH_LIMIT: usize = 10;
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct StatusSnapshot {
+    /// Changed tracked paths, excluding conflicts; a rename counts once.
     pub tracked: usize,
+    /// Tracked paths with changes in the index.
+    pub staged: usize,
     pub untracked: usize,
+    /// Every path with an unmerged (conflict) status.
+    pub conflicted: Vec<String>,
+    /// The first [`DIRTY_PATH_LIMIT`] other modified or untracked paths.
+    pub dirty: Vec<String>,
 }
 
 pub fn status_snapshot(repo: &Path) -> Result<StatusSnapshot> {
     ensure_repo(repo)?;
     let output = Command::new("git")
-        .args(["status", "--porcelain"])
+        .args(["status", "--porcelain=v2", "-z"])
         .current_dir(repo)
         .output()
         .with_context(|| format!("checking git status in {}", repo.display()))?;
@@ -73,19 +74,55 @@ pub fn status_snapshot(repo: &Path) -> Result<StatusSnapshot> {
             String::from_utf8_lossy(&output.stderr)
         );
     }
-    let mut tracked = 0;
-    let mut untracked = 0;
-    for line in String::from_utf8_lossy(&output.stdout).lines() {
-        if line.starts_with("??") {
-            untracked += 1;
-        } else if !line.trim().is_empty() {
-            tracked += 1;
+    Ok(parse_porcelain_v2(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `git status --porcelain=v2 -z` output.
+fn parse_porcelain_v2(output: &str) -> StatusSnapshot {
+    let mut snapshot = StatusSnapshot::default();
+    let mut records = output.split('\0').filter(|record| !record.is_empty());
+    while let Some(record) = records.next() {
+        let (path, staged) = match record.as_bytes()[0] {
+            b'1' => match record.splitn(9, ' ').collect::<Vec<_>>()[..] {
+                [_, xy, .., path] => (path, !xy.starts_with('.')),
+                _ => continue,
+            },
+            b'2' => {
+                // Renames and copies are followed by their original path.
+                records.next();
+                match record.splitn(10, ' ').collect::<Vec<_>>()[..] {
+                    [_, xy, .., path] => (path, !xy.starts_with('.')),
+                    _ => continue,
+                }
+            }
+            b'u' => {
+                if let Some(path) = record.splitn(11, ' ').nth(10) {
+                    snapshot.conflicted.push(path.to_string());
+                }
+                continue;
+            }
+            b'?' => {
+                snapshot.untracked += 1;
+                (record.get(2..).unwrap_or_default(), false)
+            }
+            _ => continue,
+        };
+        if record.starts_with(['1', '2']) {
+            snapshot.tracked += 1;
+            snapshot.staged += usize::from(staged);
+        }
+        if snapshot.dirty.len() < DIRTY_PATH_LIMIT {
+            snapshot.dirty.push(path.to_string());
         }
     }
-    Ok(StatusSnapshot { tracked, untracked })
+    snapshot
 }
 
 pub fn has_unmerged_paths(repo: &Path) -> Result<bool> {
+    Ok(!unmerged_paths(repo)?.is_empty())
+}
+
+pub fn unmerged_paths(repo: &Path) -> Result<Vec<String>> {
     ensure_repo(repo)?;
     let output = Command::new("git")
         .args(["diff", "--name-only", "--diff-filter=U"])
@@ -98,11 +135,68 @@ pub fn has_unmerged_paths(repo: &Path) -> Result<bool> {
             String::from_utf8_lossy(&output.stderr)
         );
     }
-    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Tags, newest version first.
+pub fn list_tags(repo: &Path) -> Result<Vec<String>> {
+    let output = run_git(repo, &["tag", "--list", "--sort=-v:refname"])?;
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+/// Checks out `rev`; tags and commits leave HEAD detached.
+pub fn checkout(repo: &Path, rev: &str) -> Result<()> {
+    run_git(repo, &["checkout", "--quiet", rev]).map(|_| ())
 }
 
 pub fn fetch(repo: &Path, remote: &str) -> Result<()> {
-    run_git(repo, &["fetch", remote]).map(|_| ())
+    remote_git(repo, remote, &["fetch", remote]).map(|_| ())
+}
+
+/// Fetches with `--prune` and returns how many remote-tracking refs of
+/// `remote` were deleted.
+pub fn fetch_prune(repo: &Path, remote: &str) -> Result<usize> {
+    let before = remote_branches(repo, remote)?;
+    remote_git(repo, remote, &["fetch", "--prune", remote])?;
+    let after = remote_branches(repo, remote)?;
+    Ok(before.iter().filter(|b| !after.contains(b)).count())
+}
+
+/// Number of remote-tracking refs `fetch --prune` would delete.
+pub fn prunable_refs(repo: &Path, remote: &str) -> Result<usize> {
+    let output = remote_git(repo, remote, &["remote", "prune", "--dry-run", remote])?;
+    Ok(would_prune(&output).len())
+}
+
+/// Refs listed as ` * [would prune] origin/feature` by `git remote prune
+/// --dry-run`.
+fn would_prune(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("* [would prune]"))
+        .map(str::trim)
+        .collect()
+}
+
+/// Runs a git command that talks to `remote`, authenticated per
+/// [`remote_git_command`]; the token is redacted from any error.
+fn remote_git(repo: &Path, remote: &str, args: &[&str]) -> Result<String> {
+    ensure_repo(repo)?;
+    let (mut command, token) = remote_git_command(repo, remote, args);
+    let output = command
+        .output()
+        .with_context(|| format!("running git {args:?} in {}", repo.display()))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git {args:?} failed: {}", redact(&stderr, token.as_deref()));
+    }
+    // `remote prune` reports on stdout, `fetch` on stderr.
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
 pub fn push(repo: &Path, remote: &str, branch: &str) -> Result<()> {
@@ -114,7 +208,51 @@ pub fn has_remote(repo: &Path, remote: &str) -> Result<bool> {
     Ok(output.lines().any(|line| line.trim() == remote))
 }
 
-pub fn divergence(repo: &Path, base: &str, other: &str) -> Result<(u32, u32)> {
+pub fn ref_exists(repo: &Path, reference: &str) -> Result<bool> {
+    let spec = format!("{reference}^{{commit}}");
+    let status = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet", &spec])
+        .current_dir(repo)
+        .output()
+        .with_context(|| format!("resolving {reference} in {}", repo.display()))?
+        .status;
+    Ok(status.success())
+}
+
+/// Remote-tracking branch names under `remote`, without the remote prefix.
+pub fn remote_branches(repo: &Path, remote: &str) -> Result<Vec<String>> {
+    let pattern = format!("refs/remotes/{remote}/");
+    let output = run_git(repo, &["for-each-ref", "--format=%(refname)", &pattern])?;
+    Ok(branch_names(&output, &pattern))
+}
+
+/// Names under `pattern` in `for-each-ref --format=%(refname)` output,
+/// leaving out the remote's symbolic `HEAD`.
+fn branch_names(output: &str, pattern: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.strip_prefix(pattern))
+        .filter(|name| *name != "HEAD")
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn divergence(
+    repo: &Path,
+    base: &str,
+    other: &str,
+) -> std::result::Result<(u32, u32), DivergenceError> {
+    for reference in [base, other] {
+        if !ref_exists(repo, reference)? {
+            return Err(DivergenceError::MissingRef(reference.to_string()));
+        }
+    }
+    if !has_merge_base(repo, base, other)? {
+        return Err(DivergenceError::UnrelatedHistories {
+            base: base.to_string(),
+            other: other.to_string(),
+        });
+    }
     let spec = format!("{base}...{other}");
     let output = run_git(repo, &["rev-list", "--left-right", "--count", &spec])?;
     let mut parts = output.split_whitespace();
@@ -131,6 +269,161 @@ pub fn divergence(repo: &Path, base: &str, other: &str) -> Result<(u32, u32)> {
     Ok((left, right))
 }
 
+/// `git merge-base` exits 1 with no output when the histories never meet.
+fn has_merge_base(repo: &Path, base: &str, other: &str) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["merge-base", base, other])
+        .current_dir(repo)
+        .output()
+        .with_context(|| format!("running git merge-base in {}", repo.display()))?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) if output.stdout.is_empty() => Ok(false),
+        _ => anyhow::bail!(
+            "git merge-base {base} {other} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    }
+}
+
 pub fn fast_forward(repo: &Path, target: &str) -> Result<()> {
     run_git(repo, &["merge", "--ff-only", target]).map(|_| ())
 }
+
+/// Non-ff merge; conflicts leave the tree mid-merge and surface as `Err`.
+pub fn merge(repo: &Path, target: &str) -> Result<()> {
+    run_git(repo, &["merge", "--no-edit", target]).map(|_| ())
+}
+
+pub fn rebase(repo: &Path, onto: &str) -> Result<()> {
+    run_git(repo, &["rebase", onto]).map(|_| ())
+}
+
+pub fn rebase_abort(repo: &Path) -> Result<()> {
+    run_git(repo, &["rebase", "--abort"]).map(|_| ())
+}
+
+pub fn diff(repo: &Path, range: &str, stat: bool) -> Result<String> {
+    let mut args = vec!["diff"];
+    if stat {
+        args.push("--stat");
+    }
+    args.push(range);
+    run_git(repo, &args)
+}
+
+pub fn reset_hard(repo: &Path, target: &str) -> Result<()> {
+    run_git(repo, &["reset", "--hard", target]).map(|_| ())
+}
+
+/// Tracked paths whose changes `git reset --hard HEAD` would discard.
+pub fn changed_tracked_paths(repo: &Path) -> Result<Vec<String>> {
+    let output = run_git(repo, &["diff", "--name-only", "HEAD"])?;
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+/// Paths `git clean -fdx` would delete (untracked and ignored).
+pub fn clean_preview(repo: &Path) -> Result<Vec<String>> {
+    would_remove(repo, "-ndx")
+}
+
+/// Untracked paths `git clean -fd` would delete, leaving ignored build
+/// artifacts out.
+pub fn untracked_preview(repo: &Path) -> Result<Vec<String>> {
+    would_remove(repo, "-nd")
+}
+
+fn would_remove(repo: &Path, flags: &str) -> Result<Vec<String>> {
+    let output = run_git(repo, &["clean", flags])?;
+    Ok(output
+        .lines()
+        .filter_map(|line| line.strip_prefix("Would remove "))
+        .map(str::to_string)
+        .collect())
+}
+
+pub fn clean_force(repo: &Path) -> Result<()> {
+    run_git(repo, &["clean", "-fdx"]).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{branch_names, divergence, parse_porcelain_v2, would_prune};
+    use codex_core::DivergenceError;
+    use std::path::Path;
+    use std::process::Command;
+
+    #[test]
+    fn divergence_reports_unrelated_roots_and_missing_refs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let repo = tmp.path();
+        git(repo, &["init", "-q", "-b", "main"]);
+        git(repo, &["commit", "-q", "--allow-empty", "-m", "fork root"]);
+        git(repo, &["checkout", "-q", "--orphan", "squashed"]);
+        git(
+            repo,
+            &["commit", "-q", "--allow-empty", "-m", "upstream root"],
+        );
+
+        assert!(matches!(
+            divergence(repo, "main", "squashed"),
+            Err(DivergenceError::UnrelatedHistories { .. })
+        ));
+        assert!(matches!(
+            divergence(repo, "main", "upstream/main"),
+            Err(DivergenceError::MissingRef(r)) if r == "upstream/main"
+        ));
+        git(repo, &["commit", "-q", "--allow-empty", "-m", "next"]);
+        assert_eq!(divergence(repo, "HEAD", "HEAD~1").unwrap(), (1, 0));
+    }
+
+    #[test]
+    fn porcelain_v2_separates_staged_renamed_and_conflicted_paths() {
+        let output = concat!(
+            "1 M. N... 100644 100644 100644 aaaa bbbb src/lib.rs\0",
+            "1 .M N... 100644 100644 100644 aaaa aaaa README.md\0",
+            "2 R. N... 100644 100644 100644 aaaa aaaa R100 src/new name.rs\0src/old.rs\0",
+            "u UU N... 100644 100644 100644 100644 aaaa bbbb cccc Cargo.toml\0",
+            "u AA N... 000000 100644 100644 100644 0000 bbbb cccc docs/both.md\0",
+            "? notes.txt\0",
+            "! target/\0",
+        );
+        let snapshot = parse_porcelain_v2(output);
+        assert_eq!(snapshot.tracked, 3);
+        assert_eq!(snapshot.staged, 2);
+        assert_eq!(snapshot.untracked, 1);
+        assert_eq!(snapshot.conflicted, ["Cargo.toml", "docs/both.md"]);
+        assert_eq!(
+            snapshot.dirty,
+            ["src/lib.rs", "README.md", "src/new name.rs", "notes.txt"]
+        );
+        assert_eq!(parse_porcelain_v2(""), Default::default());
+    }
+
+    #[test]
+    fn prune_output_lists_the_stale_refs() {
+        let dry_run = "Pruning origin\n\
+                       URL: https://example.com/codex.git\n \
+                       * [would prune] origin/feature\n \
+                       * [would prune] origin/old\n";
+        assert_eq!(would_prune(dry_run), ["origin/feature", "origin/old"]);
+        assert!(would_prune("Pruning origin\nURL: https://example.com/codex.git\n").is_empty());
+
+        let refs = "refs/remotes/origin/HEAD\nrefs/remotes/origin/main\n\
+                    refs/remotes/origin/team/feature\n";
+        assert_eq!(
+            branch_names(refs, "refs/remotes/origin/"),
+            ["main", "team/feature"]
+        );
+    }
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+}
@@ -1,6 +1,7 @@
 mod config;
 mod dev;
 mod engines;
+mod legacy_patches;
 mod process;
 mod registry;
 mod runner;
@@ -30,11 +31,16 @@ struct Cli {
 enum Command {
     /// Pull upstream, apply patches, update registry, and build
     Update(UpdateArgs),
+    /// Dry-run all enabled patch sets without resetting, writing or building
+    Preview {
+        /// Emit machine-readable JSON summary
+        #[arg(long)]
+        json: bool,
+    },
     /// Check environment, tools, and vendor repo state
     Doctor,
     /// Registry management commands
-    #[command(subcommand)]
-    Registry(RegistryCmd),
+    Registry(RegistryArgs),
     /// Developer utilities (formatting, linting, etc.)
     #[command(subcommand)]
     Dev(DevCommand),
@@ -51,17 +57,42 @@ struct UpdateArgs {
     /// Emit machine-readable JSON summary
     #[arg(long)]
     json: bool,
+    /// Pin vendor/codex to this commit or tag instead of origin/<branch>
+    #[arg(long)]
+    rev: Option<String>,
+    /// Discard uncommitted vendor changes when hard-resetting
+    #[arg(long)]
+    force: bool,
+    /// Apply only this patch-set id (repeatable)
+    #[arg(long, value_name = "ID")]
+    only: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct RegistryArgs {
+    /// Emit machine-readable JSON (list, explain, stats)
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: RegistryCmd,
 }
 
 #[derive(Subcommand, Debug)]
 enum RegistryCmd {
     /// List registered patch sets
-    List,
+    List {
+        /// Also show descriptions, last run time and last commit
+        #[arg(long)]
+        wide: bool,
+    },
     /// Show detailed information for a patch set
     Explain {
         /// Patch-set id (e.g. astgrep:increase-max-output-tokens)
         id: String,
     },
+    /// Summarize enabled, zero-match, and failed patch sets
+    Stats,
     /// Enable a patch-set by id
     Enable {
         #[arg(value_name = "ID")]
@@ -72,11 +103,16 @@ enum RegistryCmd {
         #[arg(value_name = "ID")]
         id: String,
     },
+    /// Reverse a patch-set's rules against the vendor tree
+    Unapply {
+        #[arg(value_name = "ID")]
+        id: String,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 enum DevCommand {
-    /// Watch the repo and auto-run `cargo fmt` + `cargo clippy -D warnings`
+    /// Watch the repo and re-run `[dev] watch_commands` (default: fmt + clippy)
     Watch,
 }
 
@@ -86,16 +122,33 @@ fn main() -> Result<()> {
 
     match cli.command {
         Command::Update(args) => {
-            let opts = UpdateOptions::new(args.dry_run, args.skip_build, args.json);
-            runner::run_update(&root, opts)
+            let opts = UpdateOptions {
+                dry_run: args.dry_run,
+                skip_build: args.skip_build,
+                rev: args.rev,
+                force: args.force,
+                only: args.only,
+            };
+            let summaries = runner::run_update(&root, opts)?;
+            runner::print_summary(&summaries, args.json)?;
+            runner::update_outcome(&summaries)
+        }
+        Command::Preview { json } => {
+            let summaries = runner::run_preview(&root)?;
+            runner::print_summary(&summaries, json)
         }
         Command::Doctor => runner::run_health(&root),
-        Command::Registry(RegistryCmd::List) => runner::run_list_patches(&root),
-        Command::Registry(RegistryCmd::Explain { id }) => runner::run_explain_patch(&root, &id),
-        Command::Registry(RegistryCmd::Enable { id }) => runner::run_toggle_patch(&root, &id, true),
-        Command::Registry(RegistryCmd::Disable { id }) => {
-            runner::run_toggle_patch(&root, &id, false)
+        Command::Registry(args) => match args.command {
+            RegistryCmd::List { wide } => runner::run_list_patches(&root, args.json, wide),
+            RegistryCmd::Explain { id } => runner::run_explain_patch(&root, &id, args.json),
+            RegistryCmd::Stats => runner::run_registry_stats(&root, args.json),
+            RegistryCmd::Enable { id } => runner::run_toggle_patch(&root, &id, true),
+            RegistryCmd::Disable { id } => runner::run_toggle_patch(&root, &id, false),
+            RegistryCmd::Unapply { id } => runner::run_unapply_patch(&root, &id),
+        },
+        Command::Dev(DevCommand::Watch) => {
+            let cfg = config::Config::load(&root)?;
+            dev::run_watch(&root, &cfg.dev)
         }
-        Command::Dev(DevCommand::Watch) => dev::run_watch(&root),
     }
 }
@@ -1,19 +1,90 @@
-use std::process::{Command, Stdio};
+use std::process::{Command, Output, Stdio};
 
 use anyhow::{Context, Result};
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
 
-pub fn apply_legacy_patches(vendor_dir: &Utf8Path) -> Result<()> {
-    for (name, patch) in LEGACY_PATCHES {
-        apply_patch(name, patch, vendor_dir)?;
+use crate::engines::patch::{apply_with_fallback, ApplyStrategy};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegacyPatchStatus {
+    Applied,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LegacyPatchReport {
+    pub name: String,
+    pub status: LegacyPatchStatus,
+    /// `git apply` stderr when the patch failed.
+    pub stderr: Option<String>,
+    /// Set when the patch only applied via a fuzzier fallback.
+    pub fallback: Option<&'static str>,
+}
+
+/// Applies every `.patch`/`.diff` file in `patches_dir` to `vendor_dir` in
+/// sorted filename order. Patches that already reverse-apply cleanly are
+/// skipped; a patch that does not apply cleanly is retried with reduced
+/// context and then fuzz, and one that still fails is recorded as failed
+/// while the batch keeps going so the caller sees every outcome. A missing
+/// `patches_dir` yields an empty report.
+pub fn apply_legacy_patches(
+    vendor_dir: &Utf8Path,
+    patches_dir: &Utf8Path,
+) -> Result<Vec<LegacyPatchReport>> {
+    let mut reports = Vec::new();
+    for path in patch_files(patches_dir)? {
+        let name = path.file_name().unwrap_or(path.as_str()).to_string();
+        let patch = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading legacy patch {path}"))?;
+        let already_applied = git_apply(vendor_dir, &name, &patch, &["--reverse", "--check"])?
+            .status
+            .success();
+        let (status, stderr, fallback) = if already_applied {
+            (LegacyPatchStatus::Skipped, None, None)
+        } else {
+            match apply_with_fallback(vendor_dir.as_std_path(), patch.as_bytes(), false)
+                .with_context(|| format!("applying legacy patch {name}"))?
+            {
+                Ok(ApplyStrategy::ThreeWay) => (LegacyPatchStatus::Applied, None, None),
+                Ok(strategy) => (LegacyPatchStatus::Applied, None, Some(strategy.describe())),
+                Err(stderr) => (LegacyPatchStatus::Failed, Some(stderr), None),
+            }
+        };
+        reports.push(LegacyPatchReport {
+            name,
+            status,
+            stderr,
+            fallback,
+        });
+    }
+    Ok(reports)
+}
+
+fn patch_files(patches_dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    if !patches_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in patches_dir
+        .read_dir_utf8()
+        .with_context(|| format!("reading {patches_dir}"))?
+    {
+        let path = entry?.into_path();
+        if matches!(path.extension(), Some("patch" | "diff")) && path.is_file() {
+            files.push(path);
+        }
     }
-    Ok(())
+    files.sort();
+    Ok(files)
 }
 
-fn apply_patch(name: &str, patch: &str, vendor_dir: &Utf8Path) -> Result<()> {
+fn git_apply(vendor_dir: &Utf8Path, name: &str, patch: &str, args: &[&str]) -> Result<Output> {
     let mut cmd = Command::new("git");
     cmd.arg("apply")
-        .arg("--3way")
+        .args(args)
         .arg("--allow-empty")
         .arg("--whitespace=nowarn")
         .arg("-")
@@ -22,30 +93,13 @@ fn apply_patch(name: &str, patch: &str, vendor_dir: &Utf8Path) -> Result<()> {
         .stdout(Stdio::null())
         .stderr(Stdio::piped());
 
-    let mut child = cmd.spawn().with_context(|| format!("spawning git apply for {name}"))?;
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("spawning git apply for {name}"))?;
     {
         use std::io::Write;
         let stdin = child.stdin.as_mut().expect("stdin available");
         stdin.write_all(patch.as_bytes())?;
     }
-    let output = child.wait_with_output()?;
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("git apply failed for {name}: {stderr}");
-    }
-    Ok(())
+    Ok(child.wait_with_output()?)
 }
-
-const LEGACY_PATCHES: &[(&str, &str)] = &[
-    ("fix-chatgpt-codex-mini-fallback", PATCH_CHATGPT_CODEX_MINI_FALLBACK),
-    ("unbounded-exec-output", PATCH_UNBOUNDED_EXEC_OUTPUT),
-    ("hb-unified-exec-trace", PATCH_HB_UNIFIED_EXEC_TRACE),
-    ("hb-event-log", PATCH_HB_EVENT_LOG),
-    ("allow-sudo-by-default", PATCH_ALLOW_SUDO_BY_DEFAULT),
-];
-
-const PATCH_CHATGPT_CODEX_MINI_FALLBACK: &str = r#"PLACEHOLDER"#;
-const PATCH_UNBOUNDED_EXEC_OUTPUT: &str = r#"PLACEHOLDER"#;
-const PATCH_HB_UNIFIED_EXEC_TRACE: &str = r#"PLACEHOLDER"#;
-const PATCH_HB_EVENT_LOG: &str = r#"PLACEHOLDER"#;
-const PATCH_ALLOW_SUDO_BY_DEFAULT: &str = r#"PLACEHOLDER"#;
@@ -2,6 +2,9 @@ use anyhow::{anyhow, Context, Result};
 use std::path::Path;
 use std::process::{Command, Output};
 
+use codex_core::git_auth::{redact, remote_git_command};
+use codex_core::DivergenceError;
+
 pub fn run_command(program: &str, args: &[&str], cwd: Option<&Path>) -> Result<Output> {
     let mut cmd = Command::new(program);
     cmd.args(args);
@@ -12,9 +15,23 @@ pub fn run_command(program: &str, args: &[&str], cwd: Option<&Path>) -> Result<O
         .with_context(|| format!("Failed to spawn {program} with args {args:?}"))
 }
 
+/// `git fetch <remote> <extra..>`, authenticated per [`remote_git_command`];
+/// the token is redacted from the returned stderr.
+fn git_fetch(repo: &Path, remote: &str, extra: &[&str]) -> Result<Output> {
+    let args: Vec<&str> = ["fetch", remote]
+        .into_iter()
+        .chain(extra.iter().copied())
+        .collect();
+    let (mut cmd, token) = remote_git_command(repo, remote, &args);
+    let mut out = cmd
+        .output()
+        .with_context(|| format!("Failed to spawn git fetch {remote}"))?;
+    out.stderr = redact(&String::from_utf8_lossy(&out.stderr), token.as_deref()).into_bytes();
+    Ok(out)
+}
+
 pub fn git_reset_to_branch(repo: &Path, branch: &str) -> Result<()> {
-    run_command("git", &["fetch", "origin"], Some(repo))
-        .with_context(|| "git fetch origin failed")?;
+    git_fetch(repo, "origin", &[]).with_context(|| "git fetch origin failed")?;
     let target = format!("origin/{branch}");
     let out = run_command("git", &["reset", "--hard", &target], Some(repo))?;
     if !out.status.success() {
@@ -28,6 +45,30 @@ pub fn git_reset_to_branch(repo: &Path, branch: &str) -> Result<()> {
     Ok(())
 }
 
+pub fn git_reset_to_rev(repo: &Path, rev: &str) -> Result<()> {
+    git_fetch(repo, "origin", &["--tags"]).with_context(|| "git fetch origin failed")?;
+    let spec = format!("{rev}^{{commit}}");
+    let verify = run_command(
+        "git",
+        &["rev-parse", "--verify", "--quiet", &spec],
+        Some(repo),
+    )?;
+    if !verify.status.success() {
+        anyhow::bail!("pinned revision {rev} not found in {}", repo.display());
+    }
+    let target = String::from_utf8_lossy(&verify.stdout).trim().to_string();
+    let out = run_command("git", &["reset", "--hard", &target], Some(repo))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git reset --hard {rev} failed with status {:?} and stderr:
+{}",
+            out.status.code(),
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+    Ok(())
+}
+
 pub fn git_head_commit(repo: &Path) -> Result<String> {
     let out = run_command("git", &["rev-parse", "HEAD"], Some(repo))?;
     if !out.status.success() {
@@ -52,6 +93,17 @@ pub fn git_current_branch(repo: &Path) -> Result<String> {
 }
 
 pub fn git_is_clean(repo: &Path) -> Result<bool> {
+    let out = run_command("git", &["status", "--porcelain=v2"], Some(repo))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git status --porcelain=v2 failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+    Ok(out.stdout.is_empty())
+}
+
+pub fn git_status_porcelain(repo: &Path) -> Result<String> {
     let out = run_command("git", &["status", "--porcelain"], Some(repo))?;
     if !out.status.success() {
         anyhow::bail!(
@@ -59,12 +111,32 @@ pub fn git_is_clean(repo: &Path) -> Result<bool> {
             String::from_utf8_lossy(&out.stderr)
         );
     }
-    Ok(out.stdout.is_empty())
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// Porcelain status (untracked files included) plus the full diff against
+/// HEAD; equal snapshots mean the working tree did not change.
+pub fn git_tree_state(repo: &Path) -> Result<String> {
+    let mut state = String::new();
+    for args in [
+        &["status", "--porcelain", "--untracked-files=all"][..],
+        &["diff", "HEAD", "--binary"][..],
+    ] {
+        let out = run_command("git", args, Some(repo))?;
+        if !out.status.success() {
+            anyhow::bail!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&out.stderr)
+            );
+        }
+        state.push_str(&String::from_utf8_lossy(&out.stdout));
+    }
+    Ok(state)
 }
 
 pub fn git_fetch_remote(repo: &Path, remote: &str) -> Result<()> {
-    let out = run_command("git", &["fetch", remote], Some(repo))
-        .with_context(|| format!("git fetch {remote} failed"))?;
+    let out = git_fetch(repo, remote, &[]).with_context(|| format!("git fetch {remote} failed"))?;
     if !out.status.success() {
         anyhow::bail!(
             "git fetch {remote} returned {:?}: {}",
@@ -75,7 +147,39 @@ pub fn git_fetch_remote(repo: &Path, remote: &str) -> Result<()> {
     Ok(())
 }
 
-pub fn git_divergence(repo: &Path, left: &str, right: &str) -> Result<(u32, u32)> {
+pub fn git_divergence(
+    repo: &Path,
+    left: &str,
+    right: &str,
+) -> std::result::Result<(u32, u32), DivergenceError> {
+    for reference in [left, right] {
+        let spec = format!("{reference}^{{commit}}");
+        let out = run_command(
+            "git",
+            &["rev-parse", "--verify", "--quiet", &spec],
+            Some(repo),
+        )?;
+        if !out.status.success() {
+            return Err(DivergenceError::MissingRef(reference.to_string()));
+        }
+    }
+    let out = run_command("git", &["merge-base", left, right], Some(repo))?;
+    match out.status.code() {
+        Some(0) => {}
+        Some(1) if out.stdout.is_empty() => {
+            return Err(DivergenceError::UnrelatedHistories {
+                base: left.to_string(),
+                other: right.to_string(),
+            })
+        }
+        _ => {
+            return Err(anyhow!(
+                "git merge-base {left} {right} failed: {}",
+                String::from_utf8_lossy(&out.stderr)
+            )
+            .into())
+        }
+    }
     let range = format!("{left}...{right}");
     let out = run_command(
         "git",
@@ -83,10 +187,11 @@ pub fn git_divergence(repo: &Path, left: &str, right: &str) -> Result<(u32, u32)
         Some(repo),
     )?;
     if !out.status.success() {
-        anyhow::bail!(
+        return Err(anyhow!(
             "git rev-list --left-right --count {range} failed: {}",
             String::from_utf8_lossy(&out.stderr)
-        );
+        )
+        .into());
     }
     let stdout = String::from_utf8_lossy(&out.stdout);
     let mut parts = stdout.split_whitespace();
@@ -143,6 +248,37 @@ pub fn git_merge_with_strategy(
     Ok(())
 }
 
+/// Paths left unmerged by a conflicted merge.
+pub fn git_unmerged_paths(repo: &Path) -> Result<Vec<String>> {
+    let out = run_command(
+        "git",
+        &["diff", "--name-only", "--diff-filter=U"],
+        Some(repo),
+    )?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git diff --diff-filter=U failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .map(str::to_string)
+        .collect())
+}
+
+/// Concludes an in-progress merge whose conflicts have been staged.
+pub fn git_merge_continue(repo: &Path) -> Result<()> {
+    let out = run_command("git", &["commit", "--no-edit"], Some(repo))?;
+    if !out.status.success() {
+        anyhow::bail!(
+            "git commit --no-edit failed: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+    Ok(())
+}
+
 pub fn git_merge_abort(repo: &Path) -> Result<()> {
     let out = run_command("git", &["merge", "--abort"], Some(repo))?;
     if !out.status.success() {
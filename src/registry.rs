@@ -1,5 +1,7 @@
 use crate::config::Config;
+use crate::engines::EngineOutcome;
 use anyhow::{Context, Result};
+use codex_registry::RegistryStats;
 use serde::{Deserialize, Serialize};
 use std::{fs, path::Path};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
@@ -7,10 +9,13 @@ use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum EngineKind {
-    #[serde(alias = "ast_grep")]
-    #[serde(alias = "coccinelle")]
-    #[serde(alias = "gritql")]
-    #[serde(alias = "patch")]
+    #[serde(alias = "ast-grep", alias = "astgrep")]
+    AstGrep,
+    #[serde(alias = "cocci")]
+    Coccinelle,
+    #[serde(rename = "gritql", alias = "grit_ql", alias = "grit")]
+    GritQl,
+    #[serde(alias = "git_apply", alias = "diff")]
     Patch,
 }
 
@@ -35,14 +40,22 @@ pub struct PatchSet {
     pub last_run_ts: Option<String>,
 }
 
+/// `version` and `generated_by` default so registries written by the v2
+/// binary, which has neither, load here too.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PatchRegistry {
+    #[serde(default = "default_version")]
     pub version: u32,
+    #[serde(default)]
     pub generated_by: String,
     #[serde(default)]
     pub patch_sets: Vec<PatchSet>,
 }
 
+fn default_version() -> u32 {
+    1
+}
+
 impl PatchRegistry {
     pub fn load_or_init(cfg: &Config, root: &Path) -> Result<Self> {
         let path = cfg.registry_path(root);
@@ -86,41 +99,158 @@ impl PatchRegistry {
         self.patch_sets.iter().find(|p| p.id == id)
     }
 
-    pub fn update_after_run(
-        &mut self,
-        id: &str,
-        commit: &str,
-        match_count: Option<u32>,
-        status: &str,
-    ) {
+    /// The v2 registry's stats. This registry keeps only the latest run, so
+    /// "never applied" means never run and degraded sets count as zero matches.
+    pub fn stats(&self) -> RegistryStats {
+        let sets = &self.patch_sets;
+        let enabled = sets.iter().filter(|p| p.enabled).count();
+        RegistryStats {
+            total: sets.len(),
+            enabled,
+            disabled: sets.len() - enabled,
+            zero_matches: sets
+                .iter()
+                .filter(|p| p.last_match_count == Some(0))
+                .count(),
+            failed: sets
+                .iter()
+                .filter(|p| {
+                    p.last_status
+                        .as_deref()
+                        .is_some_and(|s| s.starts_with("failed"))
+                })
+                .count(),
+            never_applied: sets.iter().filter(|p| p.last_run_ts.is_none()).count(),
+            oldest_last_applied_at: sets
+                .iter()
+                .filter_map(|p| p.last_run_ts.as_deref()?.parse().ok())
+                .min(),
+        }
+    }
+
+    /// Records a run's outcome; `last_status` is the rendered outcome, or
+    /// `degraded: ...` when a set that used to match now matches nothing.
+    pub fn update_after_run(&mut self, id: &str, commit: &str, outcome: &EngineOutcome) {
         let now = OffsetDateTime::now_utc()
             .format(&Rfc3339)
             .unwrap_or_else(|_| "unknown".to_string());
         if let Some(patch) = self.get_mut(id) {
             let previous = patch.last_match_count;
             patch.last_applied_commit = Some(commit.to_string());
-            patch.last_match_count = match_count;
+            patch.last_match_count = outcome.matches();
             patch.last_run_ts = Some(now);
 
-            let computed_status = if let Some(count) = match_count {
-                if count == 0 {
-                    if let Some(prev) = previous {
-                        if prev > 0 {
-                            format!("degraded: 0 matches (previously {prev})")
-                        } else {
-                            "no-matches".to_string()
-                        }
-                    } else {
-                        "no-matches".to_string()
-                    }
-                } else {
-                    format!("applied: {count} matches")
+            let status = match (outcome, previous) {
+                (EngineOutcome::AlreadyApplied, _) => outcome.to_string(),
+                (_, Some(prev)) if prev > 0 && outcome.matches() == Some(0) => {
+                    format!("degraded: 0 matches (previously {prev})")
                 }
-            } else {
-                status.to_string()
+                _ => outcome.to_string(),
             };
-
-            patch.last_status = Some(computed_status);
+            patch.last_status = Some(status);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_the_registry_written_by_init() {
+        let tmp = tempfile::tempdir().unwrap();
+        let workspace = camino::Utf8Path::from_path(tmp.path()).unwrap();
+        codex_core::init_workspace(&codex_core::InitOptions {
+            workspace: workspace.to_path_buf(),
+            vendor_url: None,
+            force: false,
+        })
+        .unwrap();
+        let cfg = Config::load(tmp.path()).unwrap();
+        let registry = PatchRegistry::load_or_init(&cfg, tmp.path()).unwrap();
+        assert_eq!(registry.version, 1);
+        assert!(registry.patch_sets.is_empty());
+    }
+
+    #[test]
+    fn loads_the_registry_written_by_doctor_fix() {
+        let tmp = tempfile::tempdir().unwrap();
+        let workspace = camino::Utf8Path::from_path(tmp.path()).unwrap();
+        std::fs::write(
+            tmp.path().join("codex-forksmith.toml"),
+            codex_core::DEFAULT_CONFIG,
+        )
+        .unwrap();
+        codex_core::fix_workspace(workspace).unwrap();
+        let cfg = Config::load(tmp.path()).unwrap();
+        let registry = PatchRegistry::load_or_init(&cfg, tmp.path()).unwrap();
+        assert!(tmp.path().join("patch-registry/registry.json").exists());
+        assert!(registry.patch_sets.is_empty());
+    }
+
+    #[test]
+    fn stats_match_the_v2_registry_shape() {
+        let registry: PatchRegistry = serde_json::from_str(
+            r#"{"patch_sets": [
+                {"id": "a", "description": "", "engine": "patch", "enabled": true, "rules": [],
+                 "last_match_count": 0, "last_status": "degraded: 0 matches (previously 2)",
+                 "last_run_ts": "2026-02-01T00:00:00Z"},
+                {"id": "b", "description": "", "engine": "patch", "enabled": true, "rules": [],
+                 "last_status": "failed: boom", "last_run_ts": "2026-01-01T00:00:00Z"},
+                {"id": "c", "description": "", "engine": "patch", "enabled": false, "rules": []}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            registry.stats(),
+            RegistryStats {
+                total: 3,
+                enabled: 2,
+                disabled: 1,
+                zero_matches: 1,
+                failed: 1,
+                never_applied: 1,
+                oldest_last_applied_at: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+            }
+        );
+    }
+
+    #[test]
+    fn update_after_run_renders_outcomes_and_flags_degraded_sets() {
+        let mut registry: PatchRegistry = serde_json::from_str(
+            r#"{"version": 1, "generated_by": "test", "patch_sets": [
+                {"id": "a", "description": "", "engine": "patch", "enabled": true, "rules": []}
+            ]}"#,
+        )
+        .unwrap();
+        let mut run = |outcome: EngineOutcome| {
+            registry.update_after_run("a", "abc", &outcome);
+            let set = registry.get("a").unwrap();
+            (set.last_status.clone().unwrap(), set.last_match_count)
+        };
+
+        assert_eq!(
+            run(EngineOutcome::Applied { matches: 3 }),
+            ("applied: 3 matches".to_string(), Some(3))
+        );
+        assert_eq!(
+            run(EngineOutcome::AlreadyApplied),
+            ("already-applied".to_string(), Some(0))
+        );
+        assert_eq!(
+            run(EngineOutcome::NoMatches),
+            ("no-matches".to_string(), Some(0))
+        );
+        run(EngineOutcome::DryRun { matches: 2 });
+        assert_eq!(
+            run(EngineOutcome::NoMatches),
+            ("degraded: 0 matches (previously 2)".to_string(), Some(0))
+        );
+        assert_eq!(
+            run(EngineOutcome::Failed {
+                error: "boom".into()
+            }),
+            ("failed: boom".to_string(), None)
+        );
+    }
+}
@@ -1,49 +1,94 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, IsTerminal, Write};
 use std::path::Path;
 
 use crate::config::{Config, ForkConfig};
-use crate::engines;
+use crate::engines::{self, utf8_path, EngineOutcome, EngineResult};
+use crate::legacy_patches::{apply_legacy_patches, LegacyPatchReport, LegacyPatchStatus};
 use crate::process::{
     cargo_build_release, git_current_branch, git_divergence, git_fetch_remote, git_head_commit,
-    git_is_clean, git_merge_abort, git_merge_ff_only, git_merge_with_strategy, git_reset_to_branch,
-    git_stash_pop, git_stash_push,
+    git_is_clean, git_merge_abort, git_merge_continue, git_merge_ff_only, git_merge_with_strategy,
+    git_reset_to_branch, git_reset_to_rev, git_stash_pop, git_stash_push, git_status_porcelain,
+    git_tree_state, git_unmerged_paths,
 };
-use crate::registry::{PatchRegistry, PatchSet};
-use anyhow::{anyhow, Result};
+use crate::registry::{EngineKind, PatchRegistry, PatchSet};
+use anyhow::{anyhow, Context, Result};
+use codex_core::table::{self, RowStyle, Table};
 use serde::Serialize;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct UpdateOptions {
     pub dry_run: bool,
     pub skip_build: bool,
-    pub emit_json: bool,
-}
-
-impl UpdateOptions {
-    pub fn new(dry_run: bool, skip_build: bool, emit_json: bool) -> Self {
-        Self {
-            dry_run,
-            skip_build,
-            emit_json,
-        }
-    }
+    /// Pin the vendor to this commit or tag; overrides `[vendor] rev`.
+    pub rev: Option<String>,
+    /// Hard-reset the vendor even when it has uncommitted changes.
+    pub force: bool,
+    /// Apply only these patch-set ids; empty means all enabled sets.
+    pub only: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
-struct PatchReport {
-    id: String,
-    engine: String,
-    status: String,
-    matches: Option<u32>,
+pub struct PatchReport {
+    pub id: String,
+    pub engine: String,
+    pub status: String,
+    pub matches: Option<u32>,
+    pub hunks: Option<u32>,
+    pub lines_added: Option<u32>,
+    pub lines_removed: Option<u32>,
 }
 
+/// Result of updating one vendor. Progress goes to stderr while the run is
+/// in flight; rendering the result is left to [`print_summary`].
 #[derive(Debug, Default, Serialize)]
 pub struct UpdateSummary {
-    dry_run: bool,
-    vendor_head_before: Option<String>,
-    vendor_head_after: Option<String>,
-    patch_reports: Vec<PatchReport>,
-    warnings: Vec<String>,
-    build_status: Option<String>,
+    /// Vendor id from the config; the JSON output keys on it instead.
+    #[serde(skip)]
+    pub vendor: String,
+    pub dry_run: bool,
+    pub vendor_head_before: Option<String>,
+    pub vendor_head_after: Option<String>,
+    pub legacy_patches: Vec<LegacyPatchReport>,
+    pub patch_reports: Vec<PatchReport>,
+    pub warnings: Vec<String>,
+    pub build_status: Option<String>,
+    /// Patch sets whose failure should fail the run.
+    #[serde(skip)]
+    pub failed: Vec<String>,
+}
+
+impl UpdateSummary {
+    /// `Err` when a patch set or the build failed, so automation sees a
+    /// nonzero exit.
+    pub fn outcome(&self) -> Result<()> {
+        if !self.failed.is_empty() {
+            return Err(anyhow!("patch-sets failed: {}", self.failed.join(", ")));
+        }
+        if self
+            .build_status
+            .as_deref()
+            .is_some_and(|s| s.starts_with("failed"))
+        {
+            return Err(anyhow!("build failed"));
+        }
+        Ok(())
+    }
+}
+
+/// Folds the per-vendor outcomes into one error naming each failed vendor.
+pub fn update_outcome(summaries: &[UpdateSummary]) -> Result<()> {
+    if let [summary] = summaries {
+        return summary.outcome();
+    }
+    let errors: Vec<String> = summaries
+        .iter()
+        .filter_map(|s| s.outcome().err().map(|err| format!("{}: {err}", s.vendor)))
+        .collect();
+    if !errors.is_empty() {
+        return Err(anyhow!("{}", errors.join("; ")));
+    }
+    Ok(())
 }
 
 #[derive(Debug, Serialize)]
@@ -75,29 +120,85 @@ pub fn run_health(root: &Path) -> Result<()> {
     Ok(())
 }
 
-pub fn run_list_patches(root: &Path) -> Result<()> {
+#[derive(Debug, Serialize)]
+struct PatchListEntry<'a> {
+    id: &'a str,
+    engine: &'a EngineKind,
+    enabled: bool,
+    tags: &'a [String],
+    last_status: Option<&'a str>,
+}
+
+pub fn run_list_patches(root: &Path, json: bool, wide: bool) -> Result<()> {
     let cfg = Config::load(root)?;
     let registry = PatchRegistry::load_or_init(&cfg, root)?;
+    if json {
+        let entries: Vec<PatchListEntry> = registry
+            .list()
+            .iter()
+            .map(|patch| PatchListEntry {
+                id: &patch.id,
+                engine: &patch.engine,
+                enabled: patch.enabled,
+                tags: &patch.tags,
+                last_status: patch.last_status.as_deref(),
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+    let mut headers = vec!["ID", "ENGINE", "ENABLED", "LAST STATUS", "MATCHES", "TAGS"];
+    if wide {
+        headers.extend(["LAST RUN", "LAST COMMIT", "DESCRIPTION"]);
+    }
+    let mut table = Table::new(headers);
     for patch in registry.list() {
-        println!(
-            "- {:<32} engine={:?} enabled={} tags={}",
-            patch.id,
-            patch.engine,
-            patch.enabled,
+        let status = patch.last_status.as_deref().unwrap_or("-");
+        let mut cells = vec![
+            patch.id.clone(),
+            format!("{:?}", patch.engine),
+            patch.enabled.to_string(),
+            status.to_string(),
+            patch
+                .last_match_count
+                .map_or_else(|| "-".into(), |n| n.to_string()),
             if patch.tags.is_empty() {
                 "-".into()
             } else {
                 patch.tags.join(", ")
-            }
-        );
+            },
+        ];
+        if wide {
+            cells.push(patch.last_run_ts.clone().unwrap_or_else(|| "-".into()));
+            cells.push(
+                patch
+                    .last_applied_commit
+                    .as_deref()
+                    .map_or_else(|| "-".into(), |c| c.chars().take(12).collect()),
+            );
+            cells.push(patch.description.clone());
+        }
+        let style = if !patch.enabled {
+            RowStyle::Dim
+        } else if status.starts_with("failed") || status.starts_with("degraded") {
+            RowStyle::Red
+        } else {
+            RowStyle::Plain
+        };
+        table.row(cells, style);
     }
+    print!("{}", table.render(table::color_enabled()));
     Ok(())
 }
 
-pub fn run_explain_patch(root: &Path, id: &str) -> Result<()> {
+pub fn run_explain_patch(root: &Path, id: &str, json: bool) -> Result<()> {
     let cfg = Config::load(root)?;
     let registry = PatchRegistry::load_or_init(&cfg, root)?;
     if let Some(p) = registry.get(id) {
+        if json {
+            println!("{}", serde_json::to_string_pretty(p)?);
+            return Ok(());
+        }
         println!("Patch-set: {}", p.id);
         println!("  description : {}", p.description);
         println!("  engine      : {:?}", p.engine);
@@ -127,6 +228,17 @@ pub fn run_explain_patch(root: &Path, id: &str) -> Result<()> {
     Ok(())
 }
 
+pub fn run_registry_stats(root: &Path, json: bool) -> Result<()> {
+    let cfg = Config::load(root)?;
+    let stats = PatchRegistry::load_or_init(&cfg, root)?.stats();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        println!("{stats}");
+    }
+    Ok(())
+}
+
 pub fn run_toggle_patch(root: &Path, id: &str, enabled: bool) -> Result<()> {
     let cfg = Config::load(root)?;
     let mut registry = PatchRegistry::load_or_init(&cfg, root)?;
@@ -139,8 +251,173 @@ pub fn run_toggle_patch(root: &Path, id: &str, enabled: bool) -> Result<()> {
     Ok(())
 }
 
-pub fn run_update(root: &Path, opts: UpdateOptions) -> Result<()> {
+pub fn run_unapply_patch(root: &Path, id: &str) -> Result<()> {
     let cfg = Config::load(root)?;
+    let vendor_dir = cfg.vendor_dir(root);
+    let registry = PatchRegistry::load_or_init(&cfg, root)?;
+    let patch = registry
+        .get(id)
+        .ok_or_else(|| anyhow!("No patch-set with id {id}"))?;
+    let result = engines::apply_patchset(patch, &cfg, &vendor_dir, false, true)?;
+    println!(
+        "Reversed {} ({} hunk(s) removed)",
+        id,
+        result.hunks.unwrap_or(0)
+    );
+    Ok(())
+}
+
+/// Updates every configured vendor and returns one summary per vendor, in
+/// config order. Patch-set and build failures are reported through
+/// [`update_outcome`] rather than as an `Err` here.
+pub fn run_update(root: &Path, opts: UpdateOptions) -> Result<Vec<UpdateSummary>> {
+    let cfg = Config::load(root)?;
+    ensure_known_ids(root, &cfg, &opts.only)?;
+    if cfg.vendors.len() == 1 {
+        let mut summary = update_vendor(root, &cfg, &opts, true)?;
+        summary.vendor = cfg.vendors[0].id.clone();
+        return Ok(vec![summary]);
+    }
+
+    // `--rev` only makes sense for one repo, so it pins the primary vendor.
+    let mut summaries = Vec::with_capacity(cfg.vendors.len());
+    for vendor in &cfg.vendors {
+        let primary = cfg.is_primary(vendor);
+        let vendor_opts = UpdateOptions {
+            rev: opts.rev.clone().filter(|_| primary),
+            ..opts.clone()
+        };
+        eprintln!("== vendor {} ==", vendor.id);
+        let mut summary = update_vendor(root, &cfg.for_vendor(vendor), &vendor_opts, primary)
+            .with_context(|| format!("updating vendor {}", vendor.id))?;
+        summary.vendor = vendor.id.clone();
+        summaries.push(summary);
+    }
+    Ok(summaries)
+}
+
+/// Dry-runs every enabled patch-set against each vendor as checked out: no
+/// reset, no legacy patches, no registry write, no build. Errors if a vendor
+/// tree differs afterwards.
+pub fn run_preview(root: &Path) -> Result<Vec<UpdateSummary>> {
+    let cfg = Config::load(root)?;
+    let mut summaries = Vec::with_capacity(cfg.vendors.len());
+    for vendor in &cfg.vendors {
+        let vendor_cfg = if cfg.vendors.len() == 1 {
+            cfg.clone()
+        } else {
+            cfg.for_vendor(vendor)
+        };
+        let mut summary = preview_vendor(root, &vendor_cfg)
+            .with_context(|| format!("previewing vendor {}", vendor.id))?;
+        summary.vendor = vendor.id.clone();
+        summaries.push(summary);
+    }
+    Ok(summaries)
+}
+
+fn preview_vendor(root: &Path, cfg: &Config) -> Result<UpdateSummary> {
+    let vendor_dir = cfg.vendor_dir(root);
+    if !vendor_dir.exists() {
+        return Err(anyhow!(
+            "Vendor directory {} does not exist",
+            vendor_dir.display()
+        ));
+    }
+    let before = git_tree_state(&vendor_dir)?;
+    let head = git_head_commit(&vendor_dir).ok();
+    let mut summary = UpdateSummary {
+        dry_run: true,
+        vendor_head_before: head.clone(),
+        vendor_head_after: head,
+        build_status: Some("skipped (preview)".into()),
+        ..Default::default()
+    };
+    let registry = PatchRegistry::load_or_init(cfg, root)?;
+    for patch in &registry.patch_sets {
+        if !patch.enabled {
+            record_patch(&mut summary, patch, None, "skipped (disabled)");
+            continue;
+        }
+        match engines::apply_patchset(patch, cfg, &vendor_dir, true, false) {
+            Ok(result) => record_result(&mut summary, patch, &result),
+            Err(err) => record_patch(&mut summary, patch, None, format!("failed: {err:#}")),
+        }
+    }
+    if git_tree_state(&vendor_dir)? != before {
+        return Err(anyhow!(
+            "vendor tree {} changed during preview; inspect `git status` there (this is a bug)",
+            vendor_dir.display()
+        ));
+    }
+    Ok(summary)
+}
+
+/// Fails before any vendor is touched if `--only` names an id that no
+/// vendor's registry knows.
+fn ensure_known_ids(root: &Path, cfg: &Config, only: &[String]) -> Result<()> {
+    if only.is_empty() {
+        return Ok(());
+    }
+    let mut known = BTreeSet::new();
+    if cfg.vendors.len() == 1 {
+        let registry = PatchRegistry::load_or_init(cfg, root)?;
+        known.extend(registry.patch_sets.into_iter().map(|p| p.id));
+    } else {
+        for vendor in &cfg.vendors {
+            let registry = PatchRegistry::load_or_init(&cfg.for_vendor(vendor), root)?;
+            known.extend(registry.patch_sets.into_iter().map(|p| p.id));
+        }
+    }
+    let unknown: Vec<&str> = only
+        .iter()
+        .filter(|id| !known.contains(*id))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        return Err(anyhow!(
+            "--only names unknown patch-sets: {}",
+            unknown.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Renders update results on stdout: a single summary as-is, several keyed
+/// by vendor id.
+pub fn print_summary(summaries: &[UpdateSummary], json: bool) -> Result<()> {
+    if let [summary] = summaries {
+        if json {
+            println!("{}", serde_json::to_string_pretty(summary)?);
+        } else {
+            print_vendor_summary(summary);
+        }
+        return Ok(());
+    }
+    let by_vendor: BTreeMap<&str, &UpdateSummary> =
+        summaries.iter().map(|s| (s.vendor.as_str(), s)).collect();
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&serde_json::json!({ "vendors": by_vendor }))?
+        );
+    } else {
+        for (id, summary) in by_vendor {
+            println!("\nVendor {id}:");
+            print_vendor_summary(summary);
+        }
+    }
+    Ok(())
+}
+
+/// Runs the reset/patch/build pipeline for the vendor `cfg` points at. Only
+/// the primary vendor gets the legacy patches.
+fn update_vendor(
+    root: &Path,
+    cfg: &Config,
+    opts: &UpdateOptions,
+    primary: bool,
+) -> Result<UpdateSummary> {
     let vendor_dir = cfg.vendor_dir(root);
     if !vendor_dir.exists() {
         return Err(anyhow!(
@@ -155,65 +432,143 @@ pub fn run_update(root: &Path, opts: UpdateOptions) -> Result<()> {
         ..Default::default()
     };
 
-    println!("codex-forksmith update");
-    println!("  workspace root: {}", root.display());
-    println!("  vendor dir    : {}", vendor_dir.display());
-    println!("  dry-run       : {}", opts.dry_run);
+    eprintln!("codex-forksmith update");
+    eprintln!("  workspace root: {}", root.display());
+    eprintln!("  vendor dir    : {}", vendor_dir.display());
+    eprintln!("  dry-run       : {}", opts.dry_run);
 
     if cfg.fork.enabled {
-        println!(
+        eprintln!(
             "Step 1/4: Fork sync checks (local {} -> {}, upstream {} -> {})...",
             cfg.fork.local_remote,
             cfg.fork.local_branch,
             cfg.fork.upstream_remote,
             cfg.fork.upstream_branch
         );
-        let mut fork_warnings = ensure_fork_state(&cfg, &vendor_dir)?;
+        let mut fork_warnings = ensure_fork_state(cfg, &vendor_dir)?;
         summary.warnings.append(&mut fork_warnings);
+    } else if let Some(rev) = opts.rev.as_ref().or(cfg.vendor_rev.as_ref()) {
+        eprintln!("Step 1/4: Reset vendor to pinned revision {rev}...");
+        confirm_hard_reset(&vendor_dir, opts.force)?;
+        git_reset_to_rev(&vendor_dir, rev)?;
     } else {
-        println!("Step 1/4: Reset vendor to origin/{}...", cfg.vendor_branch);
+        eprintln!("Step 1/4: Reset vendor to origin/{}...", cfg.vendor_branch);
+        confirm_hard_reset(&vendor_dir, opts.force)?;
         git_reset_to_branch(&vendor_dir, &cfg.vendor_branch)?;
     }
     let commit = git_head_commit(&vendor_dir)?;
     summary.vendor_head_after = Some(commit.clone());
 
-    println!("Step 2/4: Loading registry...");
-    let mut registry = PatchRegistry::load_or_init(&cfg, root)?;
-    println!("  {} patch-sets registered", registry.patch_sets.len());
-
-    println!("Step 3/4: Applying patch-sets...");
+    eprintln!("Step 2/4: Loading registry...");
+    let mut registry = PatchRegistry::load_or_init(cfg, root)?;
+    eprintln!("  {} patch-sets registered", registry.patch_sets.len());
+
+    eprintln!("Step 3/4: Applying patch-sets...");
+    let legacy_dir = cfg.legacy_patches_dir(root);
+    if primary && opts.dry_run {
+        eprintln!("  legacy patches skipped (dry-run)");
+    } else if primary {
+        let reports = apply_legacy_patches(&utf8_path(&vendor_dir)?, &utf8_path(&legacy_dir)?)?;
+        let failed: Vec<&str> = reports
+            .iter()
+            .filter(|r| r.status == LegacyPatchStatus::Failed)
+            .map(|r| r.name.as_str())
+            .collect();
+        if !failed.is_empty() {
+            print_legacy_reports(&reports);
+            return Err(anyhow!(
+                "legacy patches failed to apply: {}",
+                failed.join(", ")
+            ));
+        }
+        summary.legacy_patches = reports;
+    }
     for patch in registry.patch_sets.clone() {
+        if !opts.only.is_empty() && !opts.only.contains(&patch.id) {
+            record_patch(&mut summary, &patch, None, "skipped (not selected)");
+            continue;
+        }
         if !patch.enabled {
             record_patch(&mut summary, &patch, None, "skipped (disabled)");
             continue;
         }
-        let result = engines::apply_patchset(&patch, &cfg, &vendor_dir, opts.dry_run)?;
-        record_patch(&mut summary, &patch, result.matches, result.status.clone());
-        registry.update_after_run(&patch.id, &commit, result.matches, &result.status);
+        // A missing rule file is fatal even in dry-run; other engine errors
+        // only fail a real run.
+        let missing: Vec<String> = patch
+            .rules
+            .iter()
+            .filter(|rule| !engines::rule_path(&vendor_dir, rule).exists())
+            .cloned()
+            .collect();
+        // Checked before the engine runs so a set with a missing rule never
+        // half-applies its other rules.
+        let applied = if missing.is_empty() {
+            engines::apply_patchset(&patch, cfg, &vendor_dir, opts.dry_run, false)
+        } else {
+            Err(anyhow!("missing rule files: {}", missing.join(", ")))
+        };
+        match applied {
+            Ok(result) => {
+                record_result(&mut summary, &patch, &result);
+                registry.update_after_run(&patch.id, &commit, &result.outcome);
+            }
+            Err(err) => {
+                let outcome = EngineOutcome::Failed {
+                    error: format!("{err:#}"),
+                };
+                record_patch(&mut summary, &patch, None, outcome.to_string());
+                registry.update_after_run(&patch.id, &commit, &outcome);
+                if !opts.dry_run || !missing.is_empty() {
+                    summary.failed.push(patch.id.clone());
+                }
+            }
+        }
     }
 
-    registry.save(&cfg, root)?;
+    registry.save(cfg, root)?;
 
-    println!("Step 4/4: Build phase...");
+    eprintln!("Step 4/4: Build phase...");
     if opts.dry_run {
         summary.build_status = Some("skipped (dry-run)".into());
-        println!("  build skipped (dry-run)");
+        eprintln!("  build skipped (dry-run)");
     } else if opts.skip_build {
         summary.build_status = Some("skipped (--skip-build)".into());
-        println!("  build skipped (--skip-build)");
+        eprintln!("  build skipped (--skip-build)");
+    } else if let Err(err) = cargo_build_release(&vendor_dir) {
+        summary.build_status = Some(format!("failed: {err:#}"));
+        eprintln!("  build failed");
     } else {
-        cargo_build_release(&vendor_dir)?;
         summary.build_status = Some("succeeded".into());
-        println!("  build succeeded");
+        eprintln!("  build succeeded");
     }
 
-    if opts.emit_json {
-        println!("{}", serde_json::to_string_pretty(&summary)?);
-    } else {
-        print_summary(&summary);
-    }
+    Ok(summary)
+}
 
-    Ok(())
+/// Refuses to hard-reset a dirty vendor tree unless `force` is set or the
+/// user confirms interactively; prints what would be discarded either way.
+fn confirm_hard_reset(vendor_dir: &Path, force: bool) -> Result<()> {
+    let status = git_status_porcelain(vendor_dir)?;
+    if status.trim().is_empty() || force {
+        return Ok(());
+    }
+    eprintln!("  vendor tree has uncommitted changes that a hard reset would discard:");
+    for line in status.lines() {
+        eprintln!("    {line}");
+    }
+    if io::stdin().is_terminal() {
+        eprint!("  Discard these changes? [y/N] ");
+        io::stderr().flush()?;
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer)?;
+        if matches!(answer.trim(), "y" | "Y" | "yes") {
+            return Ok(());
+        }
+    }
+    Err(anyhow!(
+        "refusing to reset dirty vendor tree {}; commit or stash the changes, or re-run with --force",
+        vendor_dir.display()
+    ))
 }
 
 fn record_patch(
@@ -227,21 +582,65 @@ fn record_patch(
         engine: format!("{:?}", patch.engine),
         status: status.into(),
         matches,
+        hunks: None,
+        lines_added: None,
+        lines_removed: None,
+    });
+}
+
+fn record_result(summary: &mut UpdateSummary, patch: &PatchSet, result: &EngineResult) {
+    summary.patch_reports.push(PatchReport {
+        id: patch.id.clone(),
+        engine: format!("{:?}", patch.engine),
+        status: result.status(),
+        matches: result.outcome.matches(),
+        hunks: result.hunks,
+        lines_added: result.lines_changed.map(|(added, _)| added),
+        lines_removed: result.lines_changed.map(|(_, removed)| removed),
     });
 }
 
-fn print_summary(summary: &UpdateSummary) {
+fn print_legacy_reports(reports: &[LegacyPatchReport]) {
+    if reports.is_empty() {
+        return;
+    }
+    println!("  legacy patches:");
+    for report in reports {
+        let status = match (report.status, report.fallback) {
+            (LegacyPatchStatus::Applied, Some(via)) => format!("applied (via {via})"),
+            (LegacyPatchStatus::Applied, None) => "applied".to_string(),
+            (LegacyPatchStatus::Skipped, _) => "skipped (already applied)".to_string(),
+            (LegacyPatchStatus::Failed, _) => "FAILED".to_string(),
+        };
+        println!("    - {:<40} {status}", report.name);
+        if let Some(stderr) = &report.stderr {
+            for line in stderr.lines() {
+                println!("        {line}");
+            }
+        }
+    }
+}
+
+fn print_vendor_summary(summary: &UpdateSummary) {
     println!("\nSummary:");
     println!("  vendor before : {:?}", summary.vendor_head_before);
     println!("  vendor after  : {:?}", summary.vendor_head_after);
     println!("  dry-run       : {}", summary.dry_run);
+    print_legacy_reports(&summary.legacy_patches);
     if !summary.patch_reports.is_empty() {
         println!("  patches:");
         for report in &summary.patch_reports {
-            println!(
+            let mut line = format!(
                 "    - {:<32} {:<12} matches={:?} status={}",
                 report.id, report.engine, report.matches, report.status
             );
+            if let Some(hunks) = report.hunks {
+                line.push_str(&format!(" hunks={hunks}"));
+            }
+            if let (Some(added), Some(removed)) = (report.lines_added, report.lines_removed) {
+                line.push_str(&format!(" +{added} -{removed}"));
+            }
+            println!("{line}");
         }
     }
     if !summary.warnings.is_empty() {
@@ -320,10 +719,21 @@ fn auto_merge_reference(
                     Ok(())
                 }
                 Err(fallback_err) => {
-                    let _ = git_merge_abort(vendor_dir);
-                    Err(anyhow!(
-                        "Auto-merge fallback failed: {fallback_err} (fast-forward error: {ff_err})."
-                    ))
+                    match resolve_with_hook(vendor_dir, fork_cfg, label, warnings) {
+                        Ok(true) => Ok(()),
+                        Ok(false) => {
+                            let _ = git_merge_abort(vendor_dir);
+                            Err(anyhow!(
+                                "Auto-merge fallback failed: {fallback_err} (fast-forward error: {ff_err})."
+                            ))
+                        }
+                        Err(hook_err) => {
+                            let _ = git_merge_abort(vendor_dir);
+                            Err(anyhow!(
+                                "Auto-merge fallback failed: {fallback_err}; on_conflict_cmd did not resolve it: {hook_err:#}"
+                            ))
+                        }
+                    }
                 }
             }
         }
@@ -340,6 +750,49 @@ fn auto_merge_reference(
     merge_status
 }
 
+/// Runs `fork.on_conflict_cmd` when a merge left unmerged paths and commits
+/// the merge if the hook resolved all of them. `Ok(false)` means there was
+/// no hook or nothing unmerged to hand it.
+fn resolve_with_hook(
+    vendor_dir: &Path,
+    fork_cfg: &ForkConfig,
+    label: &str,
+    warnings: &mut Vec<String>,
+) -> Result<bool> {
+    let Some(hook) = &fork_cfg.on_conflict_cmd else {
+        return Ok(false);
+    };
+    let conflicted = git_unmerged_paths(vendor_dir)?;
+    if conflicted.is_empty() {
+        return Ok(false);
+    }
+    let out = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(hook)
+        .current_dir(vendor_dir)
+        .output()
+        .with_context(|| format!("Failed to spawn on_conflict_cmd {hook}"))?;
+    for stream in [&out.stdout, &out.stderr] {
+        let text = String::from_utf8_lossy(stream);
+        if !text.trim().is_empty() {
+            warnings.push(format!("on_conflict_cmd ({label}): {}", text.trim()));
+        }
+    }
+    if !out.status.success() {
+        return Err(anyhow!("{hook} exited with {}", out.status));
+    }
+    let remaining = git_unmerged_paths(vendor_dir)?;
+    if !remaining.is_empty() {
+        return Err(anyhow!("still unmerged: {}", remaining.join(", ")));
+    }
+    git_merge_continue(vendor_dir)?;
+    warnings.push(format!(
+        "Merged {label} after on_conflict_cmd resolved {} conflicted path(s).",
+        conflicted.len()
+    ));
+    Ok(true)
+}
+
 fn ensure_fork_state(cfg: &Config, vendor_dir: &Path) -> Result<Vec<String>> {
     let fork_cfg = &cfg.fork;
     let mut warnings = Vec::new();
@@ -441,3 +894,120 @@ fn ensure_fork_state(cfg: &Config, vendor_dir: &Path) -> Result<Vec<String>> {
 
     Ok(warnings)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    /// A repo stopped mid-merge with `file.txt` conflicted.
+    fn conflicted_repo() -> tempfile::TempDir {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        git(dir, &["init", "--quiet", "--initial-branch=main"]);
+        git(dir, &["config", "user.name", "test"]);
+        git(dir, &["config", "user.email", "test@example.com"]);
+        std::fs::write(dir.join("file.txt"), "base\n").unwrap();
+        git(dir, &["add", "."]);
+        git(dir, &["commit", "--quiet", "-m", "base"]);
+        git(dir, &["checkout", "--quiet", "-b", "upstream"]);
+        std::fs::write(dir.join("file.txt"), "upstream\n").unwrap();
+        git(dir, &["commit", "--quiet", "-am", "upstream"]);
+        git(dir, &["checkout", "--quiet", "main"]);
+        std::fs::write(dir.join("file.txt"), "local\n").unwrap();
+        git(dir, &["commit", "--quiet", "-am", "local"]);
+        let merged = Command::new("git")
+            .args(["merge", "--quiet", "upstream"])
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(!merged.status.success(), "merge should conflict");
+        tmp
+    }
+
+    fn fork_config(on_conflict_cmd: &str) -> ForkConfig {
+        ForkConfig {
+            enabled: true,
+            upstream_remote: "upstream".into(),
+            upstream_branch: "main".into(),
+            local_remote: "origin".into(),
+            local_branch: "main".into(),
+            require_clean_worktree: true,
+            abort_on_divergence: false,
+            auto_merge_upstream: true,
+            auto_stash_before_merge: false,
+            auto_merge_local: false,
+            auto_route_upstream: false,
+            merge_strategy: None,
+            merge_strategy_option: None,
+            on_conflict_cmd: Some(on_conflict_cmd.into()),
+            silence_local_ahead_warning: false,
+        }
+    }
+
+    #[test]
+    fn hook_that_resolves_commits_the_merge() {
+        let repo = conflicted_repo();
+        let cfg =
+            fork_config("echo fixed && git checkout --quiet --theirs file.txt && git add file.txt");
+        let mut warnings = Vec::new();
+
+        assert!(resolve_with_hook(repo.path(), &cfg, "upstream", &mut warnings).unwrap());
+        assert!(git_unmerged_paths(repo.path()).unwrap().is_empty());
+        assert!(git_is_clean(repo.path()).unwrap());
+        let parents = Command::new("git")
+            .args(["rev-list", "--parents", "-n1", "HEAD"])
+            .current_dir(repo.path())
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&parents.stdout)
+                .split_whitespace()
+                .count(),
+            3
+        );
+        assert_eq!(
+            std::fs::read_to_string(repo.path().join("file.txt")).unwrap(),
+            "upstream\n"
+        );
+        assert_eq!(
+            warnings,
+            [
+                "on_conflict_cmd (upstream): fixed",
+                "Merged upstream after on_conflict_cmd resolved 1 conflicted path(s).",
+            ]
+        );
+    }
+
+    #[test]
+    fn hook_that_fails_is_an_error() {
+        let repo = conflicted_repo();
+        let cfg = fork_config("echo cannot resolve >&2; exit 3");
+        let mut warnings = Vec::new();
+
+        let err = resolve_with_hook(repo.path(), &cfg, "upstream", &mut warnings).unwrap_err();
+        assert!(err.to_string().contains("exited with"), "{err}");
+        assert_eq!(warnings, ["on_conflict_cmd (upstream): cannot resolve"]);
+        assert_eq!(git_unmerged_paths(repo.path()).unwrap(), ["file.txt"]);
+    }
+
+    #[test]
+    fn hook_that_leaves_conflicts_is_an_error() {
+        let repo = conflicted_repo();
+        let cfg = fork_config("true");
+        let mut warnings = Vec::new();
+
+        let err = resolve_with_hook(repo.path(), &cfg, "upstream", &mut warnings).unwrap_err();
+        assert_eq!(err.to_string(), "still unmerged: file.txt");
+        assert!(warnings.is_empty());
+    }
+}